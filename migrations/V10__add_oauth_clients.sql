@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS oauth_clients (
+    oauth_client_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    client_id text NOT NULL,
+    secret_hash text NOT NULL,
+    scopes text[] NOT NULL,
+    grant_types text[] NOT NULL,
+    enabled boolean NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    is_deleted boolean NOT NULL,
+    UNIQUE(client_id)
+);
+
+CREATE TABLE IF NOT EXISTS oauth_client_roles (
+    oauth_client_id int NOT NULL,
+    role_id int NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    is_deleted boolean NOT NULL,
+
+    CONSTRAINT fk_oauth_client FOREIGN KEY(oauth_client_id) REFERENCES oauth_clients(oauth_client_id),
+    CONSTRAINT fk_roles FOREIGN KEY(role_id) REFERENCES roles(role_id),
+
+    UNIQUE(oauth_client_id, role_id)
+);
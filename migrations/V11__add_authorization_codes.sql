@@ -0,0 +1,23 @@
+ALTER TABLE oauth_clients ADD COLUMN IF NOT EXISTS redirect_uris text[] NOT NULL DEFAULT '{}';
+-- public clients (browser and mobile apps) can't keep a secret
+ALTER TABLE oauth_clients ALTER COLUMN secret_hash DROP NOT NULL;
+
+CREATE TABLE IF NOT EXISTS authorization_codes (
+    code_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    code_hash text NOT NULL,
+    oauth_client_id int NOT NULL,
+    user_id int NOT NULL,
+    redirect_uri text NOT NULL,
+    scope text NOT NULL,
+    code_challenge text NOT NULL,
+    session_id text NOT NULL,
+    token_version int NOT NULL,
+    is_used boolean NOT NULL,
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+
+    CONSTRAINT fk_oauth_client FOREIGN KEY(oauth_client_id) REFERENCES oauth_clients(oauth_client_id),
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(code_hash)
+);
@@ -0,0 +1,3 @@
+-- OpenID Connect claims of id_token issued on code exchange
+ALTER TABLE authorization_codes ADD COLUMN IF NOT EXISTS nonce text;
+ALTER TABLE authorization_codes ADD COLUMN IF NOT EXISTS auth_time timestamptz NOT NULL DEFAULT now();
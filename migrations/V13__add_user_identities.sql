@@ -0,0 +1,28 @@
+-- identities of users in upstream OpenID Connect providers
+CREATE TABLE IF NOT EXISTS user_identities (
+    identity_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id int NOT NULL,
+    provider text NOT NULL,
+    subject text NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_login_at timestamptz,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(provider, subject)
+);
+CREATE INDEX IF NOT EXISTS user_identities_user_id_idx ON user_identities(user_id);
+
+-- logins redirected to upstream provider and not finished yet
+CREATE TABLE IF NOT EXISTS federated_logins (
+    login_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    state_hash text NOT NULL,
+    provider text NOT NULL,
+    nonce text NOT NULL,
+    code_verifier text NOT NULL,
+    is_used boolean NOT NULL,
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+
+    UNIQUE(state_hash)
+);
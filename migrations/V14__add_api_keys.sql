@@ -0,0 +1,18 @@
+-- personal access tokens of users, NULL permissions means all permissions of the user
+CREATE TABLE IF NOT EXISTS api_keys (
+    api_key_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id int NOT NULL,
+    name text NOT NULL,
+    token_hash text NOT NULL,
+    permissions text[],
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz,
+    is_revoked boolean NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(token_hash)
+);
+-- name is unique among not revoked keys of the user
+CREATE UNIQUE INDEX IF NOT EXISTS api_keys_user_name_idx ON api_keys(user_id, name) WHERE is_revoked=FALSE;
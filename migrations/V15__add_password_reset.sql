@@ -0,0 +1,17 @@
+-- address for notifications, users without it can't reset password by themselves
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email text;
+
+-- single-use tokens of password reset, new token closes previous ones of the user
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    reset_token_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id int NOT NULL,
+    token_hash text NOT NULL,
+    is_used boolean NOT NULL,
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(token_hash)
+);
+CREATE INDEX IF NOT EXISTS password_reset_tokens_user_id_idx ON password_reset_tokens(user_id);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    token_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    token_hash text NOT NULL,
+    user_id int NOT NULL,
+    family_id text NOT NULL,
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    is_rotated boolean NOT NULL,
+    is_revoked boolean NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(token_hash)
+);
+CREATE INDEX IF NOT EXISTS refresh_tokens_family ON refresh_tokens (family_id);
@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS services (
+    service_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    client_id text NOT NULL,
+    secret_hash text NOT NULL,
+    enabled boolean NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    is_deleted boolean NOT NULL,
+    UNIQUE(client_id)
+);
+
+CREATE TABLE IF NOT EXISTS service_scopes (
+    service_id int NOT NULL,
+    scope text NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    is_deleted boolean NOT NULL,
+
+    CONSTRAINT fk_service FOREIGN KEY(service_id) REFERENCES services(service_id),
+
+    UNIQUE(service_id, scope)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE services ADD COLUMN IF NOT EXISTS cert_identity text;
+CREATE UNIQUE INDEX IF NOT EXISTS services_cert_identity_unique ON services (cert_identity);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS failed_sign_in_attempts (
+    attempt_id bigint GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    username text NOT NULL,
+    client_ip text,
+    attempted_at timestamptz NOT NULL,
+    cleared_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS failed_sign_in_attempts_username_idx
+    ON failed_sign_in_attempts (username, attempted_at);
+CREATE INDEX IF NOT EXISTS failed_sign_in_attempts_client_ip_idx
+    ON failed_sign_in_attempts (client_ip, attempted_at);
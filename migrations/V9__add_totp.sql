@@ -0,0 +1,38 @@
+CREATE TABLE IF NOT EXISTS user_totp (
+    user_id int PRIMARY KEY,
+    secret text NOT NULL,
+    is_confirmed boolean NOT NULL,
+    last_used_step bigint NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    code_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id int NOT NULL,
+    code_hash text NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(user_id, code_hash)
+);
+
+CREATE TABLE IF NOT EXISTS mfa_challenges (
+    challenge_id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    token_hash text NOT NULL,
+    user_id int NOT NULL,
+    username text NOT NULL,
+    token_version int NOT NULL,
+    failed_attempts int NOT NULL,
+    is_used boolean NOT NULL,
+    expired_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+
+    CONSTRAINT fk_user FOREIGN KEY(user_id) REFERENCES users(user_id),
+
+    UNIQUE(token_hash)
+);
@@ -1,44 +1,83 @@
+use crate::handlers::api::api_keys::{
+    api_keys_listing_handler, create_api_key_handler, revoke_api_key_handler,
+};
+use crate::handlers::api::federation::{federated_callback_handler, federated_login_handler};
+use crate::handlers::api::mfa::{confirm_totp_handler, enroll_totp_handler, verify_mfa_handler};
+use crate::handlers::api::oauth::{
+    authorize_handler, authorize_page_handler, oauth_token_handler, userinfo_handler,
+};
 use crate::handlers::api::permissions::handlers::{
     create_permission_handler, disable_permission_handler, get_permission_handler,
     permissions_listing_handler,
 };
 use crate::handlers::api::roles::handlers::{
     bind_member_with_role_handler, bind_permission_with_role_handler, create_role_handler,
-    disable_role_handler, get_role_handler, unbind_member_with_role_handler,
+    disable_role_handler, get_role_handler, roles_listing_handler, unbind_member_with_role_handler,
     unbind_permission_with_role_handler,
 };
 use crate::handlers::api::users::{
-    create_user_handler, delete_user_by_id, get_user_by_id, sign_in_user_handler,
-    validate_jwt_handler,
+    confirm_password_reset_handler, create_user_handler, delete_user_by_id, disable_user_handler,
+    enable_user_handler, get_user_by_id, refresh_token_handler, request_password_reset_handler,
+    revoke_user_sessions_handler, set_user_password_handler, sign_in_user_handler,
+    sign_out_handler, unlock_user_handler, users_listing_handler, validate_jwt_handler,
 };
 use crate::handlers::system::handlers::{ping_handler, ready_handler};
+use crate::handlers::well_known::handlers::{jwks_handler, openid_configuration_handler};
 
 use actix_web::web::ServiceConfig;
 
 pub fn init_api_v1(cfg: &mut ServiceConfig) {
-    cfg.service(get_user_by_id)
+    cfg.service(users_listing_handler)
+        .service(get_user_by_id)
         .service(create_user_handler)
         .service(delete_user_by_id)
+        .service(revoke_user_sessions_handler)
+        .service(enable_user_handler)
+        .service(disable_user_handler)
+        .service(unlock_user_handler)
+        .service(set_user_password_handler)
         .service(get_permission_handler)
         .service(create_permission_handler)
         .service(disable_permission_handler)
         .service(permissions_listing_handler)
+        .service(roles_listing_handler)
         .service(get_role_handler)
         .service(create_role_handler)
         .service(disable_role_handler)
         .service(bind_permission_with_role_handler)
         .service(unbind_permission_with_role_handler)
         .service(bind_member_with_role_handler)
-        .service(unbind_member_with_role_handler);
+        .service(unbind_member_with_role_handler)
+        .service(create_api_key_handler)
+        .service(api_keys_listing_handler)
+        .service(revoke_api_key_handler);
 }
 pub fn init_external_v1(cfg: &mut ServiceConfig) {
-    cfg.service(sign_in_user_handler);
+    cfg.service(sign_in_user_handler)
+        .service(refresh_token_handler)
+        .service(sign_out_handler)
+        .service(enroll_totp_handler)
+        .service(confirm_totp_handler)
+        .service(verify_mfa_handler)
+        .service(oauth_token_handler)
+        .service(authorize_page_handler)
+        .service(authorize_handler)
+        .service(userinfo_handler)
+        .service(federated_login_handler)
+        .service(federated_callback_handler)
+        .service(request_password_reset_handler)
+        .service(confirm_password_reset_handler);
 }
 
 pub fn init_internal_v1(cfg: &mut ServiceConfig) {
     cfg.service(validate_jwt_handler);
 }
 
+pub fn init_well_known(cfg: &mut ServiceConfig) {
+    cfg.service(jwks_handler)
+        .service(openid_configuration_handler);
+}
+
 pub fn init_system(cfg: &mut ServiceConfig) {
     cfg.service(ready_handler).service(ping_handler);
 }
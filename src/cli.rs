@@ -0,0 +1,379 @@
+use crate::common::{Config, Resources};
+use crate::storage::postgres::federation_repo::FederationRepo;
+use crate::storage::postgres::migrations::{migrate_up, migration_status};
+use crate::storage::postgres::oauth_client_repo::OAuthClientRepo;
+use crate::storage::postgres::permission_repo::PermissionRepo;
+use crate::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
+use crate::storage::postgres::role_repo::RoleRepo;
+use crate::storage::postgres::user_repo::UserRepo;
+use crate::usecases::federation::federated_sign_in::link_user_identity;
+use crate::usecases::oauth::client_registry::{bind_role_to_client, register_client};
+use crate::usecases::oauth::entities::{OAuthClientRegistration, CLIENT_CREDENTIALS_GRANT};
+use crate::usecases::permission::entities::PermissionForCreation;
+use crate::usecases::permission::permission_creator::create_new_permission;
+use crate::usecases::roles::entities::{RoleForCreation, RolesFilters};
+use crate::usecases::roles::role_creator::create_new_role;
+use crate::usecases::roles::role_get_list::get_roles_by_filters;
+use crate::usecases::roles::role_members_binder::bind_member_to_role;
+use crate::usecases::roles::role_permissions_binder::bind_permission_to_role;
+use crate::usecases::users::crypto::issue_tokens_for_user;
+use crate::usecases::users::user_creator::create_new_user;
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+#[derive(Parser)]
+#[command(name = "authust", about = "Simple auth service")]
+pub struct Cli {
+    /// TOML or YAML config file, env vars override its values
+    #[arg(long, global = true, env = "AUTHUST_CONFIG")]
+    pub config: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run http server, default command
+    Serve,
+    /// Manage database schema
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Manage users
+    User {
+        #[command(subcommand)]
+        action: UserAction,
+    },
+    /// Manage roles and their bindings
+    Role {
+        #[command(subcommand)]
+        action: RoleAction,
+    },
+    /// Manage permissions
+    Permission {
+        #[command(subcommand)]
+        action: PermissionAction,
+    },
+    /// Issue tokens
+    Token {
+        #[command(subcommand)]
+        action: TokenAction,
+    },
+    /// Manage OAuth clients
+    Client {
+        #[command(subcommand)]
+        action: ClientAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MigrateAction {
+    /// Apply pending migrations
+    Up,
+    /// Show applied and pending migrations
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum UserAction {
+    /// Create user, password is read from stdin if not passed
+    Create {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+        /// Address for password reset
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Link user with identity of upstream provider, subject is `sub` claim of the provider
+    LinkIdentity {
+        #[arg(long)]
+        user_id: i32,
+        #[arg(long)]
+        provider: String,
+        #[arg(long)]
+        subject: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RoleAction {
+    /// Create role
+    Create {
+        #[arg(long)]
+        name: String,
+    },
+    /// List active roles, optionally filtered by name
+    List {
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Add user to role
+    BindMember {
+        #[arg(long)]
+        role_id: i32,
+        #[arg(long)]
+        user_id: i32,
+    },
+    /// Add permission to role
+    BindPermission {
+        #[arg(long)]
+        role_id: i32,
+        #[arg(long)]
+        permission_id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PermissionAction {
+    /// Create permission
+    Create {
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TokenAction {
+    /// Issue access and refresh tokens for active user without password
+    Issue {
+        #[arg(long)]
+        user: i32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ClientAction {
+    /// Register OAuth client, generated secret is printed once
+    Create {
+        #[arg(long)]
+        client_id: String,
+        /// Allowed scope, can be repeated
+        #[arg(long)]
+        scope: Vec<String>,
+        /// Allowed grant type, can be repeated
+        #[arg(long, default_value = CLIENT_CREDENTIALS_GRANT)]
+        grant_type: Vec<String>,
+        /// Redirect URI for authorization code grant, can be repeated
+        #[arg(long)]
+        redirect_uri: Vec<String>,
+        /// Browser or mobile app without secret
+        #[arg(long)]
+        public: bool,
+    },
+    /// Grant role and its permissions to client tokens
+    BindRole {
+        #[arg(long)]
+        client_id: String,
+        #[arg(long)]
+        role_id: i32,
+    },
+}
+
+pub async fn run_migrate(resources: &Resources, action: MigrateAction) -> Result<(), String> {
+    match action {
+        MigrateAction::Up => {
+            let applied = migrate_up(&resources.db_pool)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            match applied.is_empty() {
+                true => println!("schema is up to date"),
+                false => println!("applied migrations: {:?}", applied),
+            }
+        }
+        MigrateAction::Status => {
+            let statuses = migration_status(&resources.db_pool)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            for status in statuses {
+                let state = match (status.applied_at, status.checksum_matches) {
+                    (None, _) => "pending".to_string(),
+                    (Some(_), false) => "CHECKSUM MISMATCH".to_string(),
+                    (Some(applied_at), true) => format!("applied at {}", applied_at.to_rfc3339()),
+                };
+                println!("V{}__{}: {}", status.version, status.name, state);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn run_user(
+    config: &Config,
+    resources: &Resources,
+    action: UserAction,
+) -> Result<(), String> {
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    match action {
+        UserAction::Create {
+            username,
+            password,
+            email,
+        } => {
+            let password = match password {
+                Some(password) => password,
+                None => read_password()?,
+            };
+            let user = create_new_user(&user_repo, username, password, email)
+                .await
+                .map_err(|e| format!("user creation failed: {:?}", e))?;
+            print_json(&user)
+        }
+        UserAction::LinkIdentity {
+            user_id,
+            provider,
+            subject,
+        } => {
+            let federation_repo = FederationRepo::new(resources.db_pool.clone());
+            link_user_identity(
+                &federation_repo,
+                &config.federation_config,
+                user_id,
+                &provider,
+                &subject,
+            )
+            .await
+            .map_err(|e| format!("identity linking failed: {:?}", e))?;
+            println!(
+                "user {} is linked with {} identity {}",
+                user_id, provider, subject
+            );
+            Ok(())
+        }
+    }
+}
+
+pub async fn run_role(resources: &Resources, action: RoleAction) -> Result<(), String> {
+    let role_repo = RoleRepo::new(resources.db_pool.clone());
+    match action {
+        RoleAction::Create { name } => {
+            let role = create_new_role(&role_repo, RoleForCreation { role_name: name })
+                .await
+                .map_err(|e| format!("role creation failed: {:?}", e))?;
+            print_json(&role)
+        }
+        RoleAction::List { name } => {
+            let filters = RolesFilters {
+                role_name: name,
+                is_deleted: Some(false),
+                user_id: None,
+                permission_id: None,
+                with_counts: true,
+                offset: 0,
+                limit: 1000,
+            };
+            let listing = get_roles_by_filters(&role_repo, filters)
+                .await
+                .map_err(|e| format!("roles listing failed: {:?}", e))?;
+            print_json(&listing.roles)
+        }
+        RoleAction::BindMember { role_id, user_id } => {
+            let binding = bind_member_to_role(&role_repo, role_id, user_id)
+                .await
+                .map_err(|e| format!("member binding failed: {:?}", e))?;
+            print_json(&binding)
+        }
+        RoleAction::BindPermission {
+            role_id,
+            permission_id,
+        } => {
+            let binding = bind_permission_to_role(&role_repo, role_id, permission_id)
+                .await
+                .map_err(|e| format!("permission binding failed: {:?}", e))?;
+            print_json(&binding)
+        }
+    }
+}
+
+pub async fn run_permission(resources: &Resources, action: PermissionAction) -> Result<(), String> {
+    let permission_repo = PermissionRepo::new(resources.db_pool.clone());
+    match action {
+        PermissionAction::Create { name } => {
+            let perm_data = PermissionForCreation {
+                permission_name: name,
+            };
+            let permission = create_new_permission(&permission_repo, perm_data)
+                .await
+                .map_err(|e| format!("permission creation failed: {:?}", e))?;
+            print_json(&permission)
+        }
+    }
+}
+
+pub async fn run_token(
+    config: &Config,
+    resources: &Resources,
+    action: TokenAction,
+) -> Result<(), String> {
+    match action {
+        TokenAction::Issue { user } => {
+            let user_repo = UserRepo::new(resources.db_pool.clone());
+            let revocation_repo = RevocationRepo::new(resources.db_pool.clone());
+            let token_repo = RefreshTokenRepo::new(resources.db_pool.clone());
+            let signed_info = issue_tokens_for_user(
+                &user_repo,
+                &revocation_repo,
+                &token_repo,
+                &config.security_config,
+                user,
+            )
+            .await
+            .map_err(|e| format!("token issuing failed: {:?}", e))?;
+            print_json(&signed_info)
+        }
+    }
+}
+
+pub async fn run_client(resources: &Resources, action: ClientAction) -> Result<(), String> {
+    let client_repo = OAuthClientRepo::new(resources.db_pool.clone());
+    match action {
+        ClientAction::Create {
+            client_id,
+            scope,
+            grant_type,
+            redirect_uri,
+            public,
+        } => {
+            let registration = OAuthClientRegistration {
+                client_id,
+                scopes: scope,
+                grant_types: grant_type,
+                redirect_uris: redirect_uri,
+                is_public: public,
+            };
+            let client = register_client(&client_repo, registration)
+                .await
+                .map_err(|e| format!("client registration failed: {:?}", e))?;
+            print_json(&client)
+        }
+        ClientAction::BindRole { client_id, role_id } => {
+            bind_role_to_client(&client_repo, &client_id, role_id)
+                .await
+                .map_err(|e| format!("role binding failed: {:?}", e))?;
+            println!("role {} is bound to client {}", role_id, client_id);
+            Ok(())
+        }
+    }
+}
+
+fn read_password() -> Result<String, String> {
+    let mut password = String::new();
+    std::io::stdin()
+        .read_line(&mut password)
+        .map_err(|e| format!("can not read password from stdin: {}", e))?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    match password.is_empty() {
+        true => Err("password must not be empty".to_string()),
+        false => Ok(password),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+    let output = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", output);
+    Ok(())
+}
@@ -1,8 +1,43 @@
+pub mod config_source;
+pub mod tls;
+
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common::config_source::{read_config_file, ConfigError, ConfigSource};
+use crate::common::tls::{
+    create_pg_tls_config, load_root_certs, PgSslMode, ReloadableCertResolver,
+};
+use crate::storage::memory::revocation_cache::RevocationCache;
+use crate::storage::memory::sign_in_attempts::MemorySignInAttemptsRepo;
+use crate::storage::memory::token_buckets::TokenBuckets;
+use crate::storage::postgres::sign_in_attempts_repo::SignInAttemptsRepo;
+use crate::usecases::users::jwt_keys::{JwtKey, JwtKeyring};
+use crate::usecases::users::sign_in_throttler::StoreSignInAttempts;
 
 use deadpool_postgres::tokio_postgres::NoTls;
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use log::LevelFilter;
+use rustls::{ClientConfig, RootCertStore};
 use tokio_postgres;
+use tokio_postgres::config::SslMode;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub workers: Option<NonZeroUsize>,
+    pub log_level: LevelFilter,
+    pub tls_cert_resolver: Option<Arc<ReloadableCertResolver>>,
+    pub tls_client_roots: Option<Arc<RootCertStore>>,
+}
 
 #[derive(Clone, Debug)]
 pub struct DbConfig {
@@ -12,62 +47,823 @@ pub struct DbConfig {
     pub port: u16,
     pub dbname: String,
     pub pool_max_size: usize,
+    pub migrate_on_startup: bool,
+    pub ssl_mode: PgSslMode,
+    pub tls_config: Option<Arc<ClientConfig>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct SecurityConfig {
-    pub secret_key: String,
-    pub expired_jwt_days: u32,
+    pub jwt_keyring: JwtKeyring,
+    pub expired_access_token_minutes: u32,
+    pub expired_refresh_token_days: u32,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub jwt_leeway_seconds: u32,
+    pub revocation_cache_seconds: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttemptsStoreKind {
+    Memory,
+    Postgres,
+}
+
+impl FromStr for AttemptsStoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(AttemptsStoreKind::Memory),
+            "postgres" => Ok(AttemptsStoreKind::Postgres),
+            _ => Err(format!("expected memory or postgres, got {}", s)),
+        }
+    }
+}
+
+/// Failed sign in attempts are counted in sliding window by username and by client IP.
+/// After `free_failures` of username next attempt waits `base_delay_seconds`, doubled with
+/// every failure, `max_failures_*` lock sign in for `lockout_seconds`
+#[derive(Clone, Debug)]
+pub struct SignInThrottlingConfig {
+    pub store: AttemptsStoreKind,
+    pub window_seconds: u32,
+    pub free_failures: u32,
+    pub base_delay_seconds: u32,
+    pub max_failures_per_username: u32,
+    pub max_failures_per_ip: u32,
+    pub lockout_seconds: u32,
+}
+
+/// Token bucket holds up to `burst` requests and is refilled with `per_second` requests
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BucketLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+/// Limits of `api/v1` are applied per user, limits of `srv/v1` per service,
+/// `overrides` are keyed by `user:{user_id}` or `service:{client_id}`
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub api: BucketLimit,
+    pub srv: BucketLimit,
+    pub overrides: HashMap<String, BucketLimit>,
+}
+
+/// Upstream OpenID Connect provider, endpoints are taken from its discovery document,
+/// `create_users` enables just-in-time creation of local users for unknown identities
+#[derive(Clone)]
+pub struct UpstreamProvider {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub username_claim: String,
+    pub create_users: bool,
+}
+
+impl fmt::Debug for UpstreamProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpstreamProvider")
+            .field("name", &self.name)
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .field("authorization_endpoint", &self.authorization_endpoint)
+            .field("token_endpoint", &self.token_endpoint)
+            .field("jwks_uri", &self.jwks_uri)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("scope", &self.scope)
+            .field("username_claim", &self.username_claim)
+            .field("create_users", &self.create_users)
+            .finish()
+    }
+}
+
+/// Providers are keyed by name, the name is a part of login and callback URLs
+#[derive(Clone, Debug)]
+pub struct FederationConfig {
+    pub providers: HashMap<String, UpstreamProvider>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignInBackend {
+    Local,
+    Ldap,
+    LocalThenLdap,
+}
+
+impl FromStr for SignInBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(SignInBackend::Local),
+            "ldap" => Ok(SignInBackend::Ldap),
+            "local_then_ldap" => Ok(SignInBackend::LocalThenLdap),
+            _ => Err(format!(
+                "expected local, ldap or local_then_ldap, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// User entry is searched with `bind_dn` (anonymously if it isn't set), then bound with the password.
+/// `group_roles` maps cn of groups from `group_attribute` to role names, roles are synced on sign in
+#[derive(Clone)]
+pub struct LdapConfig {
+    pub local_first: bool,
+    pub url: String,
+    pub starttls: bool,
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    pub user_filter: String,
+    pub username_attribute: String,
+    pub group_attribute: String,
+    pub group_roles: HashMap<String, String>,
+    pub timeout_seconds: u64,
+}
+
+impl fmt::Debug for LdapConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LdapConfig")
+            .field("local_first", &self.local_first)
+            .field("url", &self.url)
+            .field("starttls", &self.starttls)
+            .field("bind_dn", &self.bind_dn)
+            .field("base_dn", &self.base_dn)
+            .field("user_filter", &self.user_filter)
+            .field("username_attribute", &self.username_attribute)
+            .field("group_attribute", &self.group_attribute)
+            .field("group_roles", &self.group_roles)
+            .field("timeout_seconds", &self.timeout_seconds)
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotifierKind {
+    Log,
+    File,
+    Smtp,
+}
+
+impl FromStr for NotifierKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(NotifierKind::Log),
+            "file" => Ok(NotifierKind::File),
+            "smtp" => Ok(NotifierKind::Smtp),
+            _ => Err(format!("expected log, file or smtp, got {}", s)),
+        }
+    }
+}
+
+/// Notifications are written to the log, appended to `file_path` as JSON lines or sent to
+/// SMTP relay without TLS and authentication, e.g. local relay or mail catcher
+#[derive(Clone, Debug)]
+pub struct NotifierConfig {
+    pub kind: NotifierKind,
+    pub file_path: Option<String>,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub from: String,
+    pub timeout_seconds: u64,
+}
+
+/// Reset tokens live `token_minutes`, `url` is the page of reset form,
+/// the token is passed to it as `token` query parameter
+#[derive(Clone, Debug)]
+pub struct PasswordResetConfig {
+    pub token_minutes: u32,
+    pub url: Option<String>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Config {
+    pub server_config: ServerConfig,
     pub database_config: DbConfig,
     pub security_config: SecurityConfig,
+    pub sign_in_throttling_config: SignInThrottlingConfig,
+    pub rate_limit_config: RateLimitConfig,
+    pub federation_config: FederationConfig,
+    pub ldap_config: Option<LdapConfig>,
+    pub notifier_config: NotifierConfig,
+    pub password_reset_config: PasswordResetConfig,
     pub service_name: String,
 }
 
 impl Config {
+    /// Reads config file from `AUTHUST_CONFIG` if set, panics on any config error
     pub fn create_config() -> Config {
-        Config {
-            database_config: DbConfig {
-                user: env::var("PG_USER").expect("Expected env param PG_USER"),
-                password: env::var("PG_PASSWORD").expect("Expected env param PG_PASSWORD"),
-                host: env::var("PG_HOST").expect("Expected env param PG_HOST"),
-                port: env::var("PG_PORT")
-                    .expect("Expected env param PG_PORT")
-                    .parse()
-                    .expect("Wrong env param PG_PORT"),
-                dbname: env::var("PG_DBNAME").expect("Expected env param PG_DBNAME"),
-                pool_max_size: env::var("PG_POOL_MAX_SIZE")
-                    .expect("Expected env param PG_POOL_MAX_SIZE")
-                    .parse()
-                    .expect("Wrong env param PG_POOL_MAX_SIZE"),
-            },
+        let path = env::var("AUTHUST_CONFIG").ok();
+        Config::load(path.as_deref()).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Defaults are overridden by config file, config file is overridden by env vars
+    pub fn load(path: Option<&str>) -> Result<Config, ConfigError> {
+        let file_values = match path {
+            Some(path) => read_config_file(Path::new(path))?,
+            None => HashMap::new(),
+        };
+        let env_values = env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Config::from_values(file_values, env_values)
+    }
+
+    pub fn from_values(
+        file_values: HashMap<String, String>,
+        env_values: HashMap<String, String>,
+    ) -> Result<Config, ConfigError> {
+        let mut source = ConfigSource::new(file_values, env_values);
+        let server_config = ServerConfig {
+            host: source.optional("server.host", "SERVER_HOST", "127.0.0.1".to_string()),
+            port: source.optional("server.port", "SERVER_PORT", 8080),
+            workers: source.optional_value("server.workers", "SERVER_WORKERS"),
+            log_level: source.optional("server.log_level", "LOG_LEVEL", LevelFilter::Info),
+            tls_cert_resolver: create_cert_resolver(&mut source),
+            tls_client_roots: create_client_roots(&mut source),
+        };
+        let ssl_mode = source.optional("database.sslmode", "PG_SSLMODE", PgSslMode::Disable);
+        let database_config = DbConfig {
+            user: source.required("database.user", "PG_USER"),
+            password: source.required("database.password", "PG_PASSWORD"),
+            host: source.required("database.host", "PG_HOST"),
+            port: source.optional("database.port", "PG_PORT", 5432),
+            dbname: source.required("database.dbname", "PG_DBNAME"),
+            pool_max_size: source.optional("database.pool_max_size", "PG_POOL_MAX_SIZE", 16),
+            migrate_on_startup: source.optional(
+                "database.migrate_on_startup",
+                "PG_MIGRATE_ON_STARTUP",
+                false,
+            ),
+            ssl_mode,
+            tls_config: create_pg_tls_config_from_source(&mut source, ssl_mode),
+        };
+        let jwt_keyring = create_jwt_keyring(&mut source);
+        let expired_access_token_minutes = source.optional(
+            "security.expired_access_token_minutes",
+            "EXPIRED_ACCESS_TOKEN_MINUTES",
+            15,
+        );
+        let expired_refresh_token_days = source.optional(
+            "security.expired_refresh_token_days",
+            "EXPIRED_REFRESH_TOKEN_DAYS",
+            14,
+        );
+        let jwt_issuer =
+            source.optional("security.jwt_issuer", "JWT_ISSUER", "authust".to_string());
+        let jwt_audience = source.optional(
+            "security.jwt_audience",
+            "JWT_AUDIENCE",
+            "authust".to_string(),
+        );
+        let jwt_leeway_seconds =
+            source.optional("security.jwt_leeway_seconds", "JWT_LEEWAY_SECONDS", 30);
+        let revocation_cache_seconds = source.optional(
+            "security.revocation_cache_seconds",
+            "REVOCATION_CACHE_SECONDS",
+            10,
+        );
+        let sign_in_throttling_config = SignInThrottlingConfig {
+            store: source.optional(
+                "sign_in_throttling.store",
+                "SIGN_IN_ATTEMPTS_STORE",
+                AttemptsStoreKind::Postgres,
+            ),
+            window_seconds: source.optional(
+                "sign_in_throttling.window_seconds",
+                "SIGN_IN_WINDOW_SECONDS",
+                900,
+            ),
+            free_failures: source.optional(
+                "sign_in_throttling.free_failures",
+                "SIGN_IN_FREE_FAILURES",
+                3,
+            ),
+            base_delay_seconds: source.optional(
+                "sign_in_throttling.base_delay_seconds",
+                "SIGN_IN_BASE_DELAY_SECONDS",
+                1,
+            ),
+            max_failures_per_username: source.optional(
+                "sign_in_throttling.max_failures_per_username",
+                "SIGN_IN_MAX_FAILURES_PER_USERNAME",
+                10,
+            ),
+            max_failures_per_ip: source.optional(
+                "sign_in_throttling.max_failures_per_ip",
+                "SIGN_IN_MAX_FAILURES_PER_IP",
+                100,
+            ),
+            lockout_seconds: source.optional(
+                "sign_in_throttling.lockout_seconds",
+                "SIGN_IN_LOCKOUT_SECONDS",
+                900,
+            ),
+        };
+        let rate_limit_config = RateLimitConfig {
+            enabled: source.optional("rate_limit.enabled", "RATE_LIMIT_ENABLED", true),
+            api: create_bucket_limit(&mut source, "api", 100, 20.0),
+            srv: create_bucket_limit(&mut source, "srv", 500, 100.0),
+            overrides: create_rate_limit_overrides(&mut source),
+        };
+        let federation_config = FederationConfig {
+            providers: create_upstream_providers(&mut source, &jwt_issuer),
+        };
+        let ldap_config = create_ldap_config(&mut source);
+        let notifier_config = create_notifier_config(&mut source);
+        let password_reset_config = PasswordResetConfig {
+            token_minutes: source.optional(
+                "password_reset.token_minutes",
+                "PASSWORD_RESET_TOKEN_MINUTES",
+                30,
+            ),
+            url: source.optional_value("password_reset.url", "PASSWORD_RESET_URL"),
+        };
+        let service_name = source.optional("service_name", "SERVICE_NAME", "authust".to_string());
+        source.finish()?;
+        Ok(Config {
+            server_config,
+            database_config,
             security_config: SecurityConfig {
-                secret_key: env::var("SECRET_KEY").expect("Expected env param SECRET_KEY"),
-                expired_jwt_days: env::var("EXPIRED_JWT_DAYS")
-                    .expect("Expected env param EXPIRED_JWT_DAYS")
-                    .parse()
-                    .expect("Wrong env param EXPIRED_JWT_DAYS"),
+                // keyring is None only together with reported error
+                jwt_keyring: jwt_keyring.expect("jwt keyring errors are reported"),
+                expired_access_token_minutes,
+                expired_refresh_token_days,
+                jwt_issuer,
+                jwt_audience,
+                jwt_leeway_seconds,
+                revocation_cache_seconds,
             },
-            service_name: env::var("SERVICE_NAME").expect("Expected env param SERVICE_NAME"),
+            sign_in_throttling_config,
+            rate_limit_config,
+            federation_config,
+            ldap_config,
+            notifier_config,
+            password_reset_config,
+            service_name,
+        })
+    }
+}
+
+/// Listener serves HTTPS when both `server.tls_cert_path` and `server.tls_key_path` are set
+fn create_cert_resolver(source: &mut ConfigSource) -> Option<Arc<ReloadableCertResolver>> {
+    let cert_path = source.optional_value::<String>("server.tls_cert_path", "TLS_CERT_PATH");
+    let key_path = source.optional_value::<String>("server.tls_key_path", "TLS_KEY_PATH");
+    match (cert_path, key_path) {
+        (None, None) => None,
+        (Some(cert_path), Some(key_path)) => {
+            match ReloadableCertResolver::new(&cert_path, &key_path) {
+                Ok(resolver) => Some(Arc::new(resolver)),
+                Err(e) => {
+                    source.invalid("server.tls_cert_path", "TLS_CERT_PATH", e.to_string());
+                    None
+                }
+            }
+        }
+        (Some(_), None) => {
+            source.missing("server.tls_key_path", "TLS_KEY_PATH");
+            None
+        }
+        (None, Some(_)) => {
+            source.missing("server.tls_cert_path", "TLS_CERT_PATH");
+            None
         }
     }
 }
 
+/// Client certificates of internal services are accepted when `server.tls_client_ca_path` is set
+fn create_client_roots(source: &mut ConfigSource) -> Option<Arc<RootCertStore>> {
+    const KEY: &str = "server.tls_client_ca_path";
+    const ENV: &str = "TLS_CLIENT_CA_PATH";
+    let ca_path = source.optional_value::<String>(KEY, ENV)?;
+    if source
+        .raw("server.tls_cert_path", "TLS_CERT_PATH")
+        .is_none()
+    {
+        source.invalid(
+            KEY,
+            ENV,
+            "requires tls_cert_path and tls_key_path".to_string(),
+        );
+        return None;
+    }
+    match load_root_certs(&ca_path) {
+        Ok(roots) => Some(roots),
+        Err(e) => {
+            source.invalid(KEY, ENV, e.to_string());
+            None
+        }
+    }
+}
+
+fn create_pg_tls_config_from_source(
+    source: &mut ConfigSource,
+    ssl_mode: PgSslMode,
+) -> Option<Arc<ClientConfig>> {
+    let ca_cert_path = source.optional_value::<String>("database.ca_cert_path", "PG_CA_CERT_PATH");
+    if ca_cert_path.is_none() && matches!(ssl_mode, PgSslMode::VerifyCa | PgSslMode::VerifyFull) {
+        source.missing("database.ca_cert_path", "PG_CA_CERT_PATH");
+        return None;
+    }
+    match create_pg_tls_config(ssl_mode, ca_cert_path.as_deref()) {
+        Ok(tls_config) => tls_config,
+        Err(e) => {
+            source.invalid("database.ca_cert_path", "PG_CA_CERT_PATH", e.to_string());
+            None
+        }
+    }
+}
+
+/// `security.jwt_verification_keys` is a list of `kid:algorithm:path` of keys still accepted
+/// for verification, for HS256 the file contains the secret
+fn create_jwt_keyring(source: &mut ConfigSource) -> Option<JwtKeyring> {
+    const KEY: &str = "security.jwt_verification_keys";
+    const ENV: &str = "JWT_VERIFICATION_KEYS";
+    let current_key = create_jwt_key(source);
+    let mut verification_keys = vec![];
+    for entry in source.raw(KEY, ENV).unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.splitn(3, ':').collect::<Vec<&str>>()[..] {
+            [kid, algorithm, path] => match JwtKey::from_file(kid, algorithm, path) {
+                Ok(key) => verification_keys.push(key),
+                Err(e) => source.invalid(KEY, ENV, format!("{:?}", e)),
+            },
+            _ => source.invalid(
+                KEY,
+                ENV,
+                format!("expected kid:algorithm:path, got {}", entry),
+            ),
+        }
+    }
+    match JwtKeyring::new(current_key?, verification_keys) {
+        Ok(keyring) => Some(keyring),
+        Err(e) => {
+            source.invalid(KEY, ENV, format!("{:?}", e));
+            None
+        }
+    }
+}
+
+/// HS256 signs with `security.secret_key`, asymmetric algorithms load private key
+/// from `security.jwt_private_key_path`
+fn create_jwt_key(source: &mut ConfigSource) -> Option<JwtKey> {
+    let algorithm: String = source.optional(
+        "security.jwt_algorithm",
+        "JWT_ALGORITHM",
+        "HS256".to_string(),
+    );
+    let kid: String = source.optional("security.jwt_key_id", "JWT_KEY_ID", "default".to_string());
+    let secret_key: Option<String> = source.raw("security.secret_key", "SECRET_KEY");
+    let path: Option<String> = source.raw("security.jwt_private_key_path", "JWT_PRIVATE_KEY_PATH");
+    if algorithm == "HS256" {
+        return match secret_key {
+            Some(secret_key) if !secret_key.is_empty() => {
+                Some(JwtKey::from_secret(&kid, &secret_key))
+            }
+            _ => {
+                source.missing("security.secret_key", "SECRET_KEY");
+                None
+            }
+        };
+    }
+    let path = match path {
+        Some(path) if !path.is_empty() => path,
+        _ => {
+            source.missing("security.jwt_private_key_path", "JWT_PRIVATE_KEY_PATH");
+            return None;
+        }
+    };
+    match JwtKey::from_pem_file(&kid, &algorithm, &path) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            source.invalid(
+                "security.jwt_private_key_path",
+                "JWT_PRIVATE_KEY_PATH",
+                format!("{:?}", e),
+            );
+            None
+        }
+    }
+}
+
+fn parse_burst(burst: &str) -> Result<u32, String> {
+    match burst.parse() {
+        Ok(0) => Err("burst must be positive".to_string()),
+        Ok(burst) => Ok(burst),
+        Err(e) => Err(format!("burst: {}", e)),
+    }
+}
+
+fn parse_per_second(per_second: &str) -> Result<f64, String> {
+    let per_second: f64 = per_second
+        .parse()
+        .map_err(|e| format!("per_second: {}", e))?;
+    if !per_second.is_finite() || per_second <= 0.0 {
+        return Err("per_second must be positive".to_string());
+    }
+    Ok(per_second)
+}
+
+fn parse_bucket_limit(burst: &str, per_second: &str) -> Result<BucketLimit, String> {
+    Ok(BucketLimit {
+        burst: parse_burst(burst)?,
+        per_second: parse_per_second(per_second)?,
+    })
+}
+
+fn create_bucket_limit(
+    source: &mut ConfigSource,
+    scope: &str,
+    default_burst: u32,
+    default_per_second: f64,
+) -> BucketLimit {
+    let burst_key = format!("rate_limit.{}_burst", scope);
+    let burst_env = format!("RATE_LIMIT_{}_BURST", scope.to_uppercase());
+    let per_second_key = format!("rate_limit.{}_per_second", scope);
+    let per_second_env = format!("RATE_LIMIT_{}_PER_SECOND", scope.to_uppercase());
+    let burst = match source
+        .raw(&burst_key, &burst_env)
+        .map(|raw| parse_burst(&raw))
+    {
+        Some(Ok(burst)) => burst,
+        Some(Err(e)) => {
+            source.invalid(&burst_key, &burst_env, e);
+            default_burst
+        }
+        None => default_burst,
+    };
+    let per_second = match source
+        .raw(&per_second_key, &per_second_env)
+        .map(|raw| parse_per_second(&raw))
+    {
+        Some(Ok(per_second)) => per_second,
+        Some(Err(e)) => {
+            source.invalid(&per_second_key, &per_second_env, e);
+            default_per_second
+        }
+        None => default_per_second,
+    };
+    BucketLimit { burst, per_second }
+}
+
+/// `rate_limit.overrides` is a list of `user:{user_id}=burst:per_second`
+/// or `service:{client_id}=burst:per_second`
+fn create_rate_limit_overrides(source: &mut ConfigSource) -> HashMap<String, BucketLimit> {
+    const KEY: &str = "rate_limit.overrides";
+    const ENV: &str = "RATE_LIMIT_OVERRIDES";
+    let mut overrides = HashMap::new();
+    for entry in source.raw(KEY, ENV).unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let parsed = match entry.split_once('=') {
+            Some((identity, limit))
+                if identity.starts_with("user:") || identity.starts_with("service:") =>
+            {
+                match limit.split_once(':') {
+                    Some((burst, per_second)) => {
+                        parse_bucket_limit(burst, per_second).map(|limit| (identity, limit))
+                    }
+                    None => Err(format!("expected burst:per_second, got {}", limit)),
+                }
+            }
+            _ => Err(format!(
+                "expected user:{{user_id}}=burst:per_second or service:{{client_id}}=burst:per_second, got {}",
+                entry
+            )),
+        };
+        match parsed {
+            Ok((identity, limit)) => {
+                overrides.insert(identity.to_string(), limit);
+            }
+            Err(e) => source.invalid(KEY, ENV, e),
+        }
+    }
+    overrides
+}
+
+/// `federation.providers` is a list of provider names, settings of every provider are read
+/// from `federation.{name}.*` keys, e.g. `federation.corp.issuer` or env `FEDERATION_CORP_ISSUER`
+fn create_upstream_providers(
+    source: &mut ConfigSource,
+    jwt_issuer: &str,
+) -> HashMap<String, UpstreamProvider> {
+    const KEY: &str = "federation.providers";
+    const ENV: &str = "FEDERATION_PROVIDERS";
+    let mut providers = HashMap::new();
+    for name in source.raw(KEY, ENV).unwrap_or_default().split(',') {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            source.invalid(
+                KEY,
+                ENV,
+                format!("provider name must be [a-z0-9_], got {}", name),
+            );
+            continue;
+        }
+        // identities of LDAP users are linked under the same name
+        if name == "ldap" {
+            source.invalid(KEY, ENV, "provider name ldap is reserved".to_string());
+            continue;
+        }
+        let key = |field: &str| format!("federation.{}.{}", name, field);
+        let env = |field: &str| format!("FEDERATION_{}_{}", name, field).to_uppercase();
+        let default_redirect_uri = format!(
+            "{}/auth/v1/federation/{}/callback",
+            jwt_issuer.trim_end_matches('/'),
+            name
+        );
+        let provider = UpstreamProvider {
+            name: name.to_string(),
+            issuer: source.required(&key("issuer"), &env("issuer")),
+            client_id: source.required(&key("client_id"), &env("client_id")),
+            client_secret: source.optional_value(&key("client_secret"), &env("client_secret")),
+            authorization_endpoint: source.required(
+                &key("authorization_endpoint"),
+                &env("authorization_endpoint"),
+            ),
+            token_endpoint: source.required(&key("token_endpoint"), &env("token_endpoint")),
+            jwks_uri: source.required(&key("jwks_uri"), &env("jwks_uri")),
+            redirect_uri: source.optional(
+                &key("redirect_uri"),
+                &env("redirect_uri"),
+                default_redirect_uri,
+            ),
+            scope: source.optional(&key("scope"), &env("scope"), "openid profile".to_string()),
+            username_claim: source.optional(
+                &key("username_claim"),
+                &env("username_claim"),
+                "preferred_username".to_string(),
+            ),
+            create_users: source.optional(&key("create_users"), &env("create_users"), false),
+        };
+        providers.insert(name.to_string(), provider);
+    }
+    providers
+}
+
+/// LDAP settings are required only if `security.sign_in_backend` uses LDAP,
+/// `ldap.group_roles` entries are `{group cn}={role name}`
+fn create_ldap_config(source: &mut ConfigSource) -> Option<LdapConfig> {
+    let backend = source.optional(
+        "security.sign_in_backend",
+        "SIGN_IN_BACKEND",
+        SignInBackend::Local,
+    );
+    let url = source.optional_value::<String>("ldap.url", "LDAP_URL");
+    let base_dn = source.optional_value::<String>("ldap.base_dn", "LDAP_BASE_DN");
+    let mut group_roles = HashMap::new();
+    for entry in source
+        .raw("ldap.group_roles", "LDAP_GROUP_ROLES")
+        .unwrap_or_default()
+        .split(',')
+    {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.split_once('=') {
+            Some((group, role)) if !group.trim().is_empty() && !role.trim().is_empty() => {
+                group_roles.insert(group.trim().to_lowercase(), role.trim().to_string());
+            }
+            _ => source.invalid(
+                "ldap.group_roles",
+                "LDAP_GROUP_ROLES",
+                format!("expected {{group cn}}={{role name}}, got {}", entry),
+            ),
+        }
+    }
+    let config = LdapConfig {
+        local_first: backend == SignInBackend::LocalThenLdap,
+        url: url.clone().unwrap_or_default(),
+        starttls: source.optional("ldap.starttls", "LDAP_STARTTLS", false),
+        bind_dn: source.optional_value("ldap.bind_dn", "LDAP_BIND_DN"),
+        bind_password: source.optional_value("ldap.bind_password", "LDAP_BIND_PASSWORD"),
+        base_dn: base_dn.clone().unwrap_or_default(),
+        user_filter: source.optional(
+            "ldap.user_filter",
+            "LDAP_USER_FILTER",
+            "(uid={username})".to_string(),
+        ),
+        username_attribute: source.optional(
+            "ldap.username_attribute",
+            "LDAP_USERNAME_ATTRIBUTE",
+            "uid".to_string(),
+        ),
+        group_attribute: source.optional(
+            "ldap.group_attribute",
+            "LDAP_GROUP_ATTRIBUTE",
+            "memberOf".to_string(),
+        ),
+        group_roles,
+        timeout_seconds: source.optional("ldap.timeout_seconds", "LDAP_TIMEOUT_SECONDS", 5),
+    };
+    if backend == SignInBackend::Local {
+        return None;
+    }
+    if url.is_none() {
+        source.missing("ldap.url", "LDAP_URL");
+    }
+    if base_dn.is_none() {
+        source.missing("ldap.base_dn", "LDAP_BASE_DN");
+    }
+    if !config.user_filter.contains("{username}") {
+        source.invalid(
+            "ldap.user_filter",
+            "LDAP_USER_FILTER",
+            "filter must contain {username}".to_string(),
+        );
+    }
+    Some(config)
+}
+
+/// `notifier.file_path` is required only for file notifier
+fn create_notifier_config(source: &mut ConfigSource) -> NotifierConfig {
+    let config = NotifierConfig {
+        kind: source.optional("notifier.kind", "NOTIFIER", NotifierKind::Log),
+        file_path: source.optional_value("notifier.file_path", "NOTIFIER_FILE_PATH"),
+        smtp_host: source.optional("notifier.smtp_host", "SMTP_HOST", "127.0.0.1".to_string()),
+        smtp_port: source.optional("notifier.smtp_port", "SMTP_PORT", 25),
+        from: source.optional(
+            "notifier.from",
+            "NOTIFIER_FROM",
+            "authust@localhost".to_string(),
+        ),
+        timeout_seconds: source.optional("notifier.timeout_seconds", "NOTIFIER_TIMEOUT_SECONDS", 5),
+    };
+    if config.kind == NotifierKind::File && config.file_path.is_none() {
+        source.missing("notifier.file_path", "NOTIFIER_FILE_PATH");
+    }
+    config
+}
+
 #[derive(Clone)]
 pub struct Resources {
     pub db_pool: Pool,
+    pub revocation_cache: Arc<RevocationCache>,
+    pub sign_in_attempts: Arc<dyn StoreSignInAttempts + Send + Sync>,
+    pub rate_limit_buckets: Arc<TokenBuckets>,
+    /// client of upstream identity providers
+    pub http_client: reqwest::Client,
 }
 
 impl Resources {
     pub async fn create_resources(config: &Config) -> Resources {
-        let db_pool = create_pool(&config);
-        Resources { db_pool }
+        let db_pool = create_pool(config);
+        let revocation_cache = Arc::new(RevocationCache::new(Duration::from_secs(
+            config.security_config.revocation_cache_seconds.into(),
+        )));
+        let throttling_config = &config.sign_in_throttling_config;
+        let sign_in_attempts: Arc<dyn StoreSignInAttempts + Send + Sync> =
+            match throttling_config.store {
+                AttemptsStoreKind::Memory => Arc::new(MemorySignInAttemptsRepo::new(
+                    Duration::from_secs(throttling_config.window_seconds.into()),
+                )),
+                AttemptsStoreKind::Postgres => Arc::new(SignInAttemptsRepo::new(
+                    db_pool.clone(),
+                    Duration::from_secs(throttling_config.window_seconds.into()),
+                )),
+            };
+        Resources {
+            db_pool,
+            revocation_cache,
+            sign_in_attempts,
+            rate_limit_buckets: Arc::new(TokenBuckets::new()),
+            http_client: create_http_client(),
+        }
     }
 }
 
+fn create_http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Building http client failured")
+}
+
 fn create_pool(config: &Config) -> Pool {
     let mut pg_config = tokio_postgres::Config::new();
     pg_config.user(&config.database_config.user);
@@ -76,13 +872,25 @@ fn create_pool(config: &Config) -> Pool {
     pg_config.host(&config.database_config.host);
     pg_config.port(config.database_config.port);
     pg_config.application_name(&config.service_name);
+    pg_config.ssl_mode(match config.database_config.ssl_mode {
+        PgSslMode::Disable => SslMode::Disable,
+        PgSslMode::Prefer => SslMode::Prefer,
+        _ => SslMode::Require,
+    });
     let mgr_config = ManagerConfig {
         recycling_method: RecyclingMethod::Fast,
     };
-    let mgr = Manager::from_config(pg_config, NoTls, mgr_config);
-    let pool = Pool::builder(mgr)
+    let mgr = match &config.database_config.tls_config {
+        Some(tls_config) => Manager::from_config(
+            pg_config,
+            MakeRustlsConnect::new(tls_config.as_ref().clone()),
+            mgr_config,
+        ),
+        None => Manager::from_config(pg_config, NoTls, mgr_config),
+    };
+
+    Pool::builder(mgr)
         .max_size(config.database_config.pool_max_size)
         .build()
-        .expect("Building postgres pool failured");
-    pool
+        .expect("Building postgres pool failured")
 }
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadError(String),
+    ParseError(String),
+    ValidationError(Vec<ConfigFieldError>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigFieldError {
+    MissingValue {
+        key: String,
+        env: String,
+    },
+    InvalidValue {
+        key: String,
+        env: String,
+        reason: String,
+    },
+    UnknownKey(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::ReadError(e) => write!(f, "can not read config file: {}", e),
+            ConfigError::ParseError(e) => write!(f, "can not parse config file: {}", e),
+            ConfigError::ValidationError(errors) => {
+                write!(f, "wrong config:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConfigFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigFieldError::MissingValue { key, env } => {
+                write!(f, "{} (env {}) is required", key, env)
+            }
+            ConfigFieldError::InvalidValue { key, env, reason } => {
+                write!(f, "{} (env {}) is wrong: {}", key, env, reason)
+            }
+            ConfigFieldError::UnknownKey(key) => write!(f, "{} is unknown key", key),
+        }
+    }
+}
+
+/// Reads TOML or YAML file (chosen by extension) into flat `section.key` map
+pub fn read_config_file(path: &Path) -> Result<HashMap<String, String>, ConfigError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| ConfigError::ReadError(format!("{:?}: {}", path, e)))?;
+    let value: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            toml::from_str(&content).map_err(|e| ConfigError::ParseError(e.to_string()))?
+        }
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(&content).map_err(|e| ConfigError::ParseError(e.to_string()))?
+        }
+        _ => {
+            return Err(ConfigError::ParseError(format!(
+                "{:?}: expected .toml, .yaml or .yml file",
+                path
+            )))
+        }
+    };
+    let mut values = HashMap::new();
+    flatten_value("", value, &mut values);
+    Ok(values)
+}
+
+fn flatten_value(prefix: &str, value: Value, values: &mut HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = match prefix.is_empty() {
+                    true => key,
+                    false => format!("{}.{}", prefix, key),
+                };
+                flatten_value(&key, value, values);
+            }
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.into_iter().map(scalar_to_string).collect();
+            values.insert(prefix.to_string(), items.join(","));
+        }
+        Value::Null => (),
+        value => {
+            values.insert(prefix.to_string(), scalar_to_string(value));
+        }
+    }
+}
+
+fn scalar_to_string(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        value => value.to_string(),
+    }
+}
+
+/// Values from env override values from file, every problem is collected and reported by `finish`
+pub struct ConfigSource {
+    file_values: HashMap<String, String>,
+    env_values: HashMap<String, String>,
+    known_keys: HashSet<String>,
+    errors: Vec<ConfigFieldError>,
+}
+
+impl ConfigSource {
+    pub fn new(
+        file_values: HashMap<String, String>,
+        env_values: HashMap<String, String>,
+    ) -> ConfigSource {
+        ConfigSource {
+            file_values,
+            env_values,
+            known_keys: HashSet::new(),
+            errors: vec![],
+        }
+    }
+
+    pub fn raw(&mut self, key: &str, env: &str) -> Option<String> {
+        self.known_keys.insert(key.to_string());
+        self.env_values
+            .get(env)
+            .or_else(|| self.file_values.get(key))
+            .cloned()
+    }
+
+    /// Missing or wrong value is reported, returned default is never used in that case
+    pub fn required<T>(&mut self, key: &str, env: &str) -> T
+    where
+        T: FromStr + Default,
+        T::Err: fmt::Display,
+    {
+        match self.raw(key, env) {
+            Some(raw) => self.parse(key, env, &raw).unwrap_or_default(),
+            None => {
+                self.missing(key, env);
+                T::default()
+            }
+        }
+    }
+
+    pub fn optional<T>(&mut self, key: &str, env: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.optional_value(key, env).unwrap_or(default)
+    }
+
+    pub fn optional_value<T>(&mut self, key: &str, env: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match self.raw(key, env) {
+            Some(raw) if !raw.is_empty() => self.parse(key, env, &raw),
+            _ => None,
+        }
+    }
+
+    pub fn missing(&mut self, key: &str, env: &str) {
+        self.errors.push(ConfigFieldError::MissingValue {
+            key: key.to_string(),
+            env: env.to_string(),
+        });
+    }
+
+    pub fn invalid(&mut self, key: &str, env: &str, reason: String) {
+        self.errors.push(ConfigFieldError::InvalidValue {
+            key: key.to_string(),
+            env: env.to_string(),
+            reason,
+        });
+    }
+
+    pub fn finish(mut self) -> Result<(), ConfigError> {
+        let mut unknown_keys: Vec<&String> = self
+            .file_values
+            .keys()
+            .filter(|key| !self.known_keys.contains(*key))
+            .collect();
+        unknown_keys.sort();
+        let unknown_keys: Vec<ConfigFieldError> = unknown_keys
+            .into_iter()
+            .map(|key| ConfigFieldError::UnknownKey(key.clone()))
+            .collect();
+        self.errors.extend(unknown_keys);
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::ValidationError(self.errors)),
+        }
+    }
+
+    fn parse<T>(&mut self, key: &str, env: &str, raw: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match raw.parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.invalid(key, env, e.to_string());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::any::Any;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring, CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig,
+    SignatureScheme,
+};
+use x509_parser::extensions::GeneralName;
+use x509_parser::parse_x509_certificate;
+
+#[derive(Debug)]
+pub enum TlsError {
+    ReadError(String),
+    InvalidCertificateError(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsError::ReadError(e) => write!(f, "can not read {}", e),
+            TlsError::InvalidCertificateError(e) => write!(f, "invalid certificate: {}", e),
+        }
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::ReadError(format!("{}: {}", path, e)))?;
+    match certs.is_empty() {
+        true => Err(TlsError::ReadError(format!("{}: no certificates", path))),
+        false => Ok(certs),
+    }
+}
+
+pub fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, TlsError> {
+    let certs = load_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| TlsError::ReadError(format!("{}: {}", key_path, e)))?;
+    let certified_key = CertifiedKey::from_der(certs, key, &crypto_provider())
+        .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+    Ok(certified_key)
+}
+
+/// Listener certificate, files are read again by `reload` without restart
+#[derive(Debug)]
+pub struct ReloadableCertResolver {
+    cert_path: String,
+    key_path: String,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCertResolver {
+    pub fn new(cert_path: &str, key_path: &str) -> Result<ReloadableCertResolver, TlsError> {
+        let certified_key = load_certified_key(cert_path, key_path)?;
+        Ok(ReloadableCertResolver {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            certified_key: RwLock::new(Arc::new(certified_key)),
+        })
+    }
+
+    /// Previous certificate stays in use if new files are broken
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let certified_key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.certified_key.write().unwrap() = Arc::new(certified_key);
+        Ok(())
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.certified_key.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+pub fn load_root_certs(path: &str) -> Result<Arc<RootCertStore>, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+/// Client certificates are optional, but when presented they must be signed by `client_roots`
+pub fn create_server_tls_config(
+    resolver: Arc<ReloadableCertResolver>,
+    client_roots: Option<Arc<RootCertStore>>,
+) -> Result<ServerConfig, TlsError> {
+    let provider = crypto_provider();
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+    let builder = match client_roots {
+        Some(client_roots) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(client_roots, provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(builder.with_cert_resolver(resolver))
+}
+
+/// Verified end-entity certificate of the client, stored in connection data
+#[derive(Clone)]
+pub struct ClientCertificate(pub CertificateDer<'static>);
+
+/// `on_connect` callback of the listener
+pub fn store_client_certificate(connection: &dyn Any, data: &mut Extensions) {
+    if let Some(tls_stream) = connection.downcast_ref::<TlsStream<TcpStream>>() {
+        let (_, session) = tls_stream.get_ref();
+        if let Some(cert) = session.peer_certificates().and_then(|certs| certs.first()) {
+            data.insert(ClientCertificate(cert.clone().into_owned()));
+        }
+    }
+}
+
+/// DNS names and URIs from subject alternative names, then subject common names
+pub fn certificate_identities(cert: &[u8]) -> Result<Vec<String>, TlsError> {
+    let (_, cert) = parse_x509_certificate(cert)
+        .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+    let mut identities = vec![];
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in san.value.general_names.iter() {
+            match name {
+                GeneralName::DNSName(name) | GeneralName::URI(name) => {
+                    identities.push(name.to_string())
+                }
+                _ => (),
+            }
+        }
+    }
+    for common_name in cert.subject().iter_common_name() {
+        if let Ok(common_name) = common_name.as_str() {
+            identities.push(common_name.to_string());
+        }
+    }
+    Ok(identities)
+}
+
+/// The same meaning as libpq `sslmode`, `prefer` and `require` don't verify server certificate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PgSslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl FromStr for PgSslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disable" => Ok(PgSslMode::Disable),
+            "prefer" => Ok(PgSslMode::Prefer),
+            "require" => Ok(PgSslMode::Require),
+            "verify-ca" => Ok(PgSslMode::VerifyCa),
+            "verify-full" => Ok(PgSslMode::VerifyFull),
+            _ => Err(format!(
+                "expected disable, prefer, require, verify-ca or verify-full, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// Client config for postgres connections, None for `disable` mode
+pub fn create_pg_tls_config(
+    ssl_mode: PgSslMode,
+    ca_cert_path: Option<&str>,
+) -> Result<Option<Arc<ClientConfig>>, TlsError> {
+    let provider = crypto_provider();
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+    let config = match ssl_mode {
+        PgSslMode::Disable => return Ok(None),
+        PgSslMode::Prefer | PgSslMode::Require => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoCertificateVerification {
+                algorithms: provider.signature_verification_algorithms,
+            }))
+            .with_no_client_auth(),
+        PgSslMode::VerifyCa | PgSslMode::VerifyFull => {
+            let ca_cert_path = ca_cert_path.ok_or_else(|| {
+                TlsError::ReadError("CA bundle, it is required for verify modes".to_string())
+            })?;
+            let roots = load_root_certs(ca_cert_path)?;
+            let verifier = WebPkiServerVerifier::builder_with_provider(roots, provider)
+                .build()
+                .map_err(|e| TlsError::InvalidCertificateError(e.to_string()))?;
+            match ssl_mode {
+                PgSslMode::VerifyCa => builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(SkipHostnameVerification {
+                        inner: verifier,
+                    }))
+                    .with_no_client_auth(),
+                _ => builder.with_webpki_verifier(verifier).with_no_client_auth(),
+            }
+        }
+    };
+    Ok(Some(Arc::new(config)))
+}
+
+#[derive(Debug)]
+struct NoCertificateVerification {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Verifies certificate chain, but accepts any host name, like libpq `verify-ca`
+#[derive(Debug)]
+struct SkipHostnameVerification {
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl ServerCertVerifier for SkipHostnameVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
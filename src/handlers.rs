@@ -1,2 +1,3 @@
 pub mod api;
 pub mod system;
+pub mod well_known;
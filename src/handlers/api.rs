@@ -1,3 +1,7 @@
+pub mod api_keys;
+pub mod federation;
+pub mod mfa;
+pub mod oauth;
 pub mod permissions;
 pub mod roles;
 pub mod users;
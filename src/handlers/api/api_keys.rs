@@ -0,0 +1,66 @@
+use crate::common::Resources;
+use crate::storage::postgres::api_key_repo::ApiKeyRepo;
+use crate::usecases::api_keys::api_key_manager;
+use crate::usecases::api_keys::entities::ApiKeyRequest;
+use crate::usecases::api_keys::errors::ApiKeyError;
+use crate::usecases::users::entities::AuthenticatedUser;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use log::error;
+use web::Data;
+
+/// Keys are managed by the user with own access token, no permission is required
+#[post("api_keys")]
+pub async fn create_api_key_handler(
+    user: web::ReqData<AuthenticatedUser>,
+    api_key_data: web::Json<ApiKeyRequest>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let api_key_access_model = ApiKeyRepo::new(resources.db_pool.clone());
+    match api_key_manager::create_api_key(&api_key_access_model, &user, api_key_data.into_inner())
+        .await
+    {
+        Ok(api_key) => HttpResponse::Created().json(api_key),
+        Err(ApiKeyError::ValidationError(e)) => HttpResponse::BadRequest().body(e),
+        Err(ApiKeyError::ForbiddenError) => HttpResponse::Forbidden().body("Forbidden"),
+        Err(ApiKeyError::AlreadyExists) => HttpResponse::Conflict().body("already exists"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[get("api_keys")]
+pub async fn api_keys_listing_handler(
+    user: web::ReqData<AuthenticatedUser>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let api_key_access_model = ApiKeyRepo::new(resources.db_pool.clone());
+    match api_key_manager::get_api_keys(&api_key_access_model, &user).await {
+        Ok(api_keys) => HttpResponse::Ok().json(api_keys),
+        Err(ApiKeyError::ForbiddenError) => HttpResponse::Forbidden().body("Forbidden"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[delete("api_keys/{api_key_id}")]
+pub async fn revoke_api_key_handler(
+    user: web::ReqData<AuthenticatedUser>,
+    api_key_id: web::Path<u32>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let api_key_id = api_key_id.into_inner() as i32;
+    let api_key_access_model = ApiKeyRepo::new(resources.db_pool.clone());
+    match api_key_manager::revoke_api_key(&api_key_access_model, &user, api_key_id).await {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(ApiKeyError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(ApiKeyError::ForbiddenError) => HttpResponse::Forbidden().body("Forbidden"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
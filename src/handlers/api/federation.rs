@@ -0,0 +1,132 @@
+use crate::common::{Config, Resources};
+use crate::storage::http::upstream_idp::HttpUpstreamIdp;
+use crate::storage::postgres::federation_repo::FederationRepo;
+use crate::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
+use crate::storage::postgres::user_repo::UserRepo;
+use crate::usecases::federation::entities::FederatedCallback;
+use crate::usecases::federation::errors::FederationError;
+use crate::usecases::federation::federated_sign_in::{
+    authenticate_federated_user, find_provider, start_federated_login, FEDERATED_LOGIN_MINUTES,
+};
+use crate::usecases::users::crypto::issue_tokens_for_user;
+use crate::usecases::users::errors::SignError;
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header::{self, CacheControl, CacheDirective};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use log::error;
+use web::Data;
+
+fn federation_error_response(e: FederationError) -> HttpResponse {
+    match e {
+        FederationError::NotFoundError => HttpResponse::NotFound().body("Not Found"),
+        FederationError::InvalidStateError => {
+            HttpResponse::BadRequest().body("login is expired, start it again")
+        }
+        FederationError::UpstreamError | FederationError::InvalidIdTokenError => {
+            HttpResponse::Unauthorized().body("Unauthorized")
+        }
+        FederationError::NotLinkedError | FederationError::VerificationError => {
+            HttpResponse::Forbidden().body("Forbidden")
+        }
+        FederationError::AlreadyExists => {
+            HttpResponse::Conflict().body("username is taken by another user")
+        }
+        FederationError::FatalError | FederationError::TemporaryError => {
+            error!("Usecase fatal error during federated sign in: {:?}", e);
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+const STATE_COOKIE: &str = "federation_state";
+
+/// Cookie is sent only to endpoints of the provider, the callback is reached by top-level redirect
+fn state_cookie(req: &HttpRequest, state: String) -> Cookie<'static> {
+    let path = req
+        .path()
+        .trim_end_matches("login")
+        .trim_end_matches("callback");
+    Cookie::build(STATE_COOKIE, state)
+        .path(path.to_string())
+        .max_age(time::Duration::minutes(FEDERATED_LOGIN_MINUTES))
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+/// Redirects the user agent to upstream provider, the state is also kept in the cookie
+#[get("federation/{provider}/login")]
+pub async fn federated_login_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    provider: web::Path<String>,
+) -> impl Responder {
+    let provider = match find_provider(&config.federation_config, &provider) {
+        Ok(provider) => provider,
+        Err(e) => return federation_error_response(e),
+    };
+    let federation_repo = FederationRepo::new(resources.db_pool.clone());
+    match start_federated_login(&federation_repo, provider).await {
+        Ok(redirect) => HttpResponse::Found()
+            .insert_header((header::LOCATION, redirect.location))
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .cookie(state_cookie(&req, redirect.state))
+            .finish(),
+        Err(e) => federation_error_response(e),
+    }
+}
+
+/// Redirect URI registered in upstream provider, returns tokens of this service
+#[get("federation/{provider}/callback")]
+pub async fn federated_callback_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    provider: web::Path<String>,
+    callback: web::Query<FederatedCallback>,
+) -> impl Responder {
+    let provider = match find_provider(&config.federation_config, &provider) {
+        Ok(provider) => provider,
+        Err(e) => return federation_error_response(e),
+    };
+    let user_id = match authenticate_federated_user(
+        &FederationRepo::new(resources.db_pool.clone()),
+        &HttpUpstreamIdp::new(resources.http_client.clone()),
+        provider,
+        callback.into_inner(),
+        req.cookie(STATE_COOKIE).as_ref().map(Cookie::value),
+    )
+    .await
+    {
+        Ok(user_id) => user_id,
+        Err(e) => return federation_error_response(e),
+    };
+    match issue_tokens_for_user(
+        &UserRepo::new(resources.db_pool.clone()),
+        &RevocationRepo::new(resources.db_pool.clone()),
+        &RefreshTokenRepo::new(resources.db_pool.clone()),
+        &config.security_config,
+        user_id,
+    )
+    .await
+    {
+        Ok(signed_info) => {
+            let mut cookie = state_cookie(&req, String::new());
+            cookie.make_removal();
+            HttpResponse::Ok()
+                .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+                .cookie(cookie)
+                .json(signed_info)
+        }
+        Err(SignError::VerificationError) => {
+            federation_error_response(FederationError::VerificationError)
+        }
+        Err(e) => {
+            error!("Usecase fatal error during token issuing: {:?}", e);
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
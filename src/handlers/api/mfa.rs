@@ -0,0 +1,155 @@
+use crate::common::{Config, Resources};
+use crate::storage::memory::revocation_cache::CachedRevocationRepo;
+use crate::storage::postgres::mfa_repo::MfaRepo;
+use crate::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
+use crate::storage::postgres::user_repo::UserRepo;
+use crate::usecases::users::entities::{AuthenticatedUser, MfaVerification};
+use crate::usecases::users::errors::{MfaError, SignError};
+use crate::usecases::users::{crypto, mfa};
+use actix_web::http::header::{self, Header};
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
+use log::error;
+use serde::Deserialize;
+use web::Data;
+
+/// TOTP is managed by the user with own access token
+async fn authenticate(
+    req: &HttpRequest,
+    resources: &Resources,
+    config: &Config,
+) -> Result<AuthenticatedUser, HttpResponse> {
+    let auth_header = match Authorization::<Bearer>::parse(req) {
+        Ok(header) => header,
+        Err(_) => return Err(HttpResponse::Unauthorized().body("Unauthorized")),
+    };
+    let user_access_model = UserRepo::new(resources.db_pool.clone());
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    match crypto::authenticate_by_jwt(
+        &user_access_model,
+        &revocation_access_model,
+        &config.security_config,
+        auth_header.as_ref().token(),
+    )
+    .await
+    {
+        Ok(user) => Ok(user),
+        Err(SignError::VerificationError | SignError::ExpiredTokenError) => {
+            Err(HttpResponse::Unauthorized().body("Unauthorized"))
+        }
+        Err(_) => {
+            error!("Usecase fatal error during token checking");
+            Err(HttpResponse::InternalServerError().body("internal error"))
+        }
+    }
+}
+
+#[post("totp/enroll")]
+pub async fn enroll_totp_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+) -> impl Responder {
+    let user = match authenticate(&req, &resources, &config).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let user_access_model = UserRepo::new(resources.db_pool.clone());
+    let mfa_access_model = MfaRepo::new(resources.db_pool.clone());
+    match mfa::enroll_totp(
+        &user_access_model,
+        &mfa_access_model,
+        &config.security_config,
+        user.user_id,
+    )
+    .await
+    {
+        Ok(enrollment) => HttpResponse::Ok().json(enrollment),
+        Err(MfaError::AlreadyEnabledError) => HttpResponse::Conflict().body("already enabled"),
+        Err(MfaError::NotFoundError) => HttpResponse::Unauthorized().body("Unauthorized"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeScheme {
+    code: String,
+}
+
+#[post("totp/confirm")]
+pub async fn confirm_totp_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    code_data: web::Json<TotpCodeScheme>,
+) -> impl Responder {
+    let user = match authenticate(&req, &resources, &config).await {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let mfa_access_model = MfaRepo::new(resources.db_pool.clone());
+    match mfa::confirm_totp(&mfa_access_model, user.user_id, &code_data.code).await {
+        Ok(recovery_codes) => HttpResponse::Ok().json(recovery_codes),
+        Err(MfaError::VerificationError) => HttpResponse::Forbidden().body("wrong code"),
+        Err(MfaError::NotEnrolledError) => HttpResponse::NotFound().body("not enrolled"),
+        Err(MfaError::AlreadyEnabledError) => HttpResponse::Conflict().body("already enabled"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MfaVerificationScheme {
+    mfa_token: String,
+    code: Option<String>,
+    recovery_code: Option<String>,
+}
+
+#[post("mfa/verify")]
+pub async fn verify_mfa_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    verification_data: web::Json<MfaVerificationScheme>,
+) -> impl Responder {
+    let verification_data = verification_data.into_inner();
+    let verification = MfaVerification {
+        mfa_token: verification_data.mfa_token,
+        code: verification_data.code,
+        recovery_code: verification_data.recovery_code,
+        client_ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    };
+    let mfa_access_model = MfaRepo::new(resources.db_pool.clone());
+    let user_access_model = UserRepo::new(resources.db_pool.clone());
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match mfa::verify_mfa(
+        resources.sign_in_attempts.as_ref(),
+        &config.sign_in_throttling_config,
+        &mfa_access_model,
+        &user_access_model,
+        &token_access_model,
+        &config.security_config,
+        verification,
+    )
+    .await
+    {
+        Ok(signed_info) => HttpResponse::Ok().json(signed_info),
+        Err(SignError::VerificationError) => HttpResponse::Forbidden().body("Forbidden"),
+        Err(SignError::TooManyAttemptsError(retry_after)) => HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .body("Too many attempts"),
+        Err(_) => {
+            error!("Usecase fatal error during mfa verification");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
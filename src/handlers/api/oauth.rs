@@ -0,0 +1,357 @@
+pub mod views;
+
+use crate::common::{Config, Resources};
+use crate::handlers::api::oauth::views::{
+    error_page, login_page, LoginFormScheme, TokenRequestScheme,
+};
+use crate::storage::ldap::directory::LdapDirectory;
+use crate::storage::memory::revocation_cache::CachedRevocationRepo;
+use crate::storage::postgres::authorization_code_repo::AuthorizationCodeRepo;
+use crate::storage::postgres::directory_repo::DirectoryRepo;
+use crate::storage::postgres::mfa_repo::MfaRepo;
+use crate::storage::postgres::oauth_client_repo::OAuthClientRepo;
+use crate::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
+use crate::storage::postgres::user_repo::UserRepo;
+use crate::usecases::oauth::entities::{
+    redirect_url, AuthorizationRequest, CodeExchange, ResourceOwnerLogin,
+    ValidAuthorizationRequest, AUTHORIZATION_CODE_GRANT, CLIENT_CREDENTIALS_GRANT,
+};
+use crate::usecases::oauth::errors::{AuthorizationError, OAuthError};
+use crate::usecases::oauth::{authorization_code, client_credentials, oidc};
+use crate::usecases::users::entities::SignInAttempt;
+use crate::usecases::users::errors::SignError;
+use crate::usecases::users::ldap_sign_in::LdapSignInVerification;
+use actix_web::http::header::{self, CacheControl, CacheDirective, ContentType, Header};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, route, web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::headers::authorization::{Authorization, Basic, Bearer};
+use log::error;
+use serde_json::json;
+use web::Data;
+
+fn oauth_error_code(e: &OAuthError) -> &'static str {
+    match e {
+        OAuthError::InvalidRequestError => "invalid_request",
+        OAuthError::InvalidClientError => "invalid_client",
+        OAuthError::InvalidGrantError => "invalid_grant",
+        OAuthError::UnauthorizedClientError => "unauthorized_client",
+        OAuthError::UnsupportedGrantTypeError => "unsupported_grant_type",
+        OAuthError::UnsupportedResponseTypeError => "unsupported_response_type",
+        OAuthError::InvalidScopeError => "invalid_scope",
+        OAuthError::AccessDeniedError => "access_denied",
+        OAuthError::TemporaryError => "temporarily_unavailable",
+        _ => "server_error",
+    }
+}
+
+/// Error response, RFC 6749 section 5.2
+fn oauth_error_response(status: StatusCode, error: &str) -> HttpResponse {
+    let mut response = HttpResponse::build(status);
+    if status == StatusCode::UNAUTHORIZED {
+        response.insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"oauth\""));
+    }
+    response
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(json!({ "error": error }))
+}
+
+fn oauth_usecase_error_response(e: OAuthError) -> HttpResponse {
+    match e {
+        OAuthError::InvalidClientError => {
+            oauth_error_response(StatusCode::UNAUTHORIZED, oauth_error_code(&e))
+        }
+        OAuthError::FatalError | OAuthError::TemporaryError => {
+            error!("Usecase fatal error during token issuing");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+        _ => oauth_error_response(StatusCode::BAD_REQUEST, oauth_error_code(&e)),
+    }
+}
+
+/// Client authenticates with HTTP Basic or with client_id and client_secret in the form,
+/// public clients send only client_id
+fn client_credentials_from_request(
+    req: &HttpRequest,
+    form: &TokenRequestScheme,
+) -> Option<(String, Option<String>)> {
+    if let Ok(auth_header) = Authorization::<Basic>::parse(req) {
+        let client_secret = auth_header.as_ref().password().map(|s| s.to_string());
+        return Some((auth_header.as_ref().user_id().to_string(), client_secret));
+    }
+    form.client_id
+        .as_ref()
+        .map(|client_id| (client_id.clone(), form.client_secret.clone()))
+}
+
+#[post("oauth/token")]
+pub async fn oauth_token_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    form: web::Form<TokenRequestScheme>,
+) -> impl Responder {
+    let form = form.into_inner();
+    let grant_type = match &form.grant_type {
+        Some(grant_type) => grant_type.clone(),
+        None => return oauth_error_response(StatusCode::BAD_REQUEST, "invalid_request"),
+    };
+    let (client_id, client_secret) = match client_credentials_from_request(&req, &form) {
+        Some(credentials) => credentials,
+        None => return oauth_error_response(StatusCode::UNAUTHORIZED, "invalid_client"),
+    };
+    let client_access_model = OAuthClientRepo::new(resources.db_pool.clone());
+    let result = match grant_type.as_str() {
+        CLIENT_CREDENTIALS_GRANT => {
+            client_credentials::issue_client_credentials_token(
+                &client_access_model,
+                &config.security_config,
+                &client_id,
+                client_secret.as_deref(),
+                form.scope.as_deref(),
+            )
+            .await
+        }
+        AUTHORIZATION_CODE_GRANT => {
+            let code = match form.code {
+                Some(code) => code,
+                None => return oauth_error_response(StatusCode::BAD_REQUEST, "invalid_request"),
+            };
+            let exchange = CodeExchange {
+                client_id,
+                client_secret,
+                code,
+                redirect_uri: form.redirect_uri,
+                code_verifier: form.code_verifier,
+            };
+            let user_access_model = UserRepo::new(resources.db_pool.clone());
+            authorization_code::exchange_authorization_code(
+                &client_access_model,
+                &AuthorizationCodeRepo::new(resources.db_pool.clone()),
+                &user_access_model,
+                &user_access_model,
+                &RefreshTokenRepo::new(resources.db_pool.clone()),
+                &config.security_config,
+                exchange,
+            )
+            .await
+        }
+        _ => Err(OAuthError::UnsupportedGrantTypeError),
+    };
+    match result {
+        Ok(token_info) => HttpResponse::Ok()
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .json(token_info),
+        Err(e) => oauth_usecase_error_response(e),
+    }
+}
+
+fn html_response(status: StatusCode, body: String) -> HttpResponse {
+    HttpResponse::build(status)
+        .insert_header(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .insert_header((header::X_FRAME_OPTIONS, "DENY"))
+        .insert_header((
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; style-src 'unsafe-inline'; frame-ancestors 'none'",
+        ))
+        .body(body)
+}
+
+fn redirect_response(location: String) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
+
+fn redirect_with_error(redirect_uri: &str, state: Option<&str>, e: &OAuthError) -> HttpResponse {
+    let mut params = vec![("error", oauth_error_code(e))];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    redirect_response(redirect_url(redirect_uri, &params))
+}
+
+fn authorization_error_response(e: AuthorizationError) -> HttpResponse {
+    match e {
+        AuthorizationError::ClientError(
+            e @ (OAuthError::FatalError | OAuthError::TemporaryError),
+        ) => {
+            error!(
+                "Usecase fatal error during authorization request checking: {:?}",
+                e
+            );
+            HttpResponse::InternalServerError().body("internal error")
+        }
+        AuthorizationError::ClientError(OAuthError::InvalidRedirectUriError) => html_response(
+            StatusCode::BAD_REQUEST,
+            error_page("Redirect URI is not registered for the client"),
+        ),
+        AuthorizationError::ClientError(_) => html_response(
+            StatusCode::BAD_REQUEST,
+            error_page("Unknown client or malformed authorization request"),
+        ),
+        AuthorizationError::RedirectError {
+            redirect_uri,
+            state,
+            error,
+        } => redirect_with_error(&redirect_uri, state.as_deref(), &error),
+    }
+}
+
+async fn validate_request(
+    resources: &Resources,
+    request: &AuthorizationRequest,
+) -> Result<ValidAuthorizationRequest, HttpResponse> {
+    let client_access_model = OAuthClientRepo::new(resources.db_pool.clone());
+    authorization_code::validate_authorization_request(&client_access_model, request)
+        .await
+        .map_err(authorization_error_response)
+}
+
+/// Login and consent page of authorization code flow
+#[get("oauth/authorize")]
+pub async fn authorize_page_handler(
+    resources: Data<Resources>,
+    request: web::Query<AuthorizationRequest>,
+) -> impl Responder {
+    let request = request.into_inner();
+    match validate_request(&resources, &request).await {
+        Ok(valid_request) => {
+            html_response(StatusCode::OK, login_page(&request, &valid_request, None))
+        }
+        Err(response) => response,
+    }
+}
+
+#[post("oauth/authorize")]
+pub async fn authorize_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+    form: web::Form<LoginFormScheme>,
+) -> impl Responder {
+    let form = form.into_inner();
+    let request = form.authorization_request();
+    let valid_request = match validate_request(&resources, &request).await {
+        Ok(valid_request) => valid_request,
+        Err(response) => return response,
+    };
+    if form.decision != "allow" {
+        return redirect_with_error(
+            &valid_request.redirect_uri,
+            valid_request.state.as_deref(),
+            &OAuthError::AccessDeniedError,
+        );
+    }
+    let attempt = SignInAttempt {
+        username: form.username,
+        password: form.password,
+        client_ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    };
+    let login = ResourceOwnerLogin {
+        attempt,
+        totp_code: form.totp_code.filter(|code| !code.is_empty()),
+    };
+    match authorization_code::authorize(
+        resources.sign_in_attempts.as_ref(),
+        &config.sign_in_throttling_config,
+        &MfaRepo::new(resources.db_pool.clone()),
+        &LdapSignInVerification::new(
+            UserRepo::new(resources.db_pool.clone()),
+            LdapDirectory::new(),
+            DirectoryRepo::new(resources.db_pool.clone()),
+            config.ldap_config.as_ref(),
+        ),
+        &AuthorizationCodeRepo::new(resources.db_pool.clone()),
+        &valid_request,
+        login,
+    )
+    .await
+    {
+        Ok(code) => {
+            let mut params = vec![("code", code.as_str())];
+            if let Some(state) = valid_request.state.as_deref() {
+                params.push(("state", state));
+            }
+            redirect_response(redirect_url(&valid_request.redirect_uri, &params))
+        }
+        Err(SignError::VerificationError) => html_response(
+            StatusCode::UNAUTHORIZED,
+            login_page(
+                &request,
+                &valid_request,
+                Some("Wrong username, password or one-time code"),
+            ),
+        ),
+        Err(SignError::TooManyAttemptsError(retry_after)) => {
+            let mut response = html_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                login_page(
+                    &request,
+                    &valid_request,
+                    Some(&format!(
+                        "Too many attempts, try again in {} seconds",
+                        retry_after
+                    )),
+                ),
+            );
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, header::HeaderValue::from(retry_after));
+            response
+        }
+        Err(_) => {
+            error!("Usecase fatal error during authorization");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+/// Bearer token error, RFC 6750 section 3
+fn bearer_error_response(error: &str) -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((
+            header::WWW_AUTHENTICATE,
+            format!("Bearer error=\"{}\"", error),
+        ))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
+
+/// OpenID Connect UserInfo endpoint, accepts user access token
+#[route("oauth/userinfo", method = "GET", method = "POST")]
+pub async fn userinfo_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+) -> impl Responder {
+    let auth_header = match Authorization::<Bearer>::parse(&req) {
+        Ok(header) => header,
+        Err(_) => return bearer_error_response("invalid_request"),
+    };
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    match oidc::get_userinfo(
+        &UserRepo::new(resources.db_pool.clone()),
+        &revocation_access_model,
+        &config.security_config,
+        auth_header.as_ref().token(),
+    )
+    .await
+    {
+        Ok(user_info) => HttpResponse::Ok()
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .json(user_info),
+        Err(SignError::VerificationError | SignError::ExpiredTokenError) => {
+            bearer_error_response("invalid_token")
+        }
+        Err(_) => {
+            error!("Usecase fatal error during userinfo request");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::usecases::oauth::entities::{AuthorizationRequest, ValidAuthorizationRequest};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct TokenRequestScheme {
+    pub grant_type: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+/// Login page form, authorization request is passed again in hidden fields
+#[derive(Deserialize)]
+pub struct LoginFormScheme {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+    pub username: String,
+    pub password: String,
+    pub totp_code: Option<String>,
+    pub decision: String,
+}
+
+impl LoginFormScheme {
+    pub fn authorization_request(&self) -> AuthorizationRequest {
+        AuthorizationRequest {
+            response_type: self.response_type.clone(),
+            client_id: self.client_id.clone(),
+            redirect_uri: self.redirect_uri.clone(),
+            scope: self.scope.clone(),
+            state: self.state.clone(),
+            code_challenge: self.code_challenge.clone(),
+            code_challenge_method: self.code_challenge_method.clone(),
+            nonce: self.nonce.clone(),
+        }
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 22rem; margin: 4rem auto; }}
+input {{ display: block; width: 100%; margin: 0.3rem 0 0.8rem; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+",
+        title = escape_html(title),
+        body = body
+    )
+}
+
+pub fn login_page(
+    request: &AuthorizationRequest,
+    valid_request: &ValidAuthorizationRequest,
+    error: Option<&str>,
+) -> String {
+    let hidden_fields: String = [
+        ("response_type", &request.response_type),
+        ("client_id", &request.client_id),
+        ("redirect_uri", &request.redirect_uri),
+        ("scope", &request.scope),
+        ("state", &request.state),
+        ("code_challenge", &request.code_challenge),
+        ("code_challenge_method", &request.code_challenge_method),
+        ("nonce", &request.nonce),
+    ]
+    .iter()
+    .filter_map(|(name, value)| {
+        value.as_ref().map(|value| {
+            format!(
+                "<input type=\"hidden\" name=\"{}\" value=\"{}\">\n",
+                name,
+                escape_html(value)
+            )
+        })
+    })
+    .collect();
+    let scope = match valid_request.scope.is_empty() {
+        true => String::new(),
+        false => format!(
+            "<p>Requested access: <b>{}</b></p>\n",
+            escape_html(&valid_request.scope)
+        ),
+    };
+    let error = error
+        .map(|error| format!("<p class=\"error\">{}</p>\n", escape_html(error)))
+        .unwrap_or_default();
+    let body = format!(
+        "<p><b>{client_id}</b> wants to sign you in.</p>
+{scope}{error}<form method=\"post\">
+{hidden_fields}<label>Username <input name=\"username\" autocomplete=\"username\" required></label>
+<label>Password <input name=\"password\" type=\"password\" autocomplete=\"current-password\" required></label>
+<label>One-time code, if two-factor authentication is enabled
+<input name=\"totp_code\" inputmode=\"numeric\" autocomplete=\"one-time-code\"></label>
+<button name=\"decision\" value=\"allow\">Allow</button>
+<button name=\"decision\" value=\"deny\" formnovalidate>Deny</button>
+</form>",
+        client_id = escape_html(&valid_request.client_id),
+        scope = scope,
+        error = error,
+        hidden_fields = hidden_fields
+    );
+    page("Sign in", &body)
+}
+
+pub fn error_page(error: &str) -> String {
+    page(
+        "Authorization error",
+        &format!("<p class=\"error\">{}</p>", escape_html(error)),
+    )
+}
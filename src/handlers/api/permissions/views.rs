@@ -68,7 +68,7 @@ impl PermissionsFiltersInputScheme {
     ) -> Result<PermissionsFilters, String> {
         let offset = match data.offset {
             None => 0,
-            Some(offset) if 0 <= offset && offset < 9999 => offset,
+            Some(offset) if (0..9999).contains(&offset) => offset,
             _ => return Err("wrong offset value".to_string()),
         };
         let limit = match data.limit {
@@ -1,7 +1,8 @@
 use crate::common::Resources;
 use crate::handlers::api::roles::views::{
     BindingMemberCreationScheme, BindingPermissionCreationScheme, MemberBindingQuery,
-    PermissionBindingQuery, RoleMemberBindingView, RolePermissionBindingView, RoleView,
+    PermissionBindingQuery, RoleListingView, RoleMemberBindingView, RolePermissionBindingView,
+    RoleView, RolesFiltersInputScheme,
 };
 use crate::storage::postgres::role_repo::RoleRepo;
 use crate::usecases::roles::entities::RoleForCreation;
@@ -9,6 +10,7 @@ use crate::usecases::roles::errors::RoleUCError;
 use crate::usecases::roles::role_creator::create_new_role;
 use crate::usecases::roles::role_disabler::disable_role_by_id;
 use crate::usecases::roles::role_get_item::get_role_by_id;
+use crate::usecases::roles::role_get_list::get_roles_by_filters;
 use crate::usecases::roles::role_members_binder::{bind_member_to_role, unbind_member_to_role};
 use crate::usecases::roles::role_permissions_binder::{
     bind_permission_to_role, unbind_permission_to_role,
@@ -35,6 +37,34 @@ pub async fn get_role_handler(
     }
 }
 
+#[get("roles")]
+#[has_permissions("READ_ROLE")]
+pub async fn roles_listing_handler(
+    filters: web::Query<RolesFiltersInputScheme>,
+    resources: web::Data<Resources>,
+) -> impl Responder {
+    let validated_filters = match RolesFiltersInputScheme::new_with_validation(filters.into_inner())
+    {
+        Ok(data) => data,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let offset = validated_filters.offset;
+    let limit = validated_filters.limit;
+    let role_access_model = RoleRepo::new(resources.db_pool.clone());
+    match get_roles_by_filters(&role_access_model, validated_filters).await {
+        Ok(listing) => HttpResponse::Ok().json(RoleListingView::new(
+            listing.roles,
+            offset,
+            limit,
+            listing.total,
+        )),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
 #[post("roles")]
 #[has_permissions("WRITE_ROLE")]
 pub async fn create_role_handler(
@@ -1,4 +1,7 @@
-use crate::usecases::roles::entities::{Role, RoleMemberBinding, RolePermissionBinding};
+use crate::handlers::api::permissions::views::Pagination;
+use crate::usecases::roles::entities::{
+    Role, RoleMemberBinding, RolePermissionBinding, RoleSummary, RolesFilters,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -87,3 +90,57 @@ pub struct MemberBindingQuery {
     pub role_id: i32,
     pub user_id: i32,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct RoleListingView {
+    pub roles: Vec<RoleSummary>,
+    pub pagination: Pagination,
+}
+
+impl RoleListingView {
+    pub fn new(roles: Vec<RoleSummary>, offset: i64, limit: i64, total: i64) -> RoleListingView {
+        RoleListingView {
+            roles,
+            pagination: Pagination {
+                offset,
+                limit,
+                total,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RolesFiltersInputScheme {
+    pub role_name: Option<String>,
+    pub is_deleted: Option<bool>,
+    pub user_id: Option<i32>,
+    pub permission_id: Option<i32>,
+    pub with_counts: Option<bool>,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl RolesFiltersInputScheme {
+    pub fn new_with_validation(data: RolesFiltersInputScheme) -> Result<RolesFilters, String> {
+        let offset = match data.offset {
+            None => 0,
+            Some(offset) if (0..9999).contains(&offset) => offset,
+            _ => return Err("wrong offset value".to_string()),
+        };
+        let limit = match data.limit {
+            None => 100,
+            Some(limit) if 0 < limit && limit <= 1000 => limit,
+            _ => return Err("wrong limit value".to_string()),
+        };
+        Ok(RolesFilters {
+            role_name: data.role_name,
+            is_deleted: data.is_deleted,
+            user_id: data.user_id,
+            permission_id: data.permission_id,
+            with_counts: data.with_counts.unwrap_or(false),
+            offset,
+            limit,
+        })
+    }
+}
@@ -1,11 +1,27 @@
+pub mod views;
+
 use crate::common::{Config, Resources};
+use crate::handlers::api::users::views::{UserListingView, UsersFiltersInputScheme};
+use crate::storage::ldap::directory::LdapDirectory;
+use crate::storage::memory::revocation_cache::CachedRevocationRepo;
+use crate::storage::notify::configured::ConfiguredNotifier;
+use crate::storage::postgres::directory_repo::DirectoryRepo;
+use crate::storage::postgres::mfa_repo::MfaRepo;
+use crate::storage::postgres::password_reset_repo::PasswordResetRepo;
+use crate::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
 use crate::storage::postgres::user_repo::UserRepo;
-use crate::usecases::users::errors::{SignError, UserUCError};
-use crate::usecases::users::{crypto, get_user, user_creator};
-use actix_web::http::header::Header;
+use crate::usecases::users::entities::SignInAttempt;
+use crate::usecases::users::errors::{PasswordResetError, SignError, UserUCError};
+use crate::usecases::users::ldap_sign_in::LdapSignInVerification;
+use crate::usecases::users::{
+    crypto, get_user, password_reset, sign_in_throttler, token_refresher, token_revoker,
+    user_creator, user_disabler,
+};
+use actix_web::http::header::{self, Header};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
 use actix_web_grants::proc_macro::has_permissions;
-use actix_web_httpauth::headers::authorization::{Authorization, Basic};
+use actix_web_httpauth::headers::authorization::{Authorization, Basic, Bearer};
 use log::error;
 use serde::Deserialize;
 use web::Data;
@@ -25,6 +41,34 @@ pub async fn get_user_by_id(user_id: web::Path<u32>, resources: Data<Resources>)
     }
 }
 
+#[get("users")]
+#[has_permissions("READ_USER")]
+pub async fn users_listing_handler(
+    filters: web::Query<UsersFiltersInputScheme>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let validated_filters = match UsersFiltersInputScheme::new_with_validation(filters.into_inner())
+    {
+        Ok(data) => data,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let offset = validated_filters.offset;
+    let limit = validated_filters.limit;
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    match get_user::get_users_by_filters(&user_repo, validated_filters).await {
+        Ok(listing) => HttpResponse::Ok().json(UserListingView::new(
+            listing.users,
+            offset,
+            limit,
+            listing.total,
+        )),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
 #[delete("users/{user_id}")]
 #[has_permissions("WRITE_USER")]
 pub async fn delete_user_by_id(
@@ -33,7 +77,19 @@ pub async fn delete_user_by_id(
 ) -> impl Responder {
     let user_id = user_id.into_inner() as i32;
     let user_repo = UserRepo::new(resources.db_pool.clone());
-    match get_user::remove_user_by_id(&user_repo, user_id).await {
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match get_user::remove_user_by_id(
+        &user_repo,
+        &revocation_access_model,
+        &token_access_model,
+        user_id,
+    )
+    .await
+    {
         Ok(_) => HttpResponse::NoContent().body(""),
         Err(UserUCError::NotFoundError) => HttpResponse::NoContent().body(""),
         Err(_) => {
@@ -48,6 +104,8 @@ pub async fn delete_user_by_id(
 pub struct UserCreationScheme {
     username: String,
     password: String,
+    /// address for password reset
+    email: Option<String>,
 }
 
 #[post("users")]
@@ -58,9 +116,11 @@ pub async fn create_user_handler(
 ) -> impl Responder {
     let username = user_data.username.to_string();
     let password = user_data.password.to_string();
+    let email = user_data.email.clone();
     let user_access_model = UserRepo::new(resources.db_pool.clone());
-    match user_creator::create_new_user(&user_access_model, username, password).await {
+    match user_creator::create_new_user(&user_access_model, username, password, email).await {
         Ok(user) => HttpResponse::Created().json(user),
+        Err(UserUCError::ValidationError(e)) => HttpResponse::BadRequest().body(e),
         Err(_) => {
             error!("usecase error");
             HttpResponse::InternalServerError().body("internal error")
@@ -83,17 +143,35 @@ pub async fn sign_in_user_handler(
         Some(cow_pass) => cow_pass.to_string(),
         None => return HttpResponse::Forbidden().body("Forbidden"),
     };
-    let user_access_model = UserRepo::new(resources.db_pool.clone());
-    match crypto::sign_in(
-        &user_access_model,
-        &config.security_config,
+    let attempt = SignInAttempt {
         username,
         password,
+        client_ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    };
+    let mfa_access_model = MfaRepo::new(resources.db_pool.clone());
+    let user_access_model = LdapSignInVerification::new(
+        UserRepo::new(resources.db_pool.clone()),
+        LdapDirectory::new(),
+        DirectoryRepo::new(resources.db_pool.clone()),
+        config.ldap_config.as_ref(),
+    );
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match sign_in_throttler::sign_in_with_throttling(
+        resources.sign_in_attempts.as_ref(),
+        &config.sign_in_throttling_config,
+        &mfa_access_model,
+        &user_access_model,
+        &token_access_model,
+        &config.security_config,
+        attempt,
     )
     .await
     {
-        Ok(signed_info) => HttpResponse::Ok().json(signed_info),
+        Ok(sign_in_result) => HttpResponse::Ok().json(sign_in_result),
         Err(SignError::VerificationError) => HttpResponse::Forbidden().body("Forbidden"),
+        Err(SignError::TooManyAttemptsError(retry_after)) => HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .body("Too many attempts"),
         Err(_) => {
             error!("Usecase fatal error during singin");
             HttpResponse::InternalServerError().body("internal error")
@@ -101,6 +179,279 @@ pub async fn sign_in_user_handler(
     }
 }
 
+#[post("users/{user_id}/unlock")]
+#[has_permissions("WRITE_USER")]
+pub async fn unlock_user_handler(
+    user_id: web::Path<u32>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let user_id = user_id.into_inner() as i32;
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    match sign_in_throttler::unlock_user(&user_repo, resources.sign_in_attempts.as_ref(), user_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(UserUCError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[post("users/{user_id}/revoke_sessions")]
+#[has_permissions("WRITE_USER")]
+pub async fn revoke_user_sessions_handler(
+    user_id: web::Path<u32>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let user_id = user_id.into_inner() as i32;
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match token_revoker::revoke_user_sessions(
+        &revocation_access_model,
+        &token_access_model,
+        user_id,
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(UserUCError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[post("users/{user_id}/enable")]
+#[has_permissions("WRITE_USER")]
+pub async fn enable_user_handler(
+    user_id: web::Path<u32>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let user_id = user_id.into_inner() as i32;
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    match user_disabler::enable_user(&user_repo, user_id).await {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(UserUCError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[post("users/{user_id}/disable")]
+#[has_permissions("WRITE_USER")]
+pub async fn disable_user_handler(
+    user_id: web::Path<u32>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let user_id = user_id.into_inner() as i32;
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match user_disabler::disable_user(
+        &user_repo,
+        &revocation_access_model,
+        &token_access_model,
+        user_id,
+    )
+    .await
+    {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(UserUCError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PasswordScheme {
+    password: String,
+}
+
+#[post("users/{user_id}/password")]
+#[has_permissions("WRITE_USER")]
+pub async fn set_user_password_handler(
+    user_id: web::Path<u32>,
+    password_data: web::Json<PasswordScheme>,
+    resources: Data<Resources>,
+) -> impl Responder {
+    let user_id = user_id.into_inner() as i32;
+    let reset_access_model = PasswordResetRepo::new(resources.db_pool.clone());
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    match password_reset::set_user_password(
+        &reset_access_model,
+        &revocation_access_model,
+        user_id,
+        &password_data.password,
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(PasswordResetError::ValidationError(e)) => HttpResponse::BadRequest().body(e),
+        Err(PasswordResetError::NotFoundError) => HttpResponse::NotFound().body("Not Found"),
+        Err(_) => {
+            error!("usecase error");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequestScheme {
+    username: String,
+}
+
+/// Responds the same way for unknown users
+#[post("password/reset")]
+pub async fn request_password_reset_handler(
+    resources: Data<Resources>,
+    config: Data<Config>,
+    reset_data: web::Json<PasswordResetRequestScheme>,
+) -> impl Responder {
+    let reset_access_model = PasswordResetRepo::new(resources.db_pool.clone());
+    match password_reset::request_password_reset(
+        &reset_access_model,
+        &config.password_reset_config,
+        &reset_data.username,
+    )
+    .await
+    {
+        Ok(notification) => {
+            // delivery takes as long as the SMTP relay answers, waiting for it would disclose
+            // which usernames exist, so the response doesn't wait
+            if let Some(notification) = notification {
+                let notifier_config = config.notifier_config.clone();
+                actix_web::rt::spawn(async move {
+                    let notifier = ConfiguredNotifier::new(&notifier_config);
+                    password_reset::send_reset_notification(&notifier, &notification).await;
+                });
+            }
+            HttpResponse::Accepted().body("")
+        }
+        Err(_) => {
+            error!("Usecase fatal error during password reset request");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetScheme {
+    token: String,
+    password: String,
+}
+
+#[post("password/reset/confirm")]
+pub async fn confirm_password_reset_handler(
+    resources: Data<Resources>,
+    reset_data: web::Json<PasswordResetScheme>,
+) -> impl Responder {
+    let reset_access_model = PasswordResetRepo::new(resources.db_pool.clone());
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    match password_reset::reset_password(
+        &reset_access_model,
+        &revocation_access_model,
+        &reset_data.token,
+        &reset_data.password,
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(PasswordResetError::ValidationError(e)) => HttpResponse::BadRequest().body(e),
+        Err(PasswordResetError::VerificationError | PasswordResetError::NotFoundError) => {
+            HttpResponse::BadRequest().body("reset token is invalid or expired")
+        }
+        Err(_) => {
+            error!("Usecase fatal error during password reset");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[post("sign_out")]
+pub async fn sign_out_handler(
+    req: HttpRequest,
+    resources: Data<Resources>,
+    config: Data<Config>,
+) -> impl Responder {
+    let auth_header = match Authorization::<Bearer>::parse(&req) {
+        Ok(header) => header,
+        Err(_) => return HttpResponse::Unauthorized().body("Unauthorized"),
+    };
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match token_revoker::sign_out(
+        &revocation_access_model,
+        &token_access_model,
+        &config.security_config,
+        auth_header.as_ref().token(),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::NoContent().body(""),
+        Err(SignError::VerificationError | SignError::ExpiredTokenError) => {
+            HttpResponse::Unauthorized().body("Unauthorized")
+        }
+        Err(_) => {
+            error!("Usecase fatal error during sign out");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenScheme {
+    refresh_token: String,
+}
+
+#[post("token/refresh")]
+pub async fn refresh_token_handler(
+    resources: Data<Resources>,
+    config: Data<Config>,
+    token_data: web::Json<RefreshTokenScheme>,
+) -> impl Responder {
+    let user_access_model = UserRepo::new(resources.db_pool.clone());
+    let token_access_model = RefreshTokenRepo::new(resources.db_pool.clone());
+    match token_refresher::refresh_tokens(
+        &user_access_model,
+        &token_access_model,
+        &config.security_config,
+        &token_data.refresh_token,
+    )
+    .await
+    {
+        Ok(signed_info) => HttpResponse::Ok().json(signed_info),
+        Err(SignError::VerificationError | SignError::ExpiredTokenError) => {
+            HttpResponse::Forbidden().body("Forbidden")
+        }
+        Err(_) => {
+            error!("Usecase fatal error during token refreshing");
+            HttpResponse::InternalServerError().body("internal error")
+        }
+    }
+}
+
 #[derive(Deserialize)]
 
 pub struct ValidateToken {
@@ -108,20 +459,20 @@ pub struct ValidateToken {
 }
 
 #[post("validate_jwt")]
+#[has_permissions("VALIDATE_JWT")]
 pub async fn validate_jwt_handler(
-    // req: HttpRequest,
     resources: Data<Resources>,
     config: Data<Config>,
     token_data: web::Json<ValidateToken>,
 ) -> impl Responder {
-    // TODO authorization for srv methods
-    // let auth_header = match Authorization::<Bearer>::parse(&req) {
-    //     Ok(header) => header,
-    //     Err(_) => return HttpResponse::Forbidden().body("Forbidden"),
-    // };
     let user_access_model = UserRepo::new(resources.db_pool.clone());
+    let revocation_access_model = CachedRevocationRepo::new(
+        RevocationRepo::new(resources.db_pool.clone()),
+        resources.revocation_cache.clone(),
+    );
     match crypto::verificate_jwt_token_and_enrich_perms(
         &user_access_model,
+        &revocation_access_model,
         &config.security_config,
         &token_data.jwt_token,
     )
@@ -129,6 +480,7 @@ pub async fn validate_jwt_handler(
     {
         Ok(perms) => HttpResponse::Ok().json(perms),
         Err(SignError::VerificationError) => HttpResponse::NotFound().body("not found"),
+        Err(SignError::ExpiredTokenError) => HttpResponse::NotFound().body("token expired"),
         Err(_) => {
             error!("Usecase fatal error during token validation");
             HttpResponse::InternalServerError().body("internal error")
@@ -0,0 +1,55 @@
+use crate::handlers::api::permissions::views::Pagination;
+use crate::usecases::users::entities::{User, UsersFilters};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct UserListingView {
+    pub users: Vec<User>,
+    pub pagination: Pagination,
+}
+
+impl UserListingView {
+    pub fn new(users: Vec<User>, offset: i64, limit: i64, total: i64) -> UserListingView {
+        UserListingView {
+            users,
+            pagination: Pagination {
+                offset,
+                limit,
+                total,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UsersFiltersInputScheme {
+    pub username: Option<String>,
+    pub enabled: Option<bool>,
+    pub is_deleted: Option<bool>,
+    pub role_id: Option<i32>,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl UsersFiltersInputScheme {
+    pub fn new_with_validation(data: UsersFiltersInputScheme) -> Result<UsersFilters, String> {
+        let offset = match data.offset {
+            None => 0,
+            Some(offset) if (0..9999).contains(&offset) => offset,
+            _ => return Err("wrong offset value".to_string()),
+        };
+        let limit = match data.limit {
+            None => 100,
+            Some(limit) if 0 < limit && limit <= 1000 => limit,
+            _ => return Err("wrong limit value".to_string()),
+        };
+        Ok(UsersFilters {
+            username: data.username,
+            enabled: data.enabled,
+            is_deleted: data.is_deleted,
+            role_id: data.role_id,
+            offset,
+            limit,
+        })
+    }
+}
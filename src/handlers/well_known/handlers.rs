@@ -0,0 +1,15 @@
+use crate::common::Config;
+use crate::usecases::oauth::oidc::openid_configuration;
+use actix_web::{get, web, HttpResponse, Responder};
+use jsonwebtoken::jwk::JwkSet;
+
+#[get("jwks.json")]
+pub async fn jwks_handler(config: web::Data<Config>) -> impl Responder {
+    let keys = config.security_config.jwt_keyring.public_jwks();
+    HttpResponse::Ok().json(JwkSet { keys })
+}
+
+#[get("openid-configuration")]
+pub async fn openid_configuration_handler(config: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(openid_configuration(&config.security_config))
+}
@@ -1,4 +1,5 @@
 pub mod apps;
+pub mod cli;
 pub mod common;
 pub mod handlers;
 pub mod middlewares;
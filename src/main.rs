@@ -1,35 +1,137 @@
 use actix_web::dev::Server;
+use actix_web::middleware::from_fn;
 use actix_web::{web, App, HttpServer};
+use actix_web_grants::GrantsMiddleware;
 use actix_web_httpauth::middleware::HttpAuthentication;
 
-use authust::apps::{init_api_v1, init_external_v1, init_internal_v1, init_system};
+use authust::apps::{
+    init_api_v1, init_external_v1, init_internal_v1, init_system, init_well_known,
+};
+use authust::cli::{
+    run_client, run_migrate, run_permission, run_role, run_token, run_user, Cli, Command,
+};
+use authust::common::tls::{
+    create_server_tls_config, store_client_certificate, ReloadableCertResolver,
+};
 use authust::common::{Config, Resources};
-use authust::middlewares::bearer_validator;
+use authust::middlewares::{
+    api_rate_limiter, bearer_validator, service_permissions_extractor, srv_rate_limiter,
+};
+use authust::storage::postgres::migrations::migrate_up;
 
-use log::debug;
+use actix_web::rt::signal::unix::{signal, SignalKind};
+use clap::Parser;
+use log::{debug, error, info};
+use std::sync::Arc;
 extern crate env_logger;
 
 pub fn run_server(resources: Resources, config: Config) -> Result<Server, std::io::Error> {
+    let server_config = config.server_config.clone();
     let server = HttpServer::new(move || {
         let auth = HttpAuthentication::bearer(bearer_validator);
         App::new()
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(resources.clone()))
-            .service(web::scope("api/v1").configure(init_api_v1).wrap(auth))
-            .service(web::scope("srv/v1").configure(init_internal_v1))
+            .service(
+                web::scope("api/v1")
+                    .configure(init_api_v1)
+                    .wrap(from_fn(api_rate_limiter))
+                    .wrap(auth),
+            )
+            .service(
+                web::scope("srv/v1")
+                    .configure(init_internal_v1)
+                    .wrap(from_fn(srv_rate_limiter))
+                    .wrap(GrantsMiddleware::with_extractor(
+                        service_permissions_extractor,
+                    )),
+            )
             .service(web::scope("auth/v1").configure(init_external_v1))
+            .service(web::scope(".well-known").configure(init_well_known))
             .service(web::scope("").configure(init_system))
     })
-    .bind("127.0.0.1:8080")?
-    .run();
-    Ok(server)
+    .on_connect(store_client_certificate);
+    let address = (server_config.host.as_str(), server_config.port);
+    let server = match &server_config.tls_cert_resolver {
+        Some(resolver) => {
+            let tls_config =
+                create_server_tls_config(resolver.clone(), server_config.tls_client_roots.clone())
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
+            reload_cert_on_sighup(resolver.clone());
+            server.bind_rustls_0_23(address, tls_config)?
+        }
+        None => server.bind(address)?,
+    };
+    let server = match server_config.workers {
+        Some(workers) => server.workers(workers.get()),
+        None => server,
+    };
+    Ok(server.run())
+}
+
+fn reload_cert_on_sighup(resolver: Arc<ReloadableCertResolver>) {
+    actix_web::rt::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!(
+                    "can not listen SIGHUP, certificate reloading is disabled: {}",
+                    e
+                );
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match resolver.reload() {
+                Ok(_) => info!("tls certificate reloaded"),
+                Err(e) => error!("tls certificate reloading failed: {}", e),
+            }
+        }
+    });
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let config = Config::create_config();
+    let cli = Cli::parse();
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    env_logger::Builder::new()
+        .filter_level(config.server_config.log_level)
+        .parse_default_env()
+        .init();
     let resources = Resources::create_resources(&config).await;
-    env_logger::init();
     debug!(target: "init", "{:#?}", config);
-    run_server(resources, config)?.await
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Migrate { action } => run_migrate(&resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::User { action } => run_user(&config, &resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::Role { action } => run_role(&resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::Permission { action } => run_permission(&resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::Token { action } => run_token(&config, &resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::Client { action } => run_client(&resources, action)
+            .await
+            .map_err(std::io::Error::other),
+        Command::Serve => {
+            if config.database_config.migrate_on_startup {
+                migrate_up(&resources.db_pool)
+                    .await
+                    .expect("Migrations failed on startup");
+            }
+            run_server(resources, config)?.await
+        }
+    }
 }
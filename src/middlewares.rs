@@ -1,14 +1,31 @@
+use crate::common::tls::{certificate_identities, ClientCertificate};
 use crate::common::{Config, Resources};
+use crate::storage::memory::revocation_cache::CachedRevocationRepo;
+use crate::storage::postgres::api_key_repo::ApiKeyRepo;
+use crate::storage::postgres::revocation_repo::RevocationRepo;
+use crate::storage::postgres::service_repo::ServiceRepo;
 use crate::storage::postgres::user_repo::UserRepo;
+use crate::usecases::api_keys::api_key_manager::authenticate_by_api_key;
+use crate::usecases::api_keys::entities::API_KEY_PREFIX;
+use crate::usecases::services::entities::AuthenticatedService;
+use crate::usecases::services::errors::ServiceUCError;
+use crate::usecases::services::service_authenticator::{
+    authenticate_service, authenticate_service_by_certificate,
+};
 use crate::usecases::users::crypto;
+use crate::usecases::users::entities::AuthenticatedUser;
 use crate::usecases::users::errors::SignError;
 
-use actix_web::dev::ServiceRequest;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::http::header::{Header, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::middleware::Next;
 use actix_web::web::Data;
-use actix_web::Error;
+use actix_web::{Error, HttpMessage, HttpResponse};
 use actix_web_grants::permissions::AttachPermissions;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use actix_web_httpauth::headers::authorization::{Authorization, Basic};
 
 use log::error;
 
@@ -31,22 +48,178 @@ pub async fn bearer_validator(
         }
     };
     let user_access_model = UserRepo::new(resources.db_pool.clone());
-    let perms = match crypto::verificate_jwt_token_and_enrich_perms(
-        &user_access_model,
-        &config.security_config,
-        credentials.token(),
-    )
-    .await
-    {
-        Ok(permissions) => permissions,
+    let authentication = if credentials.token().starts_with(API_KEY_PREFIX) {
+        let api_key_access_model = ApiKeyRepo::new(resources.db_pool.clone());
+        authenticate_by_api_key(
+            &api_key_access_model,
+            &user_access_model,
+            credentials.token(),
+        )
+        .await
+    } else {
+        let revocation_access_model = CachedRevocationRepo::new(
+            RevocationRepo::new(resources.db_pool.clone()),
+            resources.revocation_cache.clone(),
+        );
+        crypto::authenticate_by_jwt(
+            &user_access_model,
+            &revocation_access_model,
+            &config.security_config,
+            credentials.token(),
+        )
+        .await
+    };
+    let user = match authentication {
+        Ok(user) => user,
         Err(SignError::VerificationError) => {
             return Err(ErrorUnauthorized("Wrong token".to_string()))
         }
+        Err(SignError::ExpiredTokenError) => {
+            return Err(ErrorUnauthorized("Token expired".to_string()))
+        }
         Err(_) => {
             error!("Usecase fatal error during token checking");
             return Err(ErrorInternalServerError("internal error"));
         }
     };
-    req.attach(perms);
+    req.attach(user.permissions.clone());
+    req.extensions_mut().insert(user);
     Ok(req)
 }
+
+/// Authenticates internal services by client certificate (when listener requests it) or by
+/// client id and secret with HTTP Basic auth, scopes of the service are its permissions
+pub async fn service_permissions_extractor(req: &ServiceRequest) -> Result<Vec<String>, Error> {
+    let resources = match req.app_data::<Data<Resources>>() {
+        Some(data) => data,
+        None => {
+            error!("Not found resources for service_permissions_extractor");
+            return Err(ErrorInternalServerError("internal error"));
+        }
+    };
+    let service_access_model = ServiceRepo::new(resources.db_pool.clone());
+    if let Some(cert) = req.conn_data::<ClientCertificate>() {
+        let identities = certificate_identities(&cert.0).unwrap_or_default();
+        match authenticate_service_by_certificate(&service_access_model, &identities).await {
+            Ok(service) => return Ok(attach_service(req, service)),
+            Err(ServiceUCError::VerificationError) => (),
+            Err(_) => {
+                error!("Usecase fatal error during service authentication");
+                return Err(ErrorInternalServerError("internal error"));
+            }
+        }
+    }
+    let auth_header = match Authorization::<Basic>::parse(req) {
+        Ok(header) => header,
+        Err(_) => return Err(ErrorUnauthorized("Wrong credentials".to_string())),
+    };
+    let client_secret = match auth_header.as_ref().password() {
+        Some(secret) => secret,
+        None => return Err(ErrorUnauthorized("Wrong credentials".to_string())),
+    };
+    match authenticate_service(
+        &service_access_model,
+        auth_header.as_ref().user_id(),
+        client_secret,
+    )
+    .await
+    {
+        Ok(service) => Ok(attach_service(req, service)),
+        Err(ServiceUCError::VerificationError) => {
+            Err(ErrorUnauthorized("Wrong credentials".to_string()))
+        }
+        Err(_) => {
+            error!("Usecase fatal error during service authentication");
+            Err(ErrorInternalServerError("internal error"))
+        }
+    }
+}
+
+fn attach_service(req: &ServiceRequest, service: AuthenticatedService) -> Vec<String> {
+    let scopes = service.scopes.clone();
+    req.extensions_mut().insert(service);
+    scopes
+}
+
+pub async fn api_rate_limiter(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    rate_limit(RateLimitScope::Api, req, next).await
+}
+
+pub async fn srv_rate_limiter(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    rate_limit(RateLimitScope::Srv, req, next).await
+}
+
+enum RateLimitScope {
+    Api,
+    Srv,
+}
+
+/// Token bucket per scope and caller, must be wrapped by authentication middleware
+/// to see `AuthenticatedUser` or `AuthenticatedService`, otherwise peer address is the caller
+async fn rate_limit(
+    scope: RateLimitScope,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let (config, resources) = match (
+        req.app_data::<Data<Config>>(),
+        req.app_data::<Data<Resources>>(),
+    ) {
+        (Some(config), Some(resources)) => (config.clone(), resources.clone()),
+        _ => {
+            error!("Not found config or resources for rate_limit");
+            return Err(ErrorInternalServerError("internal error"));
+        }
+    };
+    let rate_limit_config = &config.rate_limit_config;
+    if !rate_limit_config.enabled {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let identity = {
+        let extensions = req.extensions();
+        if let Some(user) = extensions.get::<AuthenticatedUser>() {
+            format!("user:{}", user.user_id)
+        } else if let Some(service) = extensions.get::<AuthenticatedService>() {
+            format!("service:{}", service.client_id)
+        } else {
+            let peer_ip = req.peer_addr().map(|addr| addr.ip().to_string());
+            format!("ip:{}", peer_ip.unwrap_or_default())
+        }
+    };
+    let (scope_name, scope_limit) = match scope {
+        RateLimitScope::Api => ("api", rate_limit_config.api),
+        RateLimitScope::Srv => ("srv", rate_limit_config.srv),
+    };
+    let limit = match rate_limit_config.overrides.get(&identity) {
+        Some(limit) => *limit,
+        None => scope_limit,
+    };
+    let decision = resources
+        .rate_limit_buckets
+        .take(&format!("{}:{}", scope_name, identity), limit);
+    let headers = [
+        ("ratelimit-limit", u64::from(decision.limit)),
+        ("ratelimit-remaining", u64::from(decision.remaining)),
+        ("ratelimit-reset", decision.reset_seconds),
+    ];
+    if !decision.allowed {
+        let mut response = HttpResponse::TooManyRequests();
+        response.insert_header((RETRY_AFTER, decision.retry_after_seconds.to_string()));
+        for (name, value) in headers {
+            response.insert_header((name, value.to_string()));
+        }
+        return Ok(req.into_response(response.body("Too many requests")));
+    }
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    for (name, value) in headers {
+        res.headers_mut()
+            .insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+    Ok(res)
+}
@@ -1 +1,5 @@
+pub mod http;
+pub mod ldap;
+pub mod memory;
+pub mod notify;
 pub mod postgres;
@@ -0,0 +1 @@
+pub mod upstream_idp;
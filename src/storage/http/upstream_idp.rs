@@ -0,0 +1,86 @@
+use crate::common::UpstreamProvider;
+use crate::usecases::federation::errors::FederationError;
+use crate::usecases::federation::federated_sign_in::UpstreamIdp;
+
+use async_trait::async_trait;
+use jsonwebtoken::jwk::JwkSet;
+use log::{error, warn};
+use serde::Deserialize;
+
+/// Upstream OpenID Connect provider reached over HTTP
+pub struct HttpUpstreamIdp {
+    http_client: reqwest::Client,
+}
+
+impl HttpUpstreamIdp {
+    pub fn new(http_client: reqwest::Client) -> HttpUpstreamIdp {
+        HttpUpstreamIdp { http_client }
+    }
+}
+
+#[derive(Deserialize)]
+struct UpstreamTokenResponse {
+    id_token: Option<String>,
+}
+
+fn request_error(e: reqwest::Error) -> FederationError {
+    error!("Upstream provider request error: {}", e);
+    match e.is_timeout() || e.is_connect() {
+        true => FederationError::TemporaryError,
+        false => FederationError::UpstreamError,
+    }
+}
+
+#[async_trait]
+impl UpstreamIdp for HttpUpstreamIdp {
+    async fn exchange_code(
+        &self,
+        provider: &UpstreamProvider,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, FederationError> {
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        let mut request = self.http_client.post(&provider.token_endpoint).form(&form);
+        if let Some(client_secret) = &provider.client_secret {
+            request = request.basic_auth(&provider.client_id, Some(client_secret));
+        }
+        let response = request.send().await.map_err(request_error)?;
+        if !response.status().is_success() {
+            warn!(
+                "Provider {} rejected authorization code with status {}",
+                provider.name,
+                response.status()
+            );
+            return Err(FederationError::UpstreamError);
+        }
+        let token_response: UpstreamTokenResponse = response.json().await.map_err(request_error)?;
+        token_response
+            .id_token
+            .ok_or(FederationError::UpstreamError)
+    }
+
+    /// Keys are fetched for every login, so rotated keys of the provider are picked up at once
+    async fn get_jwks(&self, provider: &UpstreamProvider) -> Result<JwkSet, FederationError> {
+        let response = self
+            .http_client
+            .get(&provider.jwks_uri)
+            .send()
+            .await
+            .map_err(request_error)?;
+        if !response.status().is_success() {
+            warn!(
+                "Provider {} returned status {} for JWKS",
+                provider.name,
+                response.status()
+            );
+            return Err(FederationError::UpstreamError);
+        }
+        response.json().await.map_err(request_error)
+    }
+}
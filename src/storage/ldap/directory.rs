@@ -0,0 +1,122 @@
+use crate::common::LdapConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::DirectoryEntry;
+use crate::usecases::users::ldap_sign_in::SearchDirectory;
+
+use async_trait::async_trait;
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use log::error;
+use std::time::Duration;
+
+// resultCode of failed bind, RFC 4511 appendix A.1
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// LDAP server reached with new connection for every operation
+pub struct LdapDirectory {}
+
+impl LdapDirectory {
+    pub fn new() -> LdapDirectory {
+        LdapDirectory {}
+    }
+}
+
+impl Default for LdapDirectory {
+    fn default() -> Self {
+        LdapDirectory::new()
+    }
+}
+
+fn ldap_error(e: LdapError) -> AccessModelError {
+    error!("LDAP error: {}", e);
+    match e {
+        LdapError::Io { .. } | LdapError::Timeout { .. } => AccessModelError::TemporaryError,
+        _ => AccessModelError::FatalError,
+    }
+}
+
+async fn connect(config: &LdapConfig) -> Result<Ldap, AccessModelError> {
+    let settings = LdapConnSettings::new()
+        .set_conn_timeout(Duration::from_secs(config.timeout_seconds))
+        .set_starttls(config.starttls);
+    let (conn, ldap) = LdapConnAsync::with_settings(settings, &config.url)
+        .await
+        .map_err(ldap_error)?;
+    ldap3::drive!(conn);
+    Ok(ldap)
+}
+
+/// Attribute names of returned entries keep the case used by the server
+fn attribute_values(entry: &SearchEntry, name: &str) -> Vec<String> {
+    entry
+        .attrs
+        .iter()
+        .find(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.clone())
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl SearchDirectory for LdapDirectory {
+    async fn find_user_entry(
+        &self,
+        config: &LdapConfig,
+        username: &str,
+    ) -> Result<DirectoryEntry, AccessModelError> {
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        let mut ldap = connect(config).await?;
+        if let Some(bind_dn) = &config.bind_dn {
+            let password = config.bind_password.as_deref().unwrap_or_default();
+            ldap.with_timeout(timeout)
+                .simple_bind(bind_dn, password)
+                .await
+                .and_then(|result| result.success())
+                .map_err(ldap_error)?;
+        }
+        let filter = config
+            .user_filter
+            .replace("{username}", &ldap_escape(username));
+        let attributes = vec![
+            config.username_attribute.as_str(),
+            config.group_attribute.as_str(),
+        ];
+        let (entries, _) = ldap
+            .with_timeout(timeout)
+            .search(&config.base_dn, Scope::Subtree, &filter, attributes)
+            .await
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+        let _ = ldap.unbind().await;
+        if entries.len() != 1 {
+            return Err(AccessModelError::NotFoundError);
+        }
+        let entry = SearchEntry::construct(entries.into_iter().next().unwrap());
+        let username = attribute_values(&entry, &config.username_attribute)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| username.to_string());
+        Ok(DirectoryEntry {
+            groups: attribute_values(&entry, &config.group_attribute),
+            dn: entry.dn,
+            username,
+        })
+    }
+    async fn bind_user(
+        &self,
+        config: &LdapConfig,
+        dn: &str,
+        password: &str,
+    ) -> Result<bool, AccessModelError> {
+        let mut ldap = connect(config).await?;
+        let result = ldap
+            .with_timeout(Duration::from_secs(config.timeout_seconds))
+            .simple_bind(dn, password)
+            .await
+            .map_err(ldap_error)?;
+        let _ = ldap.unbind().await;
+        match result.rc {
+            0 => Ok(true),
+            INVALID_CREDENTIALS => Ok(false),
+            _ => Err(ldap_error(LdapError::LdapResult { result })),
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod revocation_cache;
+pub mod sign_in_attempts;
+pub mod token_buckets;
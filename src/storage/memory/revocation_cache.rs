@@ -0,0 +1,108 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::token_revoker::RevokeToken;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct TtlMap<K, V> {
+    ttl: Duration,
+    items: Mutex<HashMap<K, (V, Instant)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+    fn new(ttl: Duration) -> TtlMap<K, V> {
+        TtlMap {
+            ttl,
+            items: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        let items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        match items.get(key) {
+            Some((value, stored_at)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, key: K, value: V) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        if items.len() >= PRUNE_THRESHOLD {
+            let ttl = self.ttl;
+            items.retain(|_, (_, stored_at)| stored_at.elapsed() < ttl);
+        }
+        items.insert(key, (value, Instant::now()));
+    }
+}
+
+/// Process-local cache of revocation checks. Changes made by other instances
+/// become visible after `ttl`, changes made by this instance immediately.
+pub struct RevocationCache {
+    revoked_tokens: TtlMap<String, bool>,
+    token_versions: TtlMap<i32, i32>,
+}
+
+impl RevocationCache {
+    pub fn new(ttl: Duration) -> RevocationCache {
+        RevocationCache {
+            revoked_tokens: TtlMap::new(ttl),
+            token_versions: TtlMap::new(ttl),
+        }
+    }
+}
+
+pub struct CachedRevocationRepo<R: RevokeToken> {
+    repo: R,
+    cache: Arc<RevocationCache>,
+}
+
+impl<R: RevokeToken> CachedRevocationRepo<R> {
+    pub fn new(repo: R, cache: Arc<RevocationCache>) -> CachedRevocationRepo<R> {
+        CachedRevocationRepo { repo, cache }
+    }
+}
+
+#[async_trait]
+impl<R: RevokeToken + Sync + Send> RevokeToken for CachedRevocationRepo<R> {
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, AccessModelError> {
+        let key = jti.to_string();
+        if let Some(is_revoked) = self.cache.revoked_tokens.get(&key) {
+            return Ok(is_revoked);
+        }
+        let is_revoked = self.repo.is_token_revoked(jti).await?;
+        self.cache.revoked_tokens.insert(key, is_revoked);
+        Ok(is_revoked)
+    }
+    async fn revoke_token(
+        &self,
+        jti: &str,
+        user_id: i32,
+        expired_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError> {
+        self.repo.revoke_token(jti, user_id, expired_at).await?;
+        self.cache.revoked_tokens.insert(jti.to_string(), true);
+        Ok(())
+    }
+    async fn get_token_version(&self, user_id: i32) -> Result<i32, AccessModelError> {
+        if let Some(version) = self.cache.token_versions.get(&user_id) {
+            return Ok(version);
+        }
+        let version = self.repo.get_token_version(user_id).await?;
+        self.cache.token_versions.insert(user_id, version);
+        Ok(version)
+    }
+    async fn increment_token_version(&self, user_id: i32) -> Result<i32, AccessModelError> {
+        let version = self.repo.increment_token_version(user_id).await?;
+        self.cache.token_versions.insert(user_id, version);
+        Ok(version)
+    }
+    async fn token_version_changed(&self, user_id: i32, version: i32) {
+        self.cache.token_versions.insert(user_id, version);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::{AttemptKey, FailedAttempts};
+use crate::usecases::users::sign_in_throttler::StoreSignInAttempts;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Process-local store of failed sign in attempts, every instance counts its own attempts.
+/// Attempts older than `window` are dropped
+pub struct MemorySignInAttemptsRepo {
+    window: chrono::Duration,
+    failures: Mutex<HashMap<AttemptKey, Vec<DateTime<Utc>>>>,
+}
+
+impl MemorySignInAttemptsRepo {
+    pub fn new(window: Duration) -> MemorySignInAttemptsRepo {
+        MemorySignInAttemptsRepo {
+            window: chrono::Duration::from_std(window).unwrap_or(chrono::Duration::MAX),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreSignInAttempts for MemorySignInAttemptsRepo {
+    async fn get_failures(
+        &self,
+        key: &AttemptKey,
+        since: DateTime<Utc>,
+    ) -> Result<FailedAttempts, AccessModelError> {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        let attempts: Vec<&DateTime<Utc>> = failures
+            .get(key)
+            .map(|attempts| attempts.iter().filter(|at| **at > since).collect())
+            .unwrap_or_default();
+        Ok(FailedAttempts {
+            count: attempts.len() as u32,
+            last_attempted_at: attempts.into_iter().max().copied(),
+        })
+    }
+    async fn add_failure(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+        attempted_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError> {
+        let expired_before = attempted_at - self.window;
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        if failures.len() >= PRUNE_THRESHOLD {
+            failures.retain(|_, attempts| attempts.iter().any(|at| *at > expired_before));
+        }
+        let mut keys = vec![AttemptKey::Username(username.to_string())];
+        if let Some(client_ip) = client_ip {
+            keys.push(AttemptKey::ClientIp(client_ip.to_string()));
+        }
+        for key in keys {
+            let attempts = failures.entry(key).or_default();
+            attempts.retain(|at| *at > expired_before);
+            attempts.push(attempted_at);
+        }
+        Ok(())
+    }
+    async fn clear_failures(&self, username: &str) -> Result<(), AccessModelError> {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.remove(&AttemptKey::Username(username.to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::common::BucketLimit;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    limit: BucketLimit,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst.into());
+        self.refilled_at = now;
+    }
+}
+
+/// Result of taking a token, seconds are rounded up
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// seconds until the bucket is full again
+    pub reset_seconds: u64,
+    /// seconds until the next request is allowed, zero if it's allowed now
+    pub retry_after_seconds: u64,
+}
+
+/// Process-local token buckets of rate limiter, every instance counts requests on its own
+pub struct TokenBuckets {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Default for TokenBuckets {
+    fn default() -> Self {
+        TokenBuckets::new()
+    }
+}
+
+impl TokenBuckets {
+    pub fn new() -> TokenBuckets {
+        TokenBuckets {
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn take(&self, key: &str, limit: BucketLimit) -> RateLimitDecision {
+        self.take_at(key, limit, Instant::now())
+    }
+
+    pub fn take_at(&self, key: &str, limit: BucketLimit, now: Instant) -> RateLimitDecision {
+        let burst = f64::from(limit.burst);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // full buckets are the same as missing ones
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                bucket.tokens < bucket.limit.burst.into()
+            });
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+            limit,
+        });
+        bucket.limit = limit;
+        bucket.refill(now);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let seconds = |tokens: f64| (tokens.max(0.0) / limit.per_second).ceil() as u64;
+        RateLimitDecision {
+            allowed,
+            limit: limit.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset_seconds: seconds(burst - bucket.tokens),
+            retry_after_seconds: match allowed {
+                true => 0,
+                false => seconds(1.0 - bucket.tokens),
+            },
+        }
+    }
+}
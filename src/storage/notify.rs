@@ -0,0 +1,3 @@
+pub mod configured;
+pub mod file;
+pub mod smtp;
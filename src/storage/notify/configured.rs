@@ -0,0 +1,34 @@
+use crate::common::{NotifierConfig, NotifierKind};
+use crate::storage::notify::file::FileNotifier;
+use crate::storage::notify::smtp::SmtpNotifier;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::Notification;
+use crate::usecases::users::password_reset::Notifier;
+
+use async_trait::async_trait;
+
+/// Sends notifications with the notifier chosen in config
+pub struct ConfiguredNotifier<'a> {
+    config: &'a NotifierConfig,
+}
+
+impl<'a> ConfiguredNotifier<'a> {
+    pub fn new(config: &'a NotifierConfig) -> ConfiguredNotifier<'a> {
+        ConfiguredNotifier { config }
+    }
+}
+
+#[async_trait]
+impl Notifier for ConfiguredNotifier<'_> {
+    async fn send(&self, notification: &Notification) -> Result<(), AccessModelError> {
+        match self.config.kind {
+            NotifierKind::Log => FileNotifier::new(None).send(notification).await,
+            NotifierKind::File => {
+                FileNotifier::new(self.config.file_path.clone())
+                    .send(notification)
+                    .await
+            }
+            NotifierKind::Smtp => SmtpNotifier::new(self.config).send(notification).await,
+        }
+    }
+}
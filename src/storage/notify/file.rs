@@ -0,0 +1,50 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::Notification;
+use crate::usecases::users::password_reset::Notifier;
+
+use async_trait::async_trait;
+use log::{error, info};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// Appends notifications to the file as JSON lines, writes them to the log without the file.
+/// Messages contain secrets, it's meant for development and tests
+pub struct FileNotifier {
+    path: Option<String>,
+}
+
+impl FileNotifier {
+    pub fn new(path: Option<String>) -> FileNotifier {
+        FileNotifier { path }
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), AccessModelError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                info!(
+                    "notification to {}: {}\n{}",
+                    notification.recipient, notification.subject, notification.body
+                );
+                return Ok(());
+            }
+        };
+        let mut line = serde_json::to_string(notification).map_err(|e| {
+            error!("can not serialize notification: {}", e);
+            AccessModelError::FatalError
+        })?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| {
+                error!("can not write notification to {}: {}", path, e);
+                AccessModelError::FatalError
+            })
+    }
+}
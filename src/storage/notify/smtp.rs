@@ -0,0 +1,158 @@
+use crate::common::NotifierConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::Notification;
+use crate::usecases::users::password_reset::Notifier;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use log::error;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+enum SmtpError {
+    Io(std::io::Error),
+    /// unexpected reply of the server
+    Rejected(String),
+}
+
+impl From<std::io::Error> for SmtpError {
+    fn from(e: std::io::Error) -> Self {
+        SmtpError::Io(e)
+    }
+}
+
+/// Plain SMTP without TLS and authentication, every message gets new connection
+pub struct SmtpNotifier<'a> {
+    config: &'a NotifierConfig,
+}
+
+impl<'a> SmtpNotifier<'a> {
+    pub fn new(config: &'a NotifierConfig) -> SmtpNotifier<'a> {
+        SmtpNotifier { config }
+    }
+}
+
+struct SmtpConnection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl SmtpConnection {
+    /// Reads reply with continuation lines, fails unless its code is one of `expected`
+    async fn expect(&mut self, expected: &[&str]) -> Result<(), SmtpError> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err(SmtpError::Rejected("connection is closed".to_string()));
+            }
+            let line = line.trim_end();
+            if !expected.contains(&line.get(..3).unwrap_or_default()) {
+                return Err(SmtpError::Rejected(line.to_string()));
+            }
+            // "250-" is followed by more lines, "250 " is the last one
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn command(&mut self, command: &str, expected: &[&str]) -> Result<(), SmtpError> {
+        self.writer
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await?;
+        self.expect(expected).await
+    }
+}
+
+/// Line breaks are normalized to CRLF, lines starting with dot are escaped (RFC 5321 4.5.2)
+fn message_data(from: &str, notification: &Notification) -> String {
+    let mut data = format!(
+        "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+        Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        from,
+        notification.recipient,
+        notification.subject,
+        Utc::now().to_rfc2822()
+    );
+    for line in notification.body.lines() {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    data
+}
+
+/// Header values must not break the message headers
+fn is_header_safe(value: &str) -> bool {
+    !value.contains(['\r', '\n', '<', '>'])
+}
+
+async fn deliver(config: &NotifierConfig, notification: &Notification) -> Result<(), SmtpError> {
+    let stream = TcpStream::connect((config.smtp_host.as_str(), config.smtp_port)).await?;
+    let (reader, writer) = stream.into_split();
+    let mut connection = SmtpConnection {
+        reader: BufReader::new(reader),
+        writer,
+    };
+    connection.expect(&["220"]).await?;
+    connection.command("EHLO authust", &["250"]).await?;
+    connection
+        .command(&format!("MAIL FROM:<{}>", config.from), &["250"])
+        .await?;
+    connection
+        .command(
+            &format!("RCPT TO:<{}>", notification.recipient),
+            &["250", "251"],
+        )
+        .await?;
+    connection.command("DATA", &["354"]).await?;
+    connection
+        .writer
+        .write_all(message_data(&config.from, notification).as_bytes())
+        .await?;
+    connection.expect(&["250"]).await?;
+    // the message is accepted, failed QUIT doesn't matter
+    let _ = connection.command("QUIT", &["221"]).await;
+    Ok(())
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier<'_> {
+    async fn send(&self, notification: &Notification) -> Result<(), AccessModelError> {
+        if ![
+            self.config.from.as_str(),
+            &notification.recipient,
+            &notification.subject,
+        ]
+        .iter()
+        .all(|value| is_header_safe(value))
+        {
+            error!("notification to {} is malformed", notification.recipient);
+            return Err(AccessModelError::FatalError);
+        }
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        match tokio::time::timeout(timeout, deliver(self.config, notification)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(SmtpError::Io(e))) => {
+                error!("SMTP error: {}", e);
+                Err(AccessModelError::TemporaryError)
+            }
+            Ok(Err(SmtpError::Rejected(reply))) => {
+                error!("SMTP server rejected notification: {}", reply);
+                Err(AccessModelError::FatalError)
+            }
+            Err(_) => {
+                error!(
+                    "SMTP server didn't respond in {} seconds",
+                    timeout.as_secs()
+                );
+                Err(AccessModelError::TemporaryError)
+            }
+        }
+    }
+}
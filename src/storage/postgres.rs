@@ -1,5 +1,17 @@
+pub mod api_key_repo;
+pub mod authorization_code_repo;
 mod base;
+pub mod directory_repo;
+pub mod federation_repo;
+pub mod mfa_repo;
+pub mod migrations;
+pub mod oauth_client_repo;
+pub mod password_reset_repo;
 pub mod permission_repo;
+pub mod refresh_token_repo;
+pub mod revocation_repo;
 pub mod role_repo;
+pub mod service_repo;
+pub mod sign_in_attempts_repo;
 pub mod system;
 pub mod user_repo;
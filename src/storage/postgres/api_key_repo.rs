@@ -0,0 +1,110 @@
+use crate::storage::postgres::base::{
+    delete_item, get_client, get_item, insert_item, prepare_stmt, SqlSerializer,
+};
+use crate::usecases::api_keys::api_key_manager::StoreApiKey;
+use crate::usecases::api_keys::entities::{ApiKey, ApiKeyForCreation, ApiKeyOwner};
+use crate::usecases::base_entities::AccessModelError;
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::Row;
+
+pub struct ApiKeyRepo {
+    db_pool: Pool,
+}
+
+impl ApiKeyRepo {
+    pub fn new(db_pool: Pool) -> ApiKeyRepo {
+        ApiKeyRepo { db_pool }
+    }
+}
+
+const INSERT_API_KEY_QUERY: &str = "INSERT INTO api_keys
+    (user_id, name, token_hash, permissions, expired_at, created_at, is_revoked)
+    VALUES ($1, $2, $3, $4, $5, $6, FALSE)
+    RETURNING api_key_id, name, permissions, expired_at, created_at, last_used_at";
+const GET_USER_API_KEYS_QUERY: &str = "
+    SELECT api_key_id, name, permissions, expired_at, created_at, last_used_at
+    FROM api_keys
+    WHERE user_id=$1 AND is_revoked=FALSE
+    ORDER BY api_key_id";
+const REVOKE_API_KEY_QUERY: &str = "UPDATE api_keys
+    SET is_revoked=TRUE
+    WHERE user_id=$1 AND api_key_id=$2 AND is_revoked=FALSE";
+const USE_API_KEY_QUERY: &str = "UPDATE api_keys k
+    SET last_used_at=$2
+    FROM users u
+    WHERE k.user_id=u.user_id AND k.token_hash=$1 AND k.is_revoked=FALSE AND k.expired_at>$2
+        AND u.enabled=TRUE AND u.is_deleted=FALSE
+    RETURNING k.api_key_id, k.user_id, k.permissions";
+
+impl SqlSerializer<ApiKey> for ApiKey {
+    fn from_sql_result(row: &Row) -> ApiKey {
+        ApiKey {
+            api_key_id: row.get(0),
+            name: row.get(1),
+            permissions: row.get(2),
+            expired_at: row.get(3),
+            created_at: row.get(4),
+            last_used_at: row.get(5),
+        }
+    }
+}
+
+impl SqlSerializer<ApiKeyOwner> for ApiKeyOwner {
+    fn from_sql_result(row: &Row) -> ApiKeyOwner {
+        ApiKeyOwner {
+            api_key_id: row.get(0),
+            user_id: row.get(1),
+            permissions: row.get(2),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreApiKey for ApiKeyRepo {
+    async fn save_api_key(&self, api_key: ApiKeyForCreation) -> Result<ApiKey, AccessModelError> {
+        insert_item(
+            &self.db_pool,
+            INSERT_API_KEY_QUERY,
+            &[
+                &api_key.user_id,
+                &api_key.name,
+                &api_key.token_hash,
+                &api_key.permissions,
+                &api_key.expired_at,
+                &chrono::Utc::now(),
+            ],
+        )
+        .await
+    }
+    async fn get_user_api_keys(&self, user_id: i32) -> Result<Vec<ApiKey>, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        let stmt = prepare_stmt(&client, GET_USER_API_KEYS_QUERY).await?;
+        match client.query(&stmt, &[&user_id]).await {
+            Ok(rows) => Ok(rows.iter().map(ApiKey::from_sql_result).collect()),
+            Err(e) => {
+                error!("{}", e);
+                Err(AccessModelError::FatalError)
+            }
+        }
+    }
+    async fn revoke_api_key(&self, user_id: i32, api_key_id: i32) -> Result<(), AccessModelError> {
+        delete_item(
+            &self.db_pool,
+            REVOKE_API_KEY_QUERY,
+            &[&user_id, &api_key_id],
+        )
+        .await
+    }
+    async fn use_api_key(&self, token_hash: &str) -> Result<ApiKeyOwner, AccessModelError> {
+        get_item(
+            &self.db_pool,
+            USE_API_KEY_QUERY,
+            &[&token_hash, &chrono::Utc::now()],
+        )
+        .await
+    }
+}
@@ -0,0 +1,84 @@
+use crate::storage::postgres::base::{execute_query, get_item, SqlSerializer};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::authorization_code::StoreAuthorizationCode;
+use crate::usecases::oauth::entities::{AuthorizationCode, AuthorizationCodeForCreation};
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+pub struct AuthorizationCodeRepo {
+    db_pool: Pool,
+}
+
+impl AuthorizationCodeRepo {
+    pub fn new(db_pool: Pool) -> AuthorizationCodeRepo {
+        AuthorizationCodeRepo { db_pool }
+    }
+}
+
+const SAVE_AUTHORIZATION_CODE_QUERY: &str = "INSERT INTO authorization_codes
+    (code_hash, oauth_client_id, user_id, redirect_uri, scope, code_challenge, session_id,
+        token_version, is_used, expired_at, nonce, auth_time, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, FALSE, $9, $10, $11, $12)";
+// previous value of is_used tells about repeated exchange
+const USE_AUTHORIZATION_CODE_QUERY: &str = "UPDATE authorization_codes c
+    SET is_used=TRUE
+    FROM (SELECT code_id, is_used FROM authorization_codes WHERE code_hash=$1 FOR UPDATE) prev
+    WHERE c.code_id=prev.code_id
+    RETURNING c.oauth_client_id, c.user_id, c.redirect_uri, c.scope, c.code_challenge,
+        c.session_id, c.token_version, c.expired_at, c.nonce, c.auth_time, prev.is_used";
+
+impl SqlSerializer<AuthorizationCode> for AuthorizationCode {
+    fn from_sql_result(row: &Row) -> AuthorizationCode {
+        AuthorizationCode {
+            oauth_client_id: row.get(0),
+            user_id: row.get(1),
+            redirect_uri: row.get(2),
+            scope: row.get(3),
+            code_challenge: row.get(4),
+            session_id: row.get(5),
+            token_version: row.get(6),
+            expired_at: row.get(7),
+            nonce: row.get(8),
+            auth_time: row.get(9),
+            was_used: row.get(10),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreAuthorizationCode for AuthorizationCodeRepo {
+    async fn save_authorization_code(
+        &self,
+        code: AuthorizationCodeForCreation,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[
+            &code.code_hash,
+            &code.oauth_client_id,
+            &code.user_id,
+            &code.redirect_uri,
+            &code.scope,
+            &code.code_challenge,
+            &code.session_id,
+            &code.token_version,
+            &code.expired_at,
+            &code.nonce,
+            &code.auth_time,
+            &now,
+        ];
+        match execute_query(&self.db_pool, SAVE_AUTHORIZATION_CODE_QUERY, params).await? {
+            1 => Ok(()),
+            _ => Err(AccessModelError::FatalError),
+        }
+    }
+    async fn use_authorization_code(
+        &self,
+        code_hash: &str,
+    ) -> Result<AuthorizationCode, AccessModelError> {
+        get_item(&self.db_pool, USE_AUTHORIZATION_CODE_QUERY, &[&code_hash]).await
+    }
+}
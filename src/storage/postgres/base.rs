@@ -1,7 +1,7 @@
 use crate::usecases::base_entities::AccessModelError;
 use deadpool_postgres::{Client, Pool};
 use log::error;
-use tokio_postgres::types::ToSql;
+use tokio_postgres::types::{FromSql, ToSql};
 use tokio_postgres::{error::SqlState, Row, Statement};
 
 pub async fn get_client(db_pool: &Pool) -> Result<Client, AccessModelError> {
@@ -37,7 +37,7 @@ pub async fn get_item<T: SqlSerializer<T>>(
     let stmt = prepare_stmt(&client, query).await?;
     match client.query(&stmt, params).await {
         Ok(rows) if rows.len() == 1 => Ok(T::from_sql_result(&rows[0])),
-        Ok(rows) if rows.len() == 0 => Err(AccessModelError::NotFoundError),
+        Ok(rows) if rows.is_empty() => Err(AccessModelError::NotFoundError),
         Ok(_) => {
             error!("During getting item count of retirning rows not equals one");
             Err(AccessModelError::FatalError)
@@ -49,6 +49,31 @@ pub async fn get_item<T: SqlSerializer<T>>(
     }
 }
 
+pub async fn get_value<T>(
+    db_pool: &Pool,
+    query: &str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<T, AccessModelError>
+where
+    T: for<'a> FromSql<'a>,
+{
+    // single column of single row
+    let client = get_client(db_pool).await?;
+    let stmt = prepare_stmt(&client, query).await?;
+    match client.query(&stmt, params).await {
+        Ok(rows) if rows.len() == 1 => Ok(rows[0].get(0)),
+        Ok(rows) if rows.is_empty() => Err(AccessModelError::NotFoundError),
+        Ok(_) => {
+            error!("During getting value count of retirning rows not equals one");
+            Err(AccessModelError::FatalError)
+        }
+        Err(e) => {
+            error!("{}", e);
+            Err(AccessModelError::FatalError)
+        }
+    }
+}
+
 pub async fn insert_item<T: SqlSerializer<T>>(
     db_pool: &Pool,
     query: &str,
@@ -82,7 +107,7 @@ pub async fn update_item<T: SqlSerializer<T>>(
     let stmt = prepare_stmt(&client, query).await?;
     match client.query(&stmt, params).await {
         Ok(rows) if rows.len() == 1 => Ok(T::from_sql_result(&rows[0])),
-        Ok(rows) if rows.len() == 0 => Err(AccessModelError::NotFoundError),
+        Ok(rows) if rows.is_empty() => Err(AccessModelError::NotFoundError),
         Ok(_) => {
             error!("During update item count of retirning rows not equals one");
             Err(AccessModelError::FatalError)
@@ -112,6 +137,23 @@ pub async fn delete_item(
     }
 }
 
+/// Returns count of affected rows
+pub async fn execute_query(
+    db_pool: &Pool,
+    query: &str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<u64, AccessModelError> {
+    let client = get_client(db_pool).await?;
+    let stmt = prepare_stmt(&client, query).await?;
+    match client.execute(&stmt, params).await {
+        Ok(res) => Ok(res),
+        Err(e) => {
+            error!("{}", e);
+            Err(AccessModelError::FatalError)
+        }
+    }
+}
+
 pub trait ListingQueryBuilder {
     fn build_listing_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>);
 }
@@ -126,7 +168,7 @@ where
     T: SqlSerializer<T>,
 {
     let (query, params) = filters.build_listing_query_with_params();
-    let stmt = prepare_stmt(&client, &query).await?;
+    let stmt = prepare_stmt(client, &query).await?;
     match client.query(&stmt, &params).await {
         Ok(rows) => Ok(rows
             .into_iter()
@@ -143,7 +185,7 @@ pub async fn get_count<F: CountQueryBuilder>(
     filters: F,
 ) -> Result<i64, AccessModelError> {
     let (query, params) = filters.build_count_query_with_params();
-    let stmt = prepare_stmt(&client, &query).await?;
+    let stmt = prepare_stmt(client, &query).await?;
     match client.query(&stmt, &params).await {
         Ok(rows) if rows.len() == 1 => Ok(rows[0].get(0)),
         Ok(_) => Err(AccessModelError::FatalError),
@@ -0,0 +1,124 @@
+use crate::storage::postgres::base::get_client;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::{DirectoryEntry, UserCredentials};
+use crate::usecases::users::ldap_sign_in::{SyncDirectoryUser, LDAP_PROVIDER};
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::error::SqlState;
+
+pub struct DirectoryRepo {
+    db_pool: Pool,
+}
+
+impl DirectoryRepo {
+    pub fn new(db_pool: Pool) -> DirectoryRepo {
+        DirectoryRepo { db_pool }
+    }
+}
+
+fn query_error(e: tokio_postgres::Error) -> AccessModelError {
+    match e.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => AccessModelError::AlreadyExists,
+        _ => {
+            error!("{}", e);
+            AccessModelError::FatalError
+        }
+    }
+}
+
+const FIND_LINKED_USER_QUERY: &str = "UPDATE user_identities
+    SET last_login_at=$3
+    WHERE provider=$1 AND subject=$2
+    RETURNING user_id";
+// directory users have no local password, empty hash never matches
+const INSERT_DIRECTORY_USER_QUERY: &str = "INSERT INTO users
+    (username, password_hash, enabled, created_at, updated_at, is_deleted)
+    VALUES ($1, '', TRUE, $2, $2, FALSE)
+    RETURNING user_id";
+const INSERT_IDENTITY_QUERY: &str = "INSERT INTO user_identities
+    (user_id, provider, subject, created_at, last_login_at)
+    VALUES ($1, $2, $3, $4, $4)";
+const GET_USER_CREDENTIALS_QUERY: &str = "
+    SELECT user_id, password_hash, token_version
+    FROM users
+    WHERE user_id=$1 AND enabled=TRUE AND is_deleted=FALSE";
+const UNBIND_ROLES_QUERY: &str = "UPDATE role_members rm
+    SET is_deleted=TRUE, updated_at=$3
+    FROM roles r
+    WHERE rm.role_id=r.role_id AND rm.user_id=$1 AND rm.is_deleted=FALSE
+        AND r.role_name=ANY($2)";
+const BIND_ROLES_QUERY: &str = "INSERT INTO role_members
+    (user_id, role_id, created_at, updated_at, is_deleted)
+    SELECT $1, role_id, $3, $3, FALSE
+    FROM roles
+    WHERE role_name=ANY($2) AND is_deleted=FALSE
+    ON CONFLICT (role_id, user_id) DO UPDATE
+    SET is_deleted=FALSE, updated_at=EXCLUDED.updated_at
+    WHERE role_members.is_deleted=TRUE";
+
+#[async_trait]
+impl SyncDirectoryUser for DirectoryRepo {
+    async fn sync_directory_user(
+        &self,
+        entry: &DirectoryEntry,
+    ) -> Result<UserCredentials, AccessModelError> {
+        let now = chrono::Utc::now();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        let linked = transaction
+            .query_opt(FIND_LINKED_USER_QUERY, &[&LDAP_PROVIDER, &entry.dn, &now])
+            .await
+            .map_err(query_error)?;
+        let user_id: i32 = match linked {
+            Some(row) => row.get(0),
+            None => {
+                let user_id: i32 = transaction
+                    .query_one(INSERT_DIRECTORY_USER_QUERY, &[&entry.username, &now])
+                    .await
+                    .map_err(query_error)?
+                    .get(0);
+                transaction
+                    .execute(
+                        INSERT_IDENTITY_QUERY,
+                        &[&user_id, &LDAP_PROVIDER, &entry.dn, &now],
+                    )
+                    .await
+                    .map_err(query_error)?;
+                user_id
+            }
+        };
+        let credentials = transaction
+            .query_opt(GET_USER_CREDENTIALS_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?
+            .map(|row| UserCredentials::new(row.get(0), row.get(1), row.get(2)));
+        transaction.commit().await.map_err(query_error)?;
+        credentials.ok_or(AccessModelError::NotFoundError)
+    }
+    async fn sync_user_roles(
+        &self,
+        user_id: i32,
+        managed: &[String],
+        granted: &[String],
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let revoked: Vec<&String> = managed
+            .iter()
+            .filter(|role| !granted.contains(role))
+            .collect();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        transaction
+            .execute(UNBIND_ROLES_QUERY, &[&user_id, &revoked, &now])
+            .await
+            .map_err(query_error)?;
+        transaction
+            .execute(BIND_ROLES_QUERY, &[&user_id, &granted, &now])
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)
+    }
+}
@@ -0,0 +1,162 @@
+use crate::storage::postgres::base::{
+    execute_query, get_client, get_item, get_value, SqlSerializer,
+};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::federation::entities::{
+    FederatedLogin, FederatedLoginForCreation, FederatedUserForCreation,
+};
+use crate::usecases::federation::federated_sign_in::{LinkIdentity, StoreFederatedLogin};
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+pub struct FederationRepo {
+    db_pool: Pool,
+}
+
+impl FederationRepo {
+    pub fn new(db_pool: Pool) -> FederationRepo {
+        FederationRepo { db_pool }
+    }
+}
+
+fn query_error(e: tokio_postgres::Error) -> AccessModelError {
+    error!("{}", e);
+    match e.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => AccessModelError::AlreadyExists,
+        _ => AccessModelError::FatalError,
+    }
+}
+
+const DELETE_FINISHED_LOGINS_QUERY: &str = "DELETE FROM federated_logins
+    WHERE is_used=TRUE OR expired_at<$1";
+const SAVE_FEDERATED_LOGIN_QUERY: &str = "INSERT INTO federated_logins
+    (state_hash, provider, nonce, code_verifier, is_used, expired_at, created_at)
+    VALUES ($1, $2, $3, $4, FALSE, $5, $6)";
+const USE_FEDERATED_LOGIN_QUERY: &str = "UPDATE federated_logins
+    SET is_used=TRUE
+    WHERE state_hash=$1 AND is_used=FALSE AND expired_at>$2
+    RETURNING provider, nonce, code_verifier";
+const FIND_LINKED_USER_QUERY: &str = "UPDATE user_identities
+    SET last_login_at=$3
+    WHERE provider=$1 AND subject=$2
+    RETURNING user_id";
+const LINK_IDENTITY_QUERY: &str = "INSERT INTO user_identities
+    (user_id, provider, subject, created_at)
+    SELECT user_id, $2, $3, $4
+    FROM users
+    WHERE user_id=$1 AND is_deleted=FALSE";
+// federated users have no local password, empty hash never matches
+const INSERT_FEDERATED_USER_QUERY: &str = "INSERT INTO users
+    (username, password_hash, enabled, created_at, updated_at, is_deleted)
+    VALUES ($1, '', TRUE, $2, $2, FALSE)
+    RETURNING user_id";
+const INSERT_IDENTITY_QUERY: &str = "INSERT INTO user_identities
+    (user_id, provider, subject, created_at, last_login_at)
+    VALUES ($1, $2, $3, $4, $4)";
+
+impl SqlSerializer<FederatedLogin> for FederatedLogin {
+    fn from_sql_result(row: &Row) -> FederatedLogin {
+        FederatedLogin {
+            provider: row.get(0),
+            nonce: row.get(1),
+            code_verifier: row.get(2),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreFederatedLogin for FederationRepo {
+    async fn save_federated_login(
+        &self,
+        login: FederatedLoginForCreation,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        execute_query(&self.db_pool, DELETE_FINISHED_LOGINS_QUERY, &[&now]).await?;
+        let params: &[&(dyn ToSql + Sync)] = &[
+            &login.state_hash,
+            &login.provider,
+            &login.nonce,
+            &login.code_verifier,
+            &login.expired_at,
+            &now,
+        ];
+        match execute_query(&self.db_pool, SAVE_FEDERATED_LOGIN_QUERY, params).await? {
+            1 => Ok(()),
+            _ => Err(AccessModelError::FatalError),
+        }
+    }
+    async fn use_federated_login(
+        &self,
+        state_hash: &str,
+    ) -> Result<FederatedLogin, AccessModelError> {
+        let now = chrono::Utc::now();
+        get_item(
+            &self.db_pool,
+            USE_FEDERATED_LOGIN_QUERY,
+            &[&state_hash, &now],
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl LinkIdentity for FederationRepo {
+    async fn find_linked_user(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<i32, AccessModelError> {
+        let now = chrono::Utc::now();
+        get_value(
+            &self.db_pool,
+            FIND_LINKED_USER_QUERY,
+            &[&provider, &subject, &now],
+        )
+        .await
+    }
+    async fn link_identity(
+        &self,
+        user_id: i32,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let client = get_client(&self.db_pool).await?;
+        match client
+            .execute(LINK_IDENTITY_QUERY, &[&user_id, &provider, &subject, &now])
+            .await
+            .map_err(query_error)?
+        {
+            0 => Err(AccessModelError::NotFoundError),
+            _ => Ok(()),
+        }
+    }
+    async fn create_federated_user(
+        &self,
+        user: FederatedUserForCreation,
+    ) -> Result<i32, AccessModelError> {
+        let now = chrono::Utc::now();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        let user_id: i32 = transaction
+            .query_one(INSERT_FEDERATED_USER_QUERY, &[&user.username, &now])
+            .await
+            .map_err(query_error)?
+            .get(0);
+        transaction
+            .execute(
+                INSERT_IDENTITY_QUERY,
+                &[&user_id, &user.provider, &user.subject, &now],
+            )
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)?;
+        Ok(user_id)
+    }
+}
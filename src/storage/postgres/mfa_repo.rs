@@ -0,0 +1,195 @@
+use crate::storage::postgres::base::{execute_query, get_client, get_item, SqlSerializer};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::{MfaChallenge, MfaChallengeForCreation, TotpSecret};
+use crate::usecases::users::mfa::StoreMfa;
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+pub struct MfaRepo {
+    db_pool: Pool,
+}
+
+impl MfaRepo {
+    pub fn new(db_pool: Pool) -> MfaRepo {
+        MfaRepo { db_pool }
+    }
+
+    async fn execute_one(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<(), AccessModelError> {
+        match execute_query(&self.db_pool, query, params).await? {
+            0 => Err(AccessModelError::NotFoundError),
+            _ => Ok(()),
+        }
+    }
+}
+
+const GET_TOTP_QUERY: &str = "SELECT user_id, secret, is_confirmed
+    FROM user_totp
+    WHERE user_id=$1";
+const SAVE_UNCONFIRMED_TOTP_QUERY: &str = "INSERT INTO user_totp
+    (user_id, secret, is_confirmed, last_used_step, created_at, updated_at)
+    VALUES ($1, $2, FALSE, 0, $3, $3)
+    ON CONFLICT (user_id) DO UPDATE
+    SET secret=EXCLUDED.secret, updated_at=EXCLUDED.updated_at
+    WHERE user_totp.is_confirmed=FALSE";
+const CONFIRM_TOTP_QUERY: &str = "UPDATE user_totp
+    SET is_confirmed=TRUE, last_used_step=$1, updated_at=$2
+    WHERE user_id=$3 AND is_confirmed=FALSE";
+const DELETE_RECOVERY_CODES_QUERY: &str = "DELETE FROM recovery_codes WHERE user_id=$1";
+const INSERT_RECOVERY_CODE_QUERY: &str = "INSERT INTO recovery_codes
+    (user_id, code_hash, created_at)
+    VALUES ($1, $2, $3)";
+const USE_TOTP_STEP_QUERY: &str = "UPDATE user_totp
+    SET last_used_step=$1, updated_at=$2
+    WHERE user_id=$3 AND is_confirmed=TRUE AND last_used_step<$1";
+const USE_RECOVERY_CODE_QUERY: &str = "UPDATE recovery_codes
+    SET used_at=$1
+    WHERE user_id=$2 AND code_hash=$3 AND used_at IS NULL";
+const SAVE_MFA_CHALLENGE_QUERY: &str = "INSERT INTO mfa_challenges
+    (token_hash, user_id, username, token_version, failed_attempts, is_used, expired_at, created_at)
+    VALUES ($1, $2, $3, $4, 0, FALSE, $5, $6)";
+const GET_MFA_CHALLENGE_QUERY: &str = "SELECT challenge_id, user_id, username, token_version
+    FROM mfa_challenges
+    WHERE token_hash=$1 AND is_used=FALSE AND expired_at>$2 AND failed_attempts<$3";
+const FAIL_MFA_CHALLENGE_QUERY: &str = "UPDATE mfa_challenges
+    SET failed_attempts=failed_attempts + 1
+    WHERE challenge_id=$1";
+const CLOSE_MFA_CHALLENGE_QUERY: &str = "UPDATE mfa_challenges
+    SET is_used=TRUE
+    WHERE challenge_id=$1 AND is_used=FALSE";
+
+impl SqlSerializer<TotpSecret> for TotpSecret {
+    fn from_sql_result(row: &Row) -> TotpSecret {
+        TotpSecret {
+            user_id: row.get(0),
+            secret: row.get(1),
+            is_confirmed: row.get(2),
+        }
+    }
+}
+
+impl SqlSerializer<MfaChallenge> for MfaChallenge {
+    fn from_sql_result(row: &Row) -> MfaChallenge {
+        MfaChallenge {
+            challenge_id: row.get(0),
+            user_id: row.get(1),
+            username: row.get(2),
+            token_version: row.get(3),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreMfa for MfaRepo {
+    async fn get_totp(&self, user_id: i32) -> Result<TotpSecret, AccessModelError> {
+        get_item(&self.db_pool, GET_TOTP_QUERY, &[&user_id]).await
+    }
+    async fn save_unconfirmed_totp(
+        &self,
+        user_id: i32,
+        secret: &str,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        match self
+            .execute_one(SAVE_UNCONFIRMED_TOTP_QUERY, &[&user_id, &secret, &now])
+            .await
+        {
+            Err(AccessModelError::NotFoundError) => Err(AccessModelError::AlreadyExists),
+            result => result,
+        }
+    }
+    async fn confirm_totp(
+        &self,
+        user_id: i32,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let query_error = |e: tokio_postgres::Error| {
+            error!("{}", e);
+            AccessModelError::FatalError
+        };
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        let confirmed = transaction
+            .execute(CONFIRM_TOTP_QUERY, &[&step, &now, &user_id])
+            .await
+            .map_err(query_error)?;
+        if confirmed == 0 {
+            return Err(AccessModelError::NotFoundError);
+        }
+        transaction
+            .execute(DELETE_RECOVERY_CODES_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?;
+        let stmt = transaction
+            .prepare(INSERT_RECOVERY_CODE_QUERY)
+            .await
+            .map_err(query_error)?;
+        for code_hash in recovery_code_hashes {
+            transaction
+                .execute(&stmt, &[&user_id, code_hash, &now])
+                .await
+                .map_err(query_error)?;
+        }
+        transaction.commit().await.map_err(query_error)
+    }
+    async fn use_totp_step(&self, user_id: i32, step: i64) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        self.execute_one(USE_TOTP_STEP_QUERY, &[&step, &now, &user_id])
+            .await
+    }
+    async fn use_recovery_code(
+        &self,
+        user_id: i32,
+        code_hash: &str,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        self.execute_one(USE_RECOVERY_CODE_QUERY, &[&now, &user_id, &code_hash])
+            .await
+    }
+    async fn save_mfa_challenge(
+        &self,
+        challenge: MfaChallengeForCreation,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[
+            &challenge.token_hash,
+            &challenge.user_id,
+            &challenge.username,
+            &challenge.token_version,
+            &challenge.expired_at,
+            &now,
+        ];
+        self.execute_one(SAVE_MFA_CHALLENGE_QUERY, params).await
+    }
+    async fn get_mfa_challenge(
+        &self,
+        token_hash: &str,
+        max_failures: i32,
+    ) -> Result<MfaChallenge, AccessModelError> {
+        let now = chrono::Utc::now();
+        get_item(
+            &self.db_pool,
+            GET_MFA_CHALLENGE_QUERY,
+            &[&token_hash, &now, &max_failures],
+        )
+        .await
+    }
+    async fn fail_mfa_challenge(&self, challenge_id: i32) -> Result<(), AccessModelError> {
+        self.execute_one(FAIL_MFA_CHALLENGE_QUERY, &[&challenge_id])
+            .await
+    }
+    async fn close_mfa_challenge(&self, challenge_id: i32) -> Result<(), AccessModelError> {
+        self.execute_one(CLOSE_MFA_CHALLENGE_QUERY, &[&challenge_id])
+            .await
+    }
+}
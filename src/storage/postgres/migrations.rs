@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Client, Pool};
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Schema migrations compiled into the binary, new migration goes to the end with the next version
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "add_users",
+        sql: include_str!("../../../migrations/V1__add_users.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "add_role_perms",
+        sql: include_str!("../../../migrations/V2__add_role_perms.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_roles_members",
+        sql: include_str!("../../../migrations/V3__add_roles_members.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "add_refresh_tokens",
+        sql: include_str!("../../../migrations/V4__add_refresh_tokens.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "add_token_revocation",
+        sql: include_str!("../../../migrations/V5__add_token_revocation.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "add_services",
+        sql: include_str!("../../../migrations/V6__add_services.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_service_cert_identities",
+        sql: include_str!("../../../migrations/V7__add_service_cert_identities.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_failed_sign_in_attempts",
+        sql: include_str!("../../../migrations/V8__add_failed_sign_in_attempts.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_totp",
+        sql: include_str!("../../../migrations/V9__add_totp.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "add_oauth_clients",
+        sql: include_str!("../../../migrations/V10__add_oauth_clients.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_authorization_codes",
+        sql: include_str!("../../../migrations/V11__add_authorization_codes.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "add_oidc_to_authorization_codes",
+        sql: include_str!("../../../migrations/V12__add_oidc_to_authorization_codes.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "add_user_identities",
+        sql: include_str!("../../../migrations/V13__add_user_identities.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "add_api_keys",
+        sql: include_str!("../../../migrations/V14__add_api_keys.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "add_password_reset",
+        sql: include_str!("../../../migrations/V15__add_password_reset.sql"),
+    },
+];
+
+// any constant shared by all instances, only one of them applies migrations at a time
+const MIGRATIONS_LOCK_ID: i64 = 4_271_019_585;
+
+const CREATE_MIGRATIONS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version int PRIMARY KEY,
+    name text NOT NULL,
+    checksum text NOT NULL,
+    applied_at timestamptz NOT NULL
+)";
+const GET_APPLIED_MIGRATIONS_QUERY: &str =
+    "SELECT version, checksum, applied_at FROM schema_migrations ORDER BY version";
+const INSERT_MIGRATION_QUERY: &str =
+    "INSERT INTO schema_migrations (version, name, checksum, applied_at) VALUES ($1, $2, $3, $4)";
+
+#[derive(Debug)]
+pub enum MigrationError {
+    ConnectionError(String),
+    QueryError(String),
+    ChecksumMismatchError(i32),
+}
+
+pub struct MigrationStatus {
+    pub version: i32,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub checksum_matches: bool,
+}
+
+struct AppliedMigration {
+    checksum: String,
+    applied_at: DateTime<Utc>,
+}
+
+pub fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Applies pending migrations under advisory lock, returns versions applied by this call
+pub async fn migrate_up(db_pool: &Pool) -> Result<Vec<i32>, MigrationError> {
+    let mut client = get_migration_client(db_pool).await?;
+    client
+        .execute("SELECT pg_advisory_lock($1)", &[&MIGRATIONS_LOCK_ID])
+        .await
+        .map_err(query_error)?;
+    let result = apply_pending_migrations(&mut client).await;
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&MIGRATIONS_LOCK_ID])
+        .await
+        .map_err(query_error)?;
+    result
+}
+
+pub async fn migration_status(db_pool: &Pool) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let client = get_migration_client(db_pool).await?;
+    let applied = get_applied_migrations(&client).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| {
+            let applied_migration = applied.get(&migration.version);
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied_at: applied_migration.map(|m| m.applied_at),
+                checksum_matches: applied_migration
+                    .is_none_or(|m| m.checksum == checksum(migration.sql)),
+            }
+        })
+        .collect())
+}
+
+async fn apply_pending_migrations(client: &mut Client) -> Result<Vec<i32>, MigrationError> {
+    let applied = get_applied_migrations(client).await?;
+    for version in applied.keys() {
+        if !MIGRATIONS.iter().any(|m| m.version == *version) {
+            warn!(
+                "migration V{} is applied, but unknown to this build",
+                version
+            );
+        }
+    }
+    let mut applied_now = vec![];
+    for migration in MIGRATIONS.iter() {
+        if let Some(applied_migration) = applied.get(&migration.version) {
+            if applied_migration.checksum != checksum(migration.sql) {
+                return Err(MigrationError::ChecksumMismatchError(migration.version));
+            }
+            continue;
+        }
+        let transaction = client.transaction().await.map_err(query_error)?;
+        transaction
+            .batch_execute(migration.sql)
+            .await
+            .map_err(query_error)?;
+        transaction
+            .execute(
+                INSERT_MIGRATION_QUERY,
+                &[
+                    &migration.version,
+                    &migration.name,
+                    &checksum(migration.sql),
+                    &Utc::now(),
+                ],
+            )
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)?;
+        info!(
+            "migration V{}__{} applied",
+            migration.version, migration.name
+        );
+        applied_now.push(migration.version);
+    }
+    Ok(applied_now)
+}
+
+async fn get_applied_migrations(
+    client: &Client,
+) -> Result<HashMap<i32, AppliedMigration>, MigrationError> {
+    client
+        .batch_execute(CREATE_MIGRATIONS_TABLE_QUERY)
+        .await
+        .map_err(query_error)?;
+    let rows = client
+        .query(GET_APPLIED_MIGRATIONS_QUERY, &[])
+        .await
+        .map_err(query_error)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.get(0),
+                AppliedMigration {
+                    checksum: row.get(1),
+                    applied_at: row.get(2),
+                },
+            )
+        })
+        .collect())
+}
+
+async fn get_migration_client(db_pool: &Pool) -> Result<Client, MigrationError> {
+    db_pool
+        .get()
+        .await
+        .map_err(|e| MigrationError::ConnectionError(e.to_string()))
+}
+
+fn query_error(e: tokio_postgres::Error) -> MigrationError {
+    MigrationError::QueryError(e.to_string())
+}
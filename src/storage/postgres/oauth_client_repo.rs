@@ -0,0 +1,118 @@
+use crate::storage::postgres::base::{
+    execute_query, get_client, get_item, insert_item, prepare_stmt, SqlSerializer,
+};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::client_credentials::AuthenticateClient;
+use crate::usecases::oauth::client_registry::RegisterClient;
+use crate::usecases::oauth::entities::{OAuthClient, OAuthClientForCreation};
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+pub struct OAuthClientRepo {
+    db_pool: Pool,
+}
+
+impl OAuthClientRepo {
+    pub fn new(db_pool: Pool) -> OAuthClientRepo {
+        OAuthClientRepo { db_pool }
+    }
+}
+
+const GET_CLIENT_QUERY: &str = "
+    SELECT oauth_client_id, client_id, secret_hash, scopes, grant_types, redirect_uris
+    FROM oauth_clients
+    WHERE client_id=$1 AND enabled=TRUE AND is_deleted=FALSE";
+const INSERT_CLIENT_QUERY: &str = "INSERT INTO oauth_clients
+    (client_id, secret_hash, scopes, grant_types, redirect_uris, enabled, created_at, updated_at, is_deleted)
+    VALUES ($1, $2, $3, $4, $5, TRUE, $6, $6, FALSE)
+    RETURNING oauth_client_id, client_id, secret_hash, scopes, grant_types, redirect_uris";
+const BIND_CLIENT_ROLE_QUERY: &str = "INSERT INTO oauth_client_roles
+    (oauth_client_id, role_id, created_at, updated_at, is_deleted)
+    SELECT c.oauth_client_id, r.role_id, $3, $3, FALSE
+    FROM oauth_clients c, roles r
+    WHERE c.client_id=$1 AND c.is_deleted=FALSE AND r.role_id=$2 AND r.is_deleted=FALSE
+    ON CONFLICT (oauth_client_id, role_id) DO UPDATE
+    SET is_deleted=FALSE, updated_at=EXCLUDED.updated_at";
+const GET_CLIENT_PERMS_QUERY: &str = "
+    SELECT role_name
+    FROM oauth_client_roles cr
+    JOIN roles r USING(role_id)
+    WHERE cr.oauth_client_id=$1 AND cr.is_deleted=FALSE AND r.is_deleted=FALSE
+    UNION
+    SELECT permission_name
+    FROM oauth_client_roles cr
+    JOIN roles r USING(role_id)
+    JOIN role_permissions rp USING(role_id)
+    JOIN permissions p USING(permission_id)
+    WHERE cr.oauth_client_id=$1 AND cr.is_deleted=FALSE AND r.is_deleted=FALSE
+        AND rp.is_deleted=FALSE AND p.is_deleted=FALSE";
+
+impl SqlSerializer<OAuthClient> for OAuthClient {
+    fn from_sql_result(row: &Row) -> OAuthClient {
+        OAuthClient {
+            oauth_client_id: row.get(0),
+            client_id: row.get(1),
+            secret_hash: row.get(2),
+            scopes: row.get(3),
+            grant_types: row.get(4),
+            redirect_uris: row.get(5),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthenticateClient for OAuthClientRepo {
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, AccessModelError> {
+        get_item(&self.db_pool, GET_CLIENT_QUERY, &[&client_id]).await
+    }
+    async fn get_client_perms(
+        &self,
+        oauth_client_id: i32,
+    ) -> Result<Vec<String>, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        let stmt = prepare_stmt(&client, GET_CLIENT_PERMS_QUERY).await?;
+        match client.query(&stmt, &[&oauth_client_id]).await {
+            Ok(rows) => Ok(rows.into_iter().map(|row| row.get(0)).collect()),
+            Err(e) => {
+                error!("{}", e);
+                Err(AccessModelError::FatalError)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RegisterClient for OAuthClientRepo {
+    async fn save_client(
+        &self,
+        client_data: OAuthClientForCreation,
+    ) -> Result<OAuthClient, AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[
+            &client_data.client_id,
+            &client_data.secret_hash,
+            &client_data.scopes,
+            &client_data.grant_types,
+            &client_data.redirect_uris,
+            &now,
+        ];
+        insert_item(&self.db_pool, INSERT_CLIENT_QUERY, params).await
+    }
+    async fn bind_client_role(
+        &self,
+        client_id: &str,
+        role_id: i32,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[&client_id, &role_id, &now];
+        match execute_query(&self.db_pool, BIND_CLIENT_ROLE_QUERY, params).await? {
+            0 => Err(AccessModelError::NotFoundError),
+            _ => Ok(()),
+        }
+    }
+}
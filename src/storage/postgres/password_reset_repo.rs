@@ -0,0 +1,174 @@
+use crate::storage::postgres::base::get_client;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::ResetRecipient;
+use crate::usecases::users::password_reset::StorePasswordReset;
+
+use async_trait::async_trait;
+use chrono::{self, DateTime, Utc};
+use deadpool_postgres::Pool;
+use log::error;
+
+pub struct PasswordResetRepo {
+    db_pool: Pool,
+}
+
+impl PasswordResetRepo {
+    pub fn new(db_pool: Pool) -> PasswordResetRepo {
+        PasswordResetRepo { db_pool }
+    }
+}
+
+fn query_error(e: tokio_postgres::Error) -> AccessModelError {
+    error!("{}", e);
+    AccessModelError::FatalError
+}
+
+// users without local password sign in with LDAP or upstream provider
+const GET_RESET_RECIPIENT_QUERY: &str = "SELECT user_id, email
+    FROM users
+    WHERE username=$1 AND enabled=TRUE AND is_deleted=FALSE
+        AND password_hash<>'' AND coalesce(email, '')<>''";
+// concurrent requests of the user wait for each other, so only one of them passes the interval check
+const LOCK_USER_QUERY: &str = "SELECT user_id FROM users WHERE user_id=$1 FOR UPDATE";
+const LAST_REQUESTED_AT_QUERY: &str = "SELECT max(created_at)
+    FROM password_reset_tokens
+    WHERE user_id=$1";
+const CLOSE_USER_TOKENS_QUERY: &str = "UPDATE password_reset_tokens
+    SET is_used=TRUE
+    WHERE user_id=$1 AND is_used=FALSE";
+const INSERT_TOKEN_QUERY: &str = "INSERT INTO password_reset_tokens
+    (user_id, token_hash, is_used, expired_at, created_at)
+    VALUES ($1, $2, FALSE, $3, $4)";
+const USE_TOKEN_QUERY: &str = "UPDATE password_reset_tokens t
+    SET is_used=TRUE
+    FROM users u
+    WHERE t.user_id=u.user_id AND t.token_hash=$1 AND t.is_used=FALSE AND t.expired_at>$2
+        AND u.enabled=TRUE AND u.is_deleted=FALSE
+    RETURNING t.user_id";
+// sessions started with the old password are revoked together with the change
+const SET_PASSWORD_QUERY: &str = "UPDATE users
+    SET password_hash=$2, updated_at=$3, token_version=token_version + 1
+    WHERE user_id=$1 AND is_deleted=FALSE
+    RETURNING token_version";
+const REVOKE_USER_REFRESH_TOKENS_QUERY: &str = "UPDATE refresh_tokens
+    SET is_revoked=TRUE, updated_at=$2
+    WHERE user_id=$1 AND is_revoked=FALSE";
+
+#[async_trait]
+impl StorePasswordReset for PasswordResetRepo {
+    async fn get_reset_recipient(
+        &self,
+        username: &str,
+    ) -> Result<ResetRecipient, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        match client
+            .query_opt(GET_RESET_RECIPIENT_QUERY, &[&username])
+            .await
+            .map_err(query_error)?
+        {
+            Some(row) => Ok(ResetRecipient {
+                user_id: row.get(0),
+                email: row.get(1),
+            }),
+            None => Err(AccessModelError::NotFoundError),
+        }
+    }
+    async fn save_reset_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expired_at: DateTime<Utc>,
+        requested_after: DateTime<Utc>,
+    ) -> Result<bool, AccessModelError> {
+        let now = chrono::Utc::now();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        if transaction
+            .query_opt(LOCK_USER_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?
+            .is_none()
+        {
+            return Err(AccessModelError::NotFoundError);
+        }
+        let last_requested_at: Option<DateTime<Utc>> = transaction
+            .query_one(LAST_REQUESTED_AT_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?
+            .get(0);
+        if matches!(last_requested_at, Some(requested_at) if requested_at > requested_after) {
+            return Ok(false);
+        }
+        transaction
+            .execute(CLOSE_USER_TOKENS_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?;
+        transaction
+            .execute(
+                INSERT_TOKEN_QUERY,
+                &[&user_id, &token_hash, &expired_at, &now],
+            )
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)?;
+        Ok(true)
+    }
+    async fn reset_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<(i32, i32), AccessModelError> {
+        let now = chrono::Utc::now();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        let user_id: i32 = match transaction
+            .query_opt(USE_TOKEN_QUERY, &[&token_hash, &now])
+            .await
+            .map_err(query_error)?
+        {
+            Some(row) => row.get(0),
+            None => return Err(AccessModelError::NotFoundError),
+        };
+        let token_version: i32 = match transaction
+            .query_opt(SET_PASSWORD_QUERY, &[&user_id, &password_hash, &now])
+            .await
+            .map_err(query_error)?
+        {
+            Some(row) => row.get(0),
+            None => return Err(AccessModelError::NotFoundError),
+        };
+        transaction
+            .execute(REVOKE_USER_REFRESH_TOKENS_QUERY, &[&user_id, &now])
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)?;
+        Ok((user_id, token_version))
+    }
+    async fn set_password(
+        &self,
+        user_id: i32,
+        password_hash: &str,
+    ) -> Result<i32, AccessModelError> {
+        let now = chrono::Utc::now();
+        let mut client = get_client(&self.db_pool).await?;
+        let transaction = client.transaction().await.map_err(query_error)?;
+        let token_version: i32 = match transaction
+            .query_opt(SET_PASSWORD_QUERY, &[&user_id, &password_hash, &now])
+            .await
+            .map_err(query_error)?
+        {
+            Some(row) => row.get(0),
+            None => return Err(AccessModelError::NotFoundError),
+        };
+        transaction
+            .execute(CLOSE_USER_TOKENS_QUERY, &[&user_id])
+            .await
+            .map_err(query_error)?;
+        transaction
+            .execute(REVOKE_USER_REFRESH_TOKENS_QUERY, &[&user_id, &now])
+            .await
+            .map_err(query_error)?;
+        transaction.commit().await.map_err(query_error)?;
+        Ok(token_version)
+    }
+}
@@ -96,28 +96,19 @@ fn add_permission_filters<'r, 'a>(
         }
         None => query.push_str(" WHERE TRUE"),
     }
-    match &filters.permission_id {
-        Some(permission_id) => {
-            params.push(permission_id);
-            query.push_str(&format!(" AND p.permission_id=${}", cnt));
-            cnt += 1;
-        }
-        None => (),
+    if let Some(permission_id) = &filters.permission_id {
+        params.push(permission_id);
+        query.push_str(&format!(" AND p.permission_id=${}", cnt));
+        cnt += 1;
     }
-    match &filters.permission_name {
-        Some(permission_name) => {
-            params.push(permission_name);
-            query.push_str(&format!(" AND permission_name=${}", cnt));
-            cnt += 1;
-        }
-        None => (),
+    if let Some(permission_name) = &filters.permission_name {
+        params.push(permission_name);
+        query.push_str(&format!(" AND permission_name=${}", cnt));
+        cnt += 1;
     }
-    match &filters.is_deleted {
-        Some(is_deleted) => {
-            params.push(is_deleted);
-            query.push_str(&format!(" AND p.is_deleted=${}", cnt));
-        }
-        None => (),
+    if let Some(is_deleted) = &filters.is_deleted {
+        params.push(is_deleted);
+        query.push_str(&format!(" AND p.is_deleted=${}", cnt));
     }
     (query, params)
 }
@@ -125,8 +116,8 @@ fn add_permission_filters<'r, 'a>(
 impl ListingQueryBuilder for PermissionsFilters {
     fn build_listing_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
         let mut query = GET_BY_FILTERS_QUERY.to_string();
-        let (query, params) = add_permission_filters(&mut query, &self);
-        query.push_str(&format!(" ORDER BY permission_id DESC"));
+        let (query, params) = add_permission_filters(&mut query, self);
+        query.push_str(" ORDER BY permission_id DESC");
         query.push_str(&format!(" OFFSET {}", &self.offset));
         query.push_str(&format!(" LIMIT {}", &self.limit));
         (query.to_string(), params)
@@ -135,7 +126,7 @@ impl ListingQueryBuilder for PermissionsFilters {
 impl CountQueryBuilder for PermissionsFilters {
     fn build_count_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
         let mut query = GET_TOTAL_BY_FILTERS_QUERY.to_string();
-        let (query, params) = add_permission_filters(&mut query, &self);
+        let (query, params) = add_permission_filters(&mut query, self);
         (query.to_string(), params)
     }
 }
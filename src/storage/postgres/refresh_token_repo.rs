@@ -0,0 +1,108 @@
+use crate::storage::postgres::base::SqlSerializer;
+use crate::storage::postgres::base::{delete_item, get_item, insert_item, update_item};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::{RefreshToken, RefreshTokenForCreation};
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+
+use async_trait::async_trait;
+use chrono;
+use deadpool_postgres::Pool;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+pub struct RefreshTokenRepo {
+    db_pool: Pool,
+}
+
+impl RefreshTokenRepo {
+    pub fn new(db_pool: Pool) -> RefreshTokenRepo {
+        RefreshTokenRepo { db_pool }
+    }
+}
+
+const INSERT_REFRESH_TOKEN_QUERY: &str = "WITH t AS (
+        INSERT INTO refresh_tokens 
+        (token_hash, user_id, family_id, expired_at, created_at, updated_at, is_rotated, is_revoked)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8) 
+        RETURNING token_id, user_id, family_id, expired_at, is_rotated, is_revoked
+    )
+    SELECT token_id, user_id, family_id, expired_at, is_rotated, is_revoked, u.token_version
+    FROM t
+    JOIN users u USING(user_id)";
+const GET_REFRESH_TOKEN_BY_HASH_QUERY: &str = "SELECT 
+    token_id, user_id, family_id, expired_at, is_rotated, is_revoked, u.token_version 
+    FROM refresh_tokens 
+    JOIN users u USING(user_id)
+    WHERE token_hash=$1 AND u.enabled=TRUE AND u.is_deleted=FALSE";
+const ROTATE_REFRESH_TOKEN_QUERY: &str = "UPDATE refresh_tokens rt
+    SET is_rotated=TRUE, updated_at=$1 
+    FROM users u
+    WHERE rt.user_id=u.user_id AND token_id=$2 AND is_rotated=FALSE AND is_revoked=FALSE
+    RETURNING token_id, rt.user_id, family_id, expired_at, is_rotated, is_revoked, u.token_version";
+const REVOKE_REFRESH_TOKEN_FAMILY_QUERY: &str = "UPDATE refresh_tokens 
+    SET is_revoked=TRUE, updated_at=$1 
+    WHERE family_id=$2 AND is_revoked=FALSE";
+const REVOKE_USER_REFRESH_TOKENS_QUERY: &str = "UPDATE refresh_tokens 
+    SET is_revoked=TRUE, updated_at=$1 
+    WHERE user_id=$2 AND is_revoked=FALSE";
+
+impl SqlSerializer<RefreshToken> for RefreshToken {
+    fn from_sql_result(row: &Row) -> RefreshToken {
+        RefreshToken::new(
+            row.get(0),
+            row.get(1),
+            row.get(2),
+            row.get(3),
+            row.get(4),
+            row.get(5),
+            row.get(6),
+        )
+    }
+}
+
+#[async_trait]
+impl StoreRefreshToken for RefreshTokenRepo {
+    async fn save_refresh_token(
+        &self,
+        token: RefreshTokenForCreation,
+    ) -> Result<RefreshToken, AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[
+            &token.token_hash,
+            &token.user_id,
+            &token.family_id,
+            &token.expired_at,
+            &now,
+            &now,
+            &false,
+            &false,
+        ];
+        insert_item(&self.db_pool, INSERT_REFRESH_TOKEN_QUERY, params).await
+    }
+    async fn get_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<RefreshToken, AccessModelError> {
+        get_item(
+            &self.db_pool,
+            GET_REFRESH_TOKEN_BY_HASH_QUERY,
+            &[&token_hash],
+        )
+        .await
+    }
+    async fn rotate_refresh_token(&self, token_id: i32) -> Result<RefreshToken, AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[&now, &token_id];
+        update_item(&self.db_pool, ROTATE_REFRESH_TOKEN_QUERY, params).await
+    }
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[&now, &family_id];
+        delete_item(&self.db_pool, REVOKE_REFRESH_TOKEN_FAMILY_QUERY, params).await
+    }
+    async fn revoke_user_refresh_tokens(&self, user_id: i32) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[&now, &user_id];
+        delete_item(&self.db_pool, REVOKE_USER_REFRESH_TOKENS_QUERY, params).await
+    }
+}
@@ -0,0 +1,79 @@
+use crate::storage::postgres::base::{get_client, get_value, prepare_stmt};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::token_revoker::RevokeToken;
+
+use async_trait::async_trait;
+use chrono::{self, DateTime, Utc};
+use deadpool_postgres::Pool;
+use log::error;
+
+pub struct RevocationRepo {
+    db_pool: Pool,
+}
+
+impl RevocationRepo {
+    pub fn new(db_pool: Pool) -> RevocationRepo {
+        RevocationRepo { db_pool }
+    }
+}
+
+const IS_TOKEN_REVOKED_QUERY: &str = "SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti=$1)";
+const REVOKE_TOKEN_QUERY: &str = "INSERT INTO revoked_tokens 
+    (jti, user_id, expired_at, created_at)
+    VALUES ($1, $2, $3, $4)
+    ON CONFLICT DO NOTHING";
+const DELETE_EXPIRED_TOKENS_QUERY: &str = "DELETE FROM revoked_tokens WHERE expired_at<$1";
+const GET_TOKEN_VERSION_QUERY: &str = "SELECT token_version 
+    FROM users 
+    WHERE user_id=$1 AND enabled=TRUE AND is_deleted=FALSE";
+const INCREMENT_TOKEN_VERSION_QUERY: &str = "UPDATE users 
+    SET token_version=token_version + 1, updated_at=$1 
+    WHERE user_id=$2 AND is_deleted=FALSE
+    RETURNING token_version";
+
+#[async_trait]
+impl RevokeToken for RevocationRepo {
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, AccessModelError> {
+        get_value(&self.db_pool, IS_TOKEN_REVOKED_QUERY, &[&jti]).await
+    }
+    async fn revoke_token(
+        &self,
+        jti: &str,
+        user_id: i32,
+        expired_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        let client = get_client(&self.db_pool).await?;
+        // expired tokens are refused anyway, their jti needn't be kept
+        let stmt = prepare_stmt(&client, DELETE_EXPIRED_TOKENS_QUERY).await?;
+        if let Err(e) = client.execute(&stmt, &[&now]).await {
+            error!("{}", e);
+            return Err(AccessModelError::FatalError);
+        }
+        let stmt = prepare_stmt(&client, REVOKE_TOKEN_QUERY).await?;
+        match client
+            .execute(&stmt, &[&jti, &user_id, &expired_at, &now])
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("{}", e);
+                Err(AccessModelError::FatalError)
+            }
+        }
+    }
+    async fn get_token_version(&self, user_id: i32) -> Result<i32, AccessModelError> {
+        get_value(&self.db_pool, GET_TOKEN_VERSION_QUERY, &[&user_id]).await
+    }
+    async fn increment_token_version(&self, user_id: i32) -> Result<i32, AccessModelError> {
+        let now = chrono::Utc::now();
+        get_value(
+            &self.db_pool,
+            INCREMENT_TOKEN_VERSION_QUERY,
+            &[&now, &user_id],
+        )
+        .await
+    }
+    // the version is already stored by the repo which changed it
+    async fn token_version_changed(&self, _user_id: i32, _version: i32) {}
+}
@@ -1,13 +1,18 @@
 use crate::storage::postgres::base::{
     delete_item, get_item, insert_item, update_item, SqlSerializer,
 };
+use crate::storage::postgres::base::{
+    get_client, get_count, get_list, CountQueryBuilder, ListingQueryBuilder,
+};
 use crate::usecases::base_entities::AccessModelError;
 use crate::usecases::roles::entities::{
-    Role, RoleForCreation, RoleMemberBinding, RolePermissionBinding,
+    Role, RoleForCreation, RoleMemberBinding, RolePermissionBinding, RoleSummary, RolesFilters,
+    RolesList,
 };
 use crate::usecases::roles::role_creator::CreateRole;
 use crate::usecases::roles::role_disabler::DisableRole;
 use crate::usecases::roles::role_get_item::GetRole;
+use crate::usecases::roles::role_get_list::GetRolesList;
 use crate::usecases::roles::role_members_binder::RoleBindMember;
 use crate::usecases::roles::role_permissions_binder::RoleBindPermission;
 
@@ -36,12 +41,36 @@ const INSERT_ROLE_QUERY: &str = "INSERT INTO roles (role_name, created_at, updat
 const DISABLE_ROLE_BY_ID_QUERY: &str = "UPDATE roles 
     SET is_deleted=TRUE, updated_at=$1 
     WHERE role_id=$2 AND is_deleted=FALSE";
+const GET_BY_FILTERS_QUERY: &str =
+    "SELECT role_id, role_name, r.created_at, r.updated_at, r.is_deleted, NULL::bigint, NULL::bigint 
+    FROM roles r";
+const GET_WITH_COUNTS_BY_FILTERS_QUERY: &str =
+    "SELECT role_id, role_name, r.created_at, r.updated_at, r.is_deleted,
+        (SELECT count(1) FROM role_members rm JOIN users u USING(user_id)
+            WHERE rm.role_id=r.role_id AND rm.is_deleted=FALSE AND u.enabled=TRUE AND u.is_deleted=FALSE),
+        (SELECT count(1) FROM role_permissions rp JOIN permissions p USING(permission_id)
+            WHERE rp.role_id=r.role_id AND rp.is_deleted=FALSE AND p.is_deleted=FALSE)
+    FROM roles r";
+const GET_TOTAL_BY_FILTERS_QUERY: &str = "SELECT count(1) FROM roles r";
 
 impl SqlSerializer<Role> for Role {
     fn from_sql_result(row: &Row) -> Role {
         Role::new(row.get(0), row.get(1), row.get(2), row.get(3), row.get(4))
     }
 }
+impl SqlSerializer<RoleSummary> for RoleSummary {
+    fn from_sql_result(row: &Row) -> RoleSummary {
+        RoleSummary::new(
+            row.get(0),
+            row.get(1),
+            row.get(2),
+            row.get(3),
+            row.get(4),
+            row.get(5),
+            row.get(6),
+        )
+    }
+}
 #[async_trait]
 impl GetRole for RoleRepo {
     async fn get_role_by_id(&self, role_id: i32) -> Result<Role, AccessModelError> {
@@ -70,6 +99,80 @@ impl DisableRole for RoleRepo {
     }
 }
 
+fn add_role_filters<'r, 'a>(
+    query: &'r mut String,
+    filters: &'a RolesFilters,
+) -> (&'r mut String, Vec<&'a (dyn ToSql + Sync)>) {
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![];
+    let mut cnt: u8 = 1;
+    query.push_str(" WHERE TRUE");
+    if let Some(role_name) = &filters.role_name {
+        params.push(role_name);
+        query.push_str(&format!(
+            " AND strpos(lower(role_name), lower(${})) > 0",
+            cnt
+        ));
+        cnt += 1;
+    }
+    if let Some(user_id) = &filters.user_id {
+        params.push(user_id);
+        query.push_str(&format!(
+            " AND EXISTS(SELECT 1 FROM role_members rm 
+                WHERE rm.role_id=r.role_id AND rm.user_id=${} AND rm.is_deleted=FALSE)",
+            cnt
+        ));
+        cnt += 1;
+    }
+    if let Some(permission_id) = &filters.permission_id {
+        params.push(permission_id);
+        query.push_str(&format!(
+            " AND EXISTS(SELECT 1 FROM role_permissions rp 
+                WHERE rp.role_id=r.role_id AND rp.permission_id=${} AND rp.is_deleted=FALSE)",
+            cnt
+        ));
+        cnt += 1;
+    }
+    if let Some(is_deleted) = &filters.is_deleted {
+        params.push(is_deleted);
+        query.push_str(&format!(" AND r.is_deleted=${}", cnt));
+    }
+    (query, params)
+}
+
+impl ListingQueryBuilder for RolesFilters {
+    fn build_listing_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut query = match self.with_counts {
+            true => GET_WITH_COUNTS_BY_FILTERS_QUERY.to_string(),
+            false => GET_BY_FILTERS_QUERY.to_string(),
+        };
+        let (query, params) = add_role_filters(&mut query, self);
+        query.push_str(" ORDER BY role_id DESC");
+        query.push_str(&format!(" OFFSET {}", &self.offset));
+        query.push_str(&format!(" LIMIT {}", &self.limit));
+        (query.to_string(), params)
+    }
+}
+impl CountQueryBuilder for RolesFilters {
+    fn build_count_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut query = GET_TOTAL_BY_FILTERS_QUERY.to_string();
+        let (query, params) = add_role_filters(&mut query, self);
+        (query.to_string(), params)
+    }
+}
+
+#[async_trait]
+impl GetRolesList for RoleRepo {
+    async fn get_roles_by_filters(
+        &self,
+        filters: RolesFilters,
+    ) -> Result<RolesList, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        let roles = get_list(&client, filters.clone()).await?;
+        let total = get_count(&client, filters).await?;
+        Ok(RolesList::new(roles, total))
+    }
+}
+
 const GET_ROLE_PERMISSION_BINDING_BY_PK_QUERY: &str =
     "SELECT permission_id, role_id, created_at, updated_at, is_deleted 
     FROM role_permissions 
@@ -0,0 +1,73 @@
+use crate::storage::postgres::base::{get_client, get_item, prepare_stmt, SqlSerializer};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::services::entities::ServiceCredentials;
+use crate::usecases::services::service_authenticator::AuthenticateService;
+
+use async_trait::async_trait;
+use deadpool_postgres::Pool;
+use log::error;
+use tokio_postgres::Row;
+
+pub struct ServiceRepo {
+    db_pool: Pool,
+}
+
+impl ServiceRepo {
+    pub fn new(db_pool: Pool) -> ServiceRepo {
+        ServiceRepo { db_pool }
+    }
+}
+
+const GET_SERVICE_CREDENTIALS_QUERY: &str = "
+    SELECT service_id, client_id, secret_hash
+    FROM services
+    WHERE client_id=$1 AND enabled=TRUE AND is_deleted=FALSE";
+// identities go in order of preference, SANs before the common name
+const GET_SERVICE_BY_CERT_IDENTITIES_QUERY: &str = "
+    SELECT service_id, client_id, secret_hash
+    FROM services
+    WHERE cert_identity=ANY($1) AND enabled=TRUE AND is_deleted=FALSE
+    ORDER BY array_position($1, cert_identity)
+    LIMIT 1";
+const GET_SERVICE_SCOPES_QUERY: &str = "
+    SELECT scope
+    FROM service_scopes
+    WHERE service_id=$1 AND is_deleted=FALSE";
+
+impl SqlSerializer<ServiceCredentials> for ServiceCredentials {
+    fn from_sql_result(row: &Row) -> ServiceCredentials {
+        ServiceCredentials::new(row.get(0), row.get(1), row.get(2))
+    }
+}
+
+#[async_trait]
+impl AuthenticateService for ServiceRepo {
+    async fn get_service_credentials(
+        &self,
+        client_id: &str,
+    ) -> Result<ServiceCredentials, AccessModelError> {
+        get_item(&self.db_pool, GET_SERVICE_CREDENTIALS_QUERY, &[&client_id]).await
+    }
+    async fn get_service_by_cert_identities(
+        &self,
+        identities: &[String],
+    ) -> Result<ServiceCredentials, AccessModelError> {
+        get_item(
+            &self.db_pool,
+            GET_SERVICE_BY_CERT_IDENTITIES_QUERY,
+            &[&identities],
+        )
+        .await
+    }
+    async fn get_service_scopes(&self, service_id: i32) -> Result<Vec<String>, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        let stmt = prepare_stmt(&client, GET_SERVICE_SCOPES_QUERY).await?;
+        match client.query(&stmt, &[&service_id]).await {
+            Ok(rows) => Ok(rows.into_iter().map(|row| row.get(0)).collect()),
+            Err(e) => {
+                error!("{}", e);
+                Err(AccessModelError::FatalError)
+            }
+        }
+    }
+}
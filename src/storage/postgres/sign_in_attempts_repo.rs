@@ -0,0 +1,105 @@
+use crate::storage::postgres::base::{execute_query, get_item, SqlSerializer};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::{AttemptKey, FailedAttempts};
+use crate::usecases::users::sign_in_throttler::StoreSignInAttempts;
+
+use async_trait::async_trait;
+use chrono::{self, DateTime, Utc};
+use deadpool_postgres::Pool;
+use std::time::Duration;
+use tokio_postgres::Row;
+
+/// Attempts older than `window` are deleted when new failure is added
+pub struct SignInAttemptsRepo {
+    db_pool: Pool,
+    window: chrono::Duration,
+}
+
+impl SignInAttemptsRepo {
+    pub fn new(db_pool: Pool, window: Duration) -> SignInAttemptsRepo {
+        SignInAttemptsRepo {
+            db_pool,
+            window: chrono::Duration::from_std(window).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+}
+
+const GET_USERNAME_FAILURES_QUERY: &str = "SELECT count(*), max(attempted_at)
+    FROM failed_sign_in_attempts
+    WHERE username=$1 AND attempted_at>$2 AND cleared_at IS NULL";
+const GET_CLIENT_IP_FAILURES_QUERY: &str = "SELECT count(*), max(attempted_at)
+    FROM failed_sign_in_attempts
+    WHERE client_ip=$1 AND attempted_at>$2";
+const ADD_FAILURE_QUERY: &str = "INSERT INTO failed_sign_in_attempts
+    (username, client_ip, attempted_at)
+    VALUES ($1, $2, $3)";
+const DELETE_EXPIRED_FAILURES_QUERY: &str = "DELETE FROM failed_sign_in_attempts
+    WHERE attempted_at<$1";
+const CLEAR_FAILURES_QUERY: &str = "UPDATE failed_sign_in_attempts
+    SET cleared_at=$1
+    WHERE username=$2 AND cleared_at IS NULL";
+
+impl SqlSerializer<FailedAttempts> for FailedAttempts {
+    fn from_sql_result(row: &Row) -> FailedAttempts {
+        let count: i64 = row.get(0);
+        FailedAttempts {
+            count: count as u32,
+            last_attempted_at: row.get(1),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreSignInAttempts for SignInAttemptsRepo {
+    async fn get_failures(
+        &self,
+        key: &AttemptKey,
+        since: DateTime<Utc>,
+    ) -> Result<FailedAttempts, AccessModelError> {
+        match key {
+            AttemptKey::Username(username) => {
+                get_item(
+                    &self.db_pool,
+                    GET_USERNAME_FAILURES_QUERY,
+                    &[username, &since],
+                )
+                .await
+            }
+            AttemptKey::ClientIp(client_ip) => {
+                get_item(
+                    &self.db_pool,
+                    GET_CLIENT_IP_FAILURES_QUERY,
+                    &[client_ip, &since],
+                )
+                .await
+            }
+        }
+    }
+    async fn add_failure(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+        attempted_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError> {
+        // sign in is unauthenticated, the table must not grow without bound
+        let expired_before = attempted_at - self.window;
+        execute_query(
+            &self.db_pool,
+            DELETE_EXPIRED_FAILURES_QUERY,
+            &[&expired_before],
+        )
+        .await?;
+        execute_query(
+            &self.db_pool,
+            ADD_FAILURE_QUERY,
+            &[&username, &client_ip, &attempted_at],
+        )
+        .await?;
+        Ok(())
+    }
+    async fn clear_failures(&self, username: &str) -> Result<(), AccessModelError> {
+        let now = chrono::Utc::now();
+        execute_query(&self.db_pool, CLEAR_FAILURES_QUERY, &[&now, &username]).await?;
+        Ok(())
+    }
+}
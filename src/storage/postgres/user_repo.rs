@@ -1,10 +1,17 @@
-use crate::storage::postgres::base::{delete_item, get_item, insert_item, SqlSerializer};
-use crate::storage::postgres::base::{get_client, prepare_stmt};
+use crate::storage::postgres::base::{
+    delete_item, get_item, insert_item, update_item, SqlSerializer,
+};
+use crate::storage::postgres::base::{
+    get_client, get_count, get_list, prepare_stmt, CountQueryBuilder, ListingQueryBuilder,
+};
 use crate::usecases::base_entities::AccessModelError;
 use crate::usecases::users::crypto::SignInVerification;
-use crate::usecases::users::entities::{User, UserForCreation};
-use crate::usecases::users::get_user::{FindUserById, RemoveUserById};
+use crate::usecases::users::entities::{
+    User, UserCredentials, UserForCreation, UsersFilters, UsersList,
+};
+use crate::usecases::users::get_user::{FindUserById, GetUsers, RemoveUserById};
 use crate::usecases::users::user_creator::CreateUser;
+use crate::usecases::users::user_disabler::SetUserEnabled;
 use async_trait::async_trait;
 use chrono;
 use deadpool_postgres::Pool;
@@ -28,36 +35,55 @@ const GET_BY_ID_QUERY: &str = "SELECT user_id, username, enabled, created_at, up
 const DELETE_BY_ID_QUERY: &str =
     "UPDATE users SET is_deleted=TRUE, updated_at=$1 WHERE user_id=$2 AND is_deleted=FALSE";
 const INSERT_USER_QUERY: &str = "INSERT INTO users 
-    (username, password_hash, enabled, created_at, updated_at, is_deleted)
-    VALUES ($1, $2, $3, $4, $5, $6) 
+    (username, password_hash, email, enabled, created_at, updated_at, is_deleted)
+    VALUES ($1, $2, $3, $4, $5, $6, $7) 
     RETURNING user_id, username, enabled, created_at, updated_at";
-const FIND_USER_BY_VERIFICATION: &str = "
-    SELECT user_id 
+const GET_USER_CREDENTIALS_QUERY: &str = "
+    SELECT user_id, password_hash, token_version
     FROM users 
-    WHERE username=$1 AND password_hash=$2 AND is_deleted=FALSE";
+    WHERE username=$1 AND enabled=TRUE AND is_deleted=FALSE";
+const SET_USER_ENABLED_QUERY: &str = "UPDATE users 
+    SET enabled=$1, updated_at=$2 
+    WHERE user_id=$3 AND is_deleted=FALSE
+    RETURNING user_id, username, enabled, created_at, updated_at";
+const GET_BY_FILTERS_QUERY: &str = "SELECT user_id, username, enabled, u.created_at, u.updated_at 
+    FROM users u";
+const GET_TOTAL_BY_FILTERS_QUERY: &str = "SELECT count(1) FROM users u";
 const GET_USER_ROLES_QUERY: &str = "
     SELECT role_name
     FROM role_members rm
+    JOIN users u USING(user_id)
     LEFT JOIN roles r USING(role_id) 
-    WHERE user_id=$1 AND r.is_deleted=FALSE AND rm.is_deleted=FALSE";
+    WHERE user_id=$1 AND u.enabled=TRUE AND u.is_deleted=FALSE
+        AND r.is_deleted=FALSE AND rm.is_deleted=FALSE";
 const GET_USER_PERMS_QUERY: &str = "
     SELECT role_name
     FROM role_members rm
+    JOIN users u USING(user_id)
     LEFT JOIN roles r USING(role_id) 
-    WHERE user_id=$1 AND r.is_deleted=FALSE AND rm.is_deleted=FALSE
+    WHERE user_id=$1 AND u.enabled=TRUE AND u.is_deleted=FALSE
+        AND r.is_deleted=FALSE AND rm.is_deleted=FALSE
     UNION
     SELECT permission_name
     FROM role_members rm
+    JOIN users u USING(user_id)
     LEFT JOIN roles r USING(role_id)
     LEFT JOIN role_permissions rp USING(role_id)		
     LEFT JOIN permissions p USING(permission_id)
-    WHERE user_id=$1 AND r.is_deleted=FALSE AND rm.is_deleted=FALSE AND rp.is_deleted=FALSE AND p.is_deleted=FALSE";
+    WHERE user_id=$1 AND u.enabled=TRUE AND u.is_deleted=FALSE
+        AND r.is_deleted=FALSE AND rm.is_deleted=FALSE AND rp.is_deleted=FALSE AND p.is_deleted=FALSE";
 
 impl SqlSerializer<User> for User {
     fn from_sql_result(row: &Row) -> User {
         User::new(row.get(0), row.get(1), row.get(2), row.get(3), row.get(4))
     }
 }
+
+impl SqlSerializer<UserCredentials> for UserCredentials {
+    fn from_sql_result(row: &Row) -> UserCredentials {
+        UserCredentials::new(row.get(0), row.get(1), row.get(2))
+    }
+}
 #[async_trait]
 impl FindUserById for UserRepo {
     async fn find_user_by_id(&self, user_id: i32) -> Result<User, AccessModelError> {
@@ -80,6 +106,7 @@ impl CreateUser for UserRepo {
         let params: &[&(dyn ToSql + Sync)] = &[
             &user.username,
             &user.password_hash,
+            &user.email,
             &true,
             &now,
             &now,
@@ -89,24 +116,26 @@ impl CreateUser for UserRepo {
     }
 }
 
+#[async_trait]
+impl SetUserEnabled for UserRepo {
+    async fn set_user_enabled(
+        &self,
+        user_id: i32,
+        enabled: bool,
+    ) -> Result<User, AccessModelError> {
+        let now = chrono::Utc::now();
+        let params: &[&(dyn ToSql + Sync)] = &[&enabled, &now, &user_id];
+        update_item(&self.db_pool, SET_USER_ENABLED_QUERY, params).await
+    }
+}
+
 #[async_trait]
 impl SignInVerification for UserRepo {
-    async fn verificate_default(
+    async fn get_user_credentials(
         &self,
         username: &str,
-        hash: &str,
-    ) -> Result<i32, AccessModelError> {
-        let client = get_client(&self.db_pool).await?;
-        let stmt = prepare_stmt(&client, FIND_USER_BY_VERIFICATION).await?;
-
-        match client.query(&stmt, &[&username, &hash]).await {
-            Ok(rows) if rows.len() != 0 => Ok(rows[0].get(0)),
-            Ok(_) => Err(AccessModelError::NotFoundError),
-            Err(e) => {
-                error!("{}", e);
-                Err(AccessModelError::FatalError)
-            }
-        }
+    ) -> Result<UserCredentials, AccessModelError> {
+        get_item(&self.db_pool, GET_USER_CREDENTIALS_QUERY, &[&username]).await
     }
     async fn get_user_roles(&self, user_id: &i32) -> Result<Vec<String>, AccessModelError> {
         let client = get_client(&self.db_pool).await?;
@@ -131,3 +160,68 @@ impl SignInVerification for UserRepo {
         }
     }
 }
+
+fn add_user_filters<'r, 'a>(
+    query: &'r mut String,
+    filters: &'a UsersFilters,
+) -> (&'r mut String, Vec<&'a (dyn ToSql + Sync)>) {
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![];
+    let mut cnt: u8 = 1;
+    match &filters.role_id {
+        Some(role_id) => {
+            query.push_str(&format!(
+                " JOIN role_members rm USING(user_id) WHERE rm.role_id=${} AND rm.is_deleted=FALSE",
+                cnt
+            ));
+            cnt += 1;
+            params.push(role_id)
+        }
+        None => query.push_str(" WHERE TRUE"),
+    }
+    if let Some(username) = &filters.username {
+        params.push(username);
+        query.push_str(&format!(" AND starts_with(username, ${})", cnt));
+        cnt += 1;
+    }
+    if let Some(enabled) = &filters.enabled {
+        params.push(enabled);
+        query.push_str(&format!(" AND enabled=${}", cnt));
+        cnt += 1;
+    }
+    if let Some(is_deleted) = &filters.is_deleted {
+        params.push(is_deleted);
+        query.push_str(&format!(" AND u.is_deleted=${}", cnt));
+    }
+    (query, params)
+}
+
+impl ListingQueryBuilder for UsersFilters {
+    fn build_listing_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut query = GET_BY_FILTERS_QUERY.to_string();
+        let (query, params) = add_user_filters(&mut query, self);
+        query.push_str(" ORDER BY user_id DESC");
+        query.push_str(&format!(" OFFSET {}", &self.offset));
+        query.push_str(&format!(" LIMIT {}", &self.limit));
+        (query.to_string(), params)
+    }
+}
+impl CountQueryBuilder for UsersFilters {
+    fn build_count_query_with_params(&self) -> (String, Vec<&(dyn ToSql + Sync)>) {
+        let mut query = GET_TOTAL_BY_FILTERS_QUERY.to_string();
+        let (query, params) = add_user_filters(&mut query, self);
+        (query.to_string(), params)
+    }
+}
+
+#[async_trait]
+impl GetUsers for UserRepo {
+    async fn get_users_by_filters(
+        &self,
+        filters: UsersFilters,
+    ) -> Result<UsersList, AccessModelError> {
+        let client = get_client(&self.db_pool).await?;
+        let users = get_list(&client, filters.clone()).await?;
+        let total = get_count(&client, filters).await?;
+        Ok(UsersList::new(users, total))
+    }
+}
@@ -1,4 +1,8 @@
+pub mod api_keys;
 pub mod base_entities;
+pub mod federation;
+pub mod oauth;
 pub mod permission;
 pub mod roles;
+pub mod services;
 pub mod users;
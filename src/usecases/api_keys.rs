@@ -0,0 +1,3 @@
+pub mod api_key_manager;
+pub mod entities;
+pub mod errors;
@@ -0,0 +1,154 @@
+use crate::usecases::api_keys::entities::{
+    ApiKey, ApiKeyForCreation, ApiKeyOwner, ApiKeyRequest, CreatedApiKey, API_KEY_PREFIX,
+};
+use crate::usecases::api_keys::errors::ApiKeyError;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::{generate_random_token, hash_token, SignInVerification};
+use crate::usecases::users::entities::AuthenticatedUser;
+use crate::usecases::users::errors::SignError;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+
+const MAX_API_KEY_DAYS: u32 = 365;
+const MAX_API_KEY_NAME_LENGTH: usize = 64;
+
+#[async_trait]
+pub trait StoreApiKey {
+    /// AlreadyExists if the user has not revoked key with the same name
+    async fn save_api_key(&self, api_key: ApiKeyForCreation) -> Result<ApiKey, AccessModelError>;
+    /// Not revoked keys of the user, expired ones too
+    async fn get_user_api_keys(&self, user_id: i32) -> Result<Vec<ApiKey>, AccessModelError>;
+    /// NotFound if the key of the user is unknown or already revoked
+    async fn revoke_api_key(&self, user_id: i32, api_key_id: i32) -> Result<(), AccessModelError>;
+    /// Returns owner of not revoked and not expired key of enabled user,
+    /// last usage time of the key is updated
+    async fn use_api_key(&self, token_hash: &str) -> Result<ApiKeyOwner, AccessModelError>;
+}
+
+fn api_key_error(e: AccessModelError) -> ApiKeyError {
+    match e {
+        AccessModelError::NotFoundError => ApiKeyError::NotFoundError,
+        AccessModelError::AlreadyExists => ApiKeyError::AlreadyExists,
+        AccessModelError::TemporaryError => ApiKeyError::TemporaryError,
+        _ => ApiKeyError::FatalError,
+    }
+}
+
+/// Leaked key must not be enough to mint new keys or revoke other ones
+fn check_session_user(user: &AuthenticatedUser) -> Result<(), ApiKeyError> {
+    match user.api_key_id {
+        Some(_) => Err(ApiKeyError::ForbiddenError),
+        None => Ok(()),
+    }
+}
+
+fn validate_request(
+    user: &AuthenticatedUser,
+    request: &ApiKeyRequest,
+) -> Result<(String, Option<Vec<String>>), ApiKeyError> {
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_API_KEY_NAME_LENGTH {
+        return Err(ApiKeyError::ValidationError(format!(
+            "name must be 1..{} characters",
+            MAX_API_KEY_NAME_LENGTH
+        )));
+    }
+    if request.expires_in_days == 0 || request.expires_in_days > MAX_API_KEY_DAYS {
+        return Err(ApiKeyError::ValidationError(format!(
+            "expires_in_days must be 1..{}",
+            MAX_API_KEY_DAYS
+        )));
+    }
+    let permissions = match &request.permissions {
+        Some(permissions) => {
+            if permissions
+                .iter()
+                .any(|permission| !user.permissions.contains(permission))
+            {
+                return Err(ApiKeyError::ForbiddenError);
+            }
+            let mut permissions = permissions.clone();
+            permissions.sort();
+            permissions.dedup();
+            Some(permissions)
+        }
+        None => None,
+    };
+    Ok((name.to_string(), permissions))
+}
+
+/// Permissions of the key are checked against current permissions of the user
+pub async fn create_api_key(
+    api_key_storage: &impl StoreApiKey,
+    user: &AuthenticatedUser,
+    request: ApiKeyRequest,
+) -> Result<CreatedApiKey, ApiKeyError> {
+    check_session_user(user)?;
+    let (name, permissions) = validate_request(user, &request)?;
+    let token = format!("{}{}", API_KEY_PREFIX, generate_random_token(32));
+    let api_key = ApiKeyForCreation {
+        user_id: user.user_id,
+        name,
+        token_hash: hash_token(&token),
+        permissions,
+        expired_at: Utc::now() + Duration::days(i64::from(request.expires_in_days)),
+    };
+    match api_key_storage.save_api_key(api_key).await {
+        Ok(api_key) => Ok(CreatedApiKey { api_key, token }),
+        Err(e) => Err(api_key_error(e)),
+    }
+}
+
+pub async fn get_api_keys(
+    api_key_storage: &impl StoreApiKey,
+    user: &AuthenticatedUser,
+) -> Result<Vec<ApiKey>, ApiKeyError> {
+    check_session_user(user)?;
+    api_key_storage
+        .get_user_api_keys(user.user_id)
+        .await
+        .map_err(api_key_error)
+}
+
+pub async fn revoke_api_key(
+    api_key_storage: &impl StoreApiKey,
+    user: &AuthenticatedUser,
+    api_key_id: i32,
+) -> Result<(), ApiKeyError> {
+    check_session_user(user)?;
+    api_key_storage
+        .revoke_api_key(user.user_id, api_key_id)
+        .await
+        .map_err(api_key_error)
+}
+
+/// Permissions of the key are limited by actual permissions of its user
+pub async fn authenticate_by_api_key(
+    api_key_storage: &impl StoreApiKey,
+    verificator: &impl SignInVerification,
+    token: &str,
+) -> Result<AuthenticatedUser, SignError> {
+    let owner = match api_key_storage.use_api_key(&hash_token(token)).await {
+        Ok(owner) => owner,
+        Err(AccessModelError::NotFoundError) => return Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+        Err(_) => return Err(SignError::FatalError),
+    };
+    let user_permissions = match verificator.get_user_perms(&owner.user_id).await {
+        Ok(permissions) => permissions,
+        Err(_) => return Err(SignError::FatalError),
+    };
+    let permissions = match owner.permissions {
+        Some(allowed) => user_permissions
+            .into_iter()
+            .filter(|permission| allowed.contains(permission))
+            .collect(),
+        None => user_permissions,
+    };
+    Ok(AuthenticatedUser {
+        user_id: owner.user_id,
+        permissions,
+        api_key_id: Some(owner.api_key_id),
+    })
+}
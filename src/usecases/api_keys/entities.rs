@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Bearer tokens with the prefix are API keys, others are JWT
+pub const API_KEY_PREFIX: &str = "pat_";
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    pub expires_in_days: u32,
+    /// subset of user permissions, all permissions of the user if not set
+    pub permissions: Option<Vec<String>>,
+}
+
+pub struct ApiKeyForCreation {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub permissions: Option<Vec<String>>,
+    pub expired_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiKey {
+    pub api_key_id: i32,
+    pub name: String,
+    pub permissions: Option<Vec<String>>,
+    pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// The token is returned only once, only its hash is stored
+#[derive(Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub token: String,
+}
+
+pub struct ApiKeyOwner {
+    pub api_key_id: i32,
+    pub user_id: i32,
+    pub permissions: Option<Vec<String>>,
+}
@@ -0,0 +1,11 @@
+#[derive(Debug)]
+pub enum ApiKeyError {
+    FatalError,
+    TemporaryError,
+    NotFoundError,
+    /// user has not revoked key with the same name
+    AlreadyExists,
+    ValidationError(String),
+    /// keys are managed only with access tokens, or permission isn't granted to the user
+    ForbiddenError,
+}
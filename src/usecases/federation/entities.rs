@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+pub struct FederatedLoginForCreation {
+    pub state_hash: String,
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expired_at: DateTime<Utc>,
+}
+
+pub struct FederatedLogin {
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+/// Location of provider's authorization endpoint and state kept by user agent
+pub struct FederatedLoginRedirect {
+    pub location: String,
+    pub state: String,
+}
+
+/// Query of the callback, provider sends either code or error
+#[derive(Deserialize)]
+pub struct FederatedCallback {
+    pub state: Option<String>,
+    pub code: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Claims of upstream id_token, registered claims are checked on decoding
+#[derive(Deserialize)]
+pub struct UpstreamClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+pub struct FederatedUserForCreation {
+    pub username: String,
+    pub provider: String,
+    pub subject: String,
+}
@@ -0,0 +1,16 @@
+#[derive(Debug)]
+pub enum FederationError {
+    FatalError,
+    TemporaryError,
+    NotFoundError,
+    AlreadyExists,
+    /// unknown, used or expired state of the login
+    InvalidStateError,
+    /// provider rejected the code or returned malformed response
+    UpstreamError,
+    InvalidIdTokenError,
+    /// there is no local user for the identity and just-in-time creation is disabled
+    NotLinkedError,
+    /// local user is disabled or deleted
+    VerificationError,
+}
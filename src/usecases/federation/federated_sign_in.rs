@@ -0,0 +1,252 @@
+use crate::common::{FederationConfig, UpstreamProvider};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::federation::entities::{
+    FederatedCallback, FederatedLogin, FederatedLoginForCreation, FederatedLoginRedirect,
+    FederatedUserForCreation, UpstreamClaims,
+};
+use crate::usecases::federation::errors::FederationError;
+use crate::usecases::oauth::authorization_code::code_challenge_s256;
+use crate::usecases::oauth::entities::redirect_url;
+use crate::usecases::users::crypto::{generate_random_token, hash_token};
+use crate::usecases::users::ldap_sign_in::LDAP_PROVIDER;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+
+pub const FEDERATED_LOGIN_MINUTES: i64 = 10;
+// allowed clock skew between this service and upstream provider
+const UPSTREAM_LEEWAY_SECONDS: u64 = 60;
+
+#[async_trait]
+pub trait StoreFederatedLogin {
+    /// Also deletes used and expired logins
+    async fn save_federated_login(
+        &self,
+        login: FederatedLoginForCreation,
+    ) -> Result<(), AccessModelError>;
+    /// Marks the login as used, NotFound if it is unknown, used or expired
+    async fn use_federated_login(
+        &self,
+        state_hash: &str,
+    ) -> Result<FederatedLogin, AccessModelError>;
+}
+
+#[async_trait]
+pub trait LinkIdentity {
+    /// Returns linked user and updates last login time of the identity
+    async fn find_linked_user(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<i32, AccessModelError>;
+    /// NotFound if the user doesn't exist, AlreadyExists if the identity is linked
+    async fn link_identity(
+        &self,
+        user_id: i32,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), AccessModelError>;
+    /// Creates user without local password together with the identity,
+    /// AlreadyExists if the username is taken
+    async fn create_federated_user(
+        &self,
+        user: FederatedUserForCreation,
+    ) -> Result<i32, AccessModelError>;
+}
+
+#[async_trait]
+pub trait UpstreamIdp {
+    /// Exchanges authorization code, returns id_token of the token response
+    async fn exchange_code(
+        &self,
+        provider: &UpstreamProvider,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, FederationError>;
+    async fn get_jwks(&self, provider: &UpstreamProvider) -> Result<JwkSet, FederationError>;
+}
+
+fn federation_error(e: AccessModelError) -> FederationError {
+    match e {
+        AccessModelError::NotFoundError => FederationError::NotFoundError,
+        AccessModelError::AlreadyExists => FederationError::AlreadyExists,
+        AccessModelError::TemporaryError => FederationError::TemporaryError,
+        _ => FederationError::FatalError,
+    }
+}
+
+pub fn find_provider<'a>(
+    config: &'a FederationConfig,
+    name: &str,
+) -> Result<&'a UpstreamProvider, FederationError> {
+    config
+        .providers
+        .get(name)
+        .ok_or(FederationError::NotFoundError)
+}
+
+/// Saves state of the login and returns URL of provider's authorization endpoint
+/// together with the state kept by user agent, PKCE and nonce are used with every provider
+pub async fn start_federated_login(
+    login_storage: &impl StoreFederatedLogin,
+    provider: &UpstreamProvider,
+) -> Result<FederatedLoginRedirect, FederationError> {
+    let state = generate_random_token(16);
+    let nonce = generate_random_token(16);
+    let code_verifier = generate_random_token(32);
+    let code_challenge = code_challenge_s256(&code_verifier);
+    let login = FederatedLoginForCreation {
+        state_hash: hash_token(&state),
+        provider: provider.name.clone(),
+        nonce: nonce.clone(),
+        code_verifier,
+        expired_at: Utc::now() + Duration::minutes(FEDERATED_LOGIN_MINUTES),
+    };
+    login_storage
+        .save_federated_login(login)
+        .await
+        .map_err(federation_error)?;
+    let location = redirect_url(
+        &provider.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", &provider.client_id),
+            ("redirect_uri", &provider.redirect_uri),
+            ("scope", &provider.scope),
+            ("state", &state),
+            ("nonce", &nonce),
+            ("code_challenge", &code_challenge),
+            ("code_challenge_method", "S256"),
+        ],
+    );
+    Ok(FederatedLoginRedirect { location, state })
+}
+
+/// Signature is checked with keys of the provider, HMAC algorithms are not accepted
+pub fn validate_upstream_id_token(
+    provider: &UpstreamProvider,
+    jwks: &JwkSet,
+    id_token: &str,
+    nonce: &str,
+) -> Result<UpstreamClaims, FederationError> {
+    let header = decode_header(id_token).map_err(|_| FederationError::InvalidIdTokenError)?;
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(FederationError::InvalidIdTokenError);
+    }
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(FederationError::InvalidIdTokenError)?;
+    let key = DecodingKey::from_jwk(jwk).map_err(|_| FederationError::InvalidIdTokenError)?;
+    let mut validation = Validation::new(header.alg);
+    validation.leeway = UPSTREAM_LEEWAY_SECONDS;
+    validation.set_issuer(&[&provider.issuer]);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+    let claims: UpstreamClaims = match decode(id_token, &key, &validation) {
+        Ok(token_data) => token_data.claims,
+        Err(_) => return Err(FederationError::InvalidIdTokenError),
+    };
+    match claims.nonce.as_deref() == Some(nonce) {
+        true => Ok(claims),
+        false => Err(FederationError::InvalidIdTokenError),
+    }
+}
+
+/// Unknown identity gets new local user only if the provider allows it,
+/// existing local users are never linked by username
+async fn find_or_create_user(
+    identity_repo: &impl LinkIdentity,
+    provider: &UpstreamProvider,
+    claims: &UpstreamClaims,
+) -> Result<i32, FederationError> {
+    match identity_repo
+        .find_linked_user(&provider.name, &claims.sub)
+        .await
+    {
+        Ok(user_id) => return Ok(user_id),
+        Err(AccessModelError::NotFoundError) if provider.create_users => (),
+        Err(AccessModelError::NotFoundError) => return Err(FederationError::NotLinkedError),
+        Err(e) => return Err(federation_error(e)),
+    };
+    let username = claims
+        .other
+        .get(&provider.username_claim)
+        .and_then(Value::as_str)
+        .filter(|username| !username.is_empty())
+        .ok_or(FederationError::InvalidIdTokenError)?;
+    let user = FederatedUserForCreation {
+        username: username.to_string(),
+        provider: provider.name.clone(),
+        subject: claims.sub.clone(),
+    };
+    identity_repo
+        .create_federated_user(user)
+        .await
+        .map_err(federation_error)
+}
+
+/// Finishes login on callback: state must be the one kept by user agent and is used once,
+/// code is exchanged with PKCE verifier and id_token is checked, returns local user of the upstream identity
+pub async fn authenticate_federated_user(
+    federation_storage: &(impl StoreFederatedLogin + LinkIdentity),
+    upstream: &impl UpstreamIdp,
+    provider: &UpstreamProvider,
+    callback: FederatedCallback,
+    agent_state: Option<&str>,
+) -> Result<i32, FederationError> {
+    let state = callback.state.ok_or(FederationError::InvalidStateError)?;
+    // login started in another user agent isn't finished here, it prevents login CSRF
+    if agent_state != Some(state.as_str()) {
+        return Err(FederationError::InvalidStateError);
+    }
+    let login = match federation_storage
+        .use_federated_login(&hash_token(&state))
+        .await
+    {
+        Ok(login) => login,
+        Err(AccessModelError::NotFoundError) => return Err(FederationError::InvalidStateError),
+        Err(e) => return Err(federation_error(e)),
+    };
+    if login.provider != provider.name {
+        return Err(FederationError::InvalidStateError);
+    }
+    // the state is used even if the provider returned error, login is started again
+    let code = match (callback.code, callback.error) {
+        (Some(code), None) => code,
+        _ => return Err(FederationError::UpstreamError),
+    };
+    let id_token = upstream
+        .exchange_code(provider, &code, &login.code_verifier)
+        .await?;
+    let jwks = upstream.get_jwks(provider).await?;
+    let claims = validate_upstream_id_token(provider, &jwks, &id_token, &login.nonce)?;
+    find_or_create_user(federation_storage, provider, &claims).await
+}
+
+/// Links existing local user with identity of configured provider,
+/// `ldap` provider links LDAP entry by its DN
+pub async fn link_user_identity(
+    identity_repo: &impl LinkIdentity,
+    config: &FederationConfig,
+    user_id: i32,
+    provider: &str,
+    subject: &str,
+) -> Result<(), FederationError> {
+    let provider = match provider {
+        LDAP_PROVIDER => LDAP_PROVIDER,
+        provider => &find_provider(config, provider)?.name,
+    };
+    identity_repo
+        .link_identity(user_id, provider, subject)
+        .await
+        .map_err(federation_error)
+}
@@ -0,0 +1,6 @@
+pub mod authorization_code;
+pub mod client_credentials;
+pub mod client_registry;
+pub mod entities;
+pub mod errors;
+pub mod oidc;
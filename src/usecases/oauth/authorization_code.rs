@@ -0,0 +1,276 @@
+use crate::common::{SecurityConfig, SignInThrottlingConfig};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::client_credentials::{
+    authenticate_client, grant_scopes, AuthenticateClient,
+};
+use crate::usecases::oauth::entities::{
+    AccessTokenInfo, AuthorizationCode, AuthorizationCodeForCreation, AuthorizationRequest,
+    CodeExchange, OAuthClient, ResourceOwnerLogin, ValidAuthorizationRequest,
+    AUTHORIZATION_CODE_GRANT, OIDC_SCOPES,
+};
+use crate::usecases::oauth::errors::{AuthorizationError, OAuthError};
+use crate::usecases::oauth::oidc::issue_id_token;
+use crate::usecases::users::crypto::{
+    generate_random_token, hash_token, issue_tokens, SignInVerification,
+};
+use crate::usecases::users::entities::TokenSession;
+use crate::usecases::users::errors::SignError;
+use crate::usecases::users::get_user::FindUserById;
+use crate::usecases::users::mfa::StoreMfa;
+use crate::usecases::users::sign_in_throttler::{
+    verify_login_with_throttling, StoreSignInAttempts,
+};
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use log::warn;
+use sha2::{Digest, Sha256};
+
+const AUTHORIZATION_CODE_SECONDS: i64 = 60;
+const CODE_CHALLENGE_METHOD: &str = "S256";
+
+#[async_trait]
+pub trait StoreAuthorizationCode {
+    async fn save_authorization_code(
+        &self,
+        code: AuthorizationCodeForCreation,
+    ) -> Result<(), AccessModelError>;
+    /// Marks the code as used, `was_used` is true for repeated exchange
+    async fn use_authorization_code(
+        &self,
+        code_hash: &str,
+    ) -> Result<AuthorizationCode, AccessModelError>;
+}
+
+/// Code verifier and challenge are 43-128 unreserved characters, RFC 7636 section 4.1
+fn is_valid_pkce_value(value: &str) -> bool {
+    (43..=128).contains(&value.len())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ['-', '.', '_', '~'].contains(&c))
+}
+
+pub fn code_challenge_s256(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// OpenID Connect scopes are granted along with scopes of the client,
+/// requesting only them doesn't grant all scopes of the client
+fn grant_authorization_scopes(
+    client: &OAuthClient,
+    requested: Option<&str>,
+) -> Result<String, OAuthError> {
+    let (oidc_scopes, client_scopes): (Vec<&str>, Vec<&str>) = requested
+        .unwrap_or_default()
+        .split_whitespace()
+        .partition(|scope| OIDC_SCOPES.contains(scope));
+    let mut granted: Vec<String> = oidc_scopes.iter().map(|scope| scope.to_string()).collect();
+    if !client_scopes.is_empty() || oidc_scopes.is_empty() {
+        let scopes = grant_scopes(client, Some(&client_scopes.join(" ")))?;
+        if !scopes.is_empty() {
+            granted.push(scopes);
+        }
+    }
+    Ok(granted.join(" "))
+}
+
+/// Errors are sent to the client only after redirect URI is matched with registered ones
+pub async fn validate_authorization_request(
+    client_access_model: &impl AuthenticateClient,
+    request: &AuthorizationRequest,
+) -> Result<ValidAuthorizationRequest, AuthorizationError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .ok_or(AuthorizationError::ClientError(
+            OAuthError::InvalidRequestError,
+        ))?;
+    let client = match client_access_model.get_client(client_id).await {
+        Ok(client) => client,
+        Err(AccessModelError::NotFoundError) => {
+            return Err(AuthorizationError::ClientError(
+                OAuthError::InvalidClientError,
+            ))
+        }
+        Err(AccessModelError::TemporaryError) => {
+            return Err(AuthorizationError::ClientError(OAuthError::TemporaryError))
+        }
+        Err(_) => return Err(AuthorizationError::ClientError(OAuthError::FatalError)),
+    };
+    // redirect URI can be omitted only if the client has just one
+    let redirect_uri = match (&request.redirect_uri, client.redirect_uris.as_slice()) {
+        (Some(uri), registered) if registered.contains(uri) => uri.clone(),
+        (None, [uri]) => uri.clone(),
+        _ => {
+            return Err(AuthorizationError::ClientError(
+                OAuthError::InvalidRedirectUriError,
+            ))
+        }
+    };
+    let redirect_error = |error: OAuthError| AuthorizationError::RedirectError {
+        redirect_uri: redirect_uri.clone(),
+        state: request.state.clone(),
+        error,
+    };
+    if !client.has_grant_type(AUTHORIZATION_CODE_GRANT) {
+        return Err(redirect_error(OAuthError::UnauthorizedClientError));
+    }
+    if request.response_type.as_deref() != Some("code") {
+        return Err(redirect_error(OAuthError::UnsupportedResponseTypeError));
+    }
+    // PKCE is required for all clients, plain method is not supported
+    let code_challenge = match (&request.code_challenge, &request.code_challenge_method) {
+        (Some(challenge), Some(method))
+            if method == CODE_CHALLENGE_METHOD && is_valid_pkce_value(challenge) =>
+        {
+            challenge.clone()
+        }
+        _ => return Err(redirect_error(OAuthError::InvalidRequestError)),
+    };
+    let scope =
+        grant_authorization_scopes(&client, request.scope.as_deref()).map_err(redirect_error)?;
+    Ok(ValidAuthorizationRequest {
+        oauth_client_id: client.oauth_client_id,
+        client_id: client.client_id,
+        redirect_uri,
+        scope,
+        state: request.state.clone(),
+        code_challenge,
+        nonce: request.nonce.clone(),
+    })
+}
+
+/// Checks credentials entered on the login page and issues single-use authorization code
+pub async fn authorize(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    throttling_config: &SignInThrottlingConfig,
+    mfa_store: &impl StoreMfa,
+    verificator: &impl SignInVerification,
+    code_storage: &impl StoreAuthorizationCode,
+    request: &ValidAuthorizationRequest,
+    login: ResourceOwnerLogin,
+) -> Result<String, SignError> {
+    let credentials = verify_login_with_throttling(
+        attempts_store,
+        throttling_config,
+        mfa_store,
+        verificator,
+        &login.attempt,
+        login.totp_code.as_deref(),
+    )
+    .await?;
+    let code = generate_random_token(32);
+    let code_data = AuthorizationCodeForCreation {
+        code_hash: hash_token(&code),
+        oauth_client_id: request.oauth_client_id,
+        user_id: credentials.user_id,
+        redirect_uri: request.redirect_uri.clone(),
+        scope: request.scope.clone(),
+        code_challenge: request.code_challenge.clone(),
+        session_id: generate_random_token(16),
+        token_version: credentials.token_version,
+        expired_at: Utc::now() + Duration::seconds(AUTHORIZATION_CODE_SECONDS),
+        nonce: request.nonce.clone(),
+        auth_time: Utc::now(),
+    };
+    match code_storage.save_authorization_code(code_data).await {
+        Ok(_) => Ok(code),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+fn sign_error_to_oauth(e: SignError) -> OAuthError {
+    match e {
+        SignError::VerificationError | SignError::ExpiredTokenError => {
+            OAuthError::InvalidGrantError
+        }
+        SignError::TemporaryError => OAuthError::TemporaryError,
+        _ => OAuthError::FatalError,
+    }
+}
+
+/// Authorization code grant, RFC 6749 section 4.1.3 with PKCE verification,
+/// id_token is added for `openid` scope
+pub async fn exchange_authorization_code(
+    client_access_model: &impl AuthenticateClient,
+    code_storage: &impl StoreAuthorizationCode,
+    user_repo: &impl FindUserById,
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    exchange: CodeExchange,
+) -> Result<AccessTokenInfo, OAuthError> {
+    let client = authenticate_client(
+        client_access_model,
+        &exchange.client_id,
+        exchange.client_secret.as_deref(),
+    )
+    .await?;
+    if !client.has_grant_type(AUTHORIZATION_CODE_GRANT) {
+        return Err(OAuthError::UnauthorizedClientError);
+    }
+    let code = match code_storage
+        .use_authorization_code(&hash_token(&exchange.code))
+        .await
+    {
+        Ok(code) => code,
+        Err(AccessModelError::NotFoundError) => return Err(OAuthError::InvalidGrantError),
+        Err(AccessModelError::TemporaryError) => return Err(OAuthError::TemporaryError),
+        Err(_) => return Err(OAuthError::FatalError),
+    };
+    if code.oauth_client_id != client.oauth_client_id {
+        return Err(OAuthError::InvalidGrantError);
+    }
+    if code.was_used {
+        // the code is leaked, tokens issued for it are revoked, RFC 6749 section 4.1.2
+        warn!(
+            "Reuse of authorization code detected, revoke session of user {}",
+            code.user_id
+        );
+        return match token_storage
+            .revoke_refresh_token_family(&code.session_id)
+            .await
+        {
+            Ok(_) | Err(AccessModelError::NotFoundError) => Err(OAuthError::InvalidGrantError),
+            Err(AccessModelError::TemporaryError) => Err(OAuthError::TemporaryError),
+            Err(_) => Err(OAuthError::FatalError),
+        };
+    }
+    let code_verifier = exchange
+        .code_verifier
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequestError)?;
+    if code.expired_at <= Utc::now()
+        || exchange.redirect_uri.as_deref() != Some(code.redirect_uri.as_str())
+        || !is_valid_pkce_value(code_verifier)
+        || code_challenge_s256(code_verifier) != code.code_challenge
+    {
+        return Err(OAuthError::InvalidGrantError);
+    }
+    let id_token = issue_id_token(user_repo, security_config, &client.client_id, &code).await?;
+    let session = TokenSession {
+        session_id: code.session_id,
+        token_version: code.token_version,
+    };
+    let signed_info = issue_tokens(
+        verificator,
+        token_storage,
+        security_config,
+        code.user_id,
+        session,
+    )
+    .await
+    .map_err(sign_error_to_oauth)?;
+    Ok(AccessTokenInfo {
+        access_token: signed_info.jwt_token,
+        token_type: "Bearer".to_string(),
+        expires_in: i64::from(security_config.expired_access_token_minutes) * 60,
+        scope: code.scope,
+        refresh_token: Some(signed_info.refresh_token),
+        id_token,
+    })
+}
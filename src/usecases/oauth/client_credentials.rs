@@ -0,0 +1,99 @@
+use crate::common::SecurityConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::entities::{
+    AccessTokenInfo, ClientClaims, OAuthClient, CLIENT_CREDENTIALS_GRANT,
+};
+use crate::usecases::oauth::errors::OAuthError;
+use crate::usecases::users::crypto::{encode_jwt, generate_random_token, hash_token};
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait AuthenticateClient {
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, AccessModelError>;
+    /// Names of bound roles and of their permissions
+    async fn get_client_perms(&self, oauth_client_id: i32)
+        -> Result<Vec<String>, AccessModelError>;
+}
+
+/// Returns enabled client, client secret is stored as sha256 hash,
+/// public clients are identified by client id only
+pub async fn authenticate_client(
+    client_access_model: &impl AuthenticateClient,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, OAuthError> {
+    let client = match client_access_model.get_client(client_id).await {
+        Ok(client) => client,
+        Err(AccessModelError::NotFoundError) => return Err(OAuthError::InvalidClientError),
+        Err(AccessModelError::TemporaryError) => return Err(OAuthError::TemporaryError),
+        Err(_) => return Err(OAuthError::FatalError),
+    };
+    match (&client.secret_hash, client_secret) {
+        (None, _) => Ok(client),
+        (Some(secret_hash), Some(client_secret)) if *secret_hash == hash_token(client_secret) => {
+            Ok(client)
+        }
+        _ => Err(OAuthError::InvalidClientError),
+    }
+}
+
+/// Requested scopes (space separated) must be allowed for the client, all allowed by default
+pub fn grant_scopes(client: &OAuthClient, requested: Option<&str>) -> Result<String, OAuthError> {
+    let requested: Vec<&str> = match requested {
+        Some(requested) => requested.split_whitespace().collect(),
+        None => vec![],
+    };
+    if requested.is_empty() {
+        return Ok(client.scopes.join(" "));
+    }
+    match requested
+        .iter()
+        .all(|scope| client.scopes.iter().any(|allowed| allowed == scope))
+    {
+        true => Ok(requested.join(" ")),
+        false => Err(OAuthError::InvalidScopeError),
+    }
+}
+
+/// Client credentials grant, RFC 6749 section 4.4, refresh token is not issued
+pub async fn issue_client_credentials_token(
+    client_access_model: &impl AuthenticateClient,
+    security_config: &SecurityConfig,
+    client_id: &str,
+    client_secret: Option<&str>,
+    scope: Option<&str>,
+) -> Result<AccessTokenInfo, OAuthError> {
+    let client = authenticate_client(client_access_model, client_id, client_secret).await?;
+    if !client.has_grant_type(CLIENT_CREDENTIALS_GRANT) {
+        return Err(OAuthError::UnauthorizedClientError);
+    }
+    let scope = grant_scopes(&client, scope)?;
+    let permissions = match client_access_model
+        .get_client_perms(client.oauth_client_id)
+        .await
+    {
+        Ok(permissions) => permissions,
+        Err(AccessModelError::TemporaryError) => return Err(OAuthError::TemporaryError),
+        Err(_) => return Err(OAuthError::FatalError),
+    };
+    let expired_in = chrono::Duration::minutes(security_config.expired_access_token_minutes.into());
+    let claims = ClientClaims::new(
+        client.client_id,
+        permissions,
+        scope.clone(),
+        security_config.jwt_issuer.clone(),
+        security_config.jwt_audience.clone(),
+        expired_in,
+        generate_random_token(16),
+    );
+    let access_token = encode_jwt(security_config, &claims).map_err(|_| OAuthError::FatalError)?;
+    Ok(AccessTokenInfo {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: expired_in.num_seconds(),
+        scope,
+        refresh_token: None,
+        id_token: None,
+    })
+}
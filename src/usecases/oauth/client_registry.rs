@@ -0,0 +1,133 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::entities::{
+    OAuthClient, OAuthClientForCreation, OAuthClientRegistration, RegisteredOAuthClient,
+    AUTHORIZATION_CODE_GRANT, CLIENT_CREDENTIALS_GRANT, SUPPORTED_GRANT_TYPES,
+};
+use crate::usecases::oauth::errors::OAuthError;
+use crate::usecases::users::crypto::{generate_random_token, hash_token};
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait RegisterClient {
+    async fn save_client(
+        &self,
+        client_data: OAuthClientForCreation,
+    ) -> Result<OAuthClient, AccessModelError>;
+    /// NotFound if the client or the role doesn't exist, repeated binding is not an error
+    async fn bind_client_role(&self, client_id: &str, role_id: i32)
+        -> Result<(), AccessModelError>;
+}
+
+/// Absolute URI without fragment. Plain http is allowed only for loopback,
+/// other schemes only as reverse domain private-use schemes of mobile apps (RFC 8252 7.1),
+/// so `javascript:`, `data:` or `file:` are refused
+pub fn is_valid_redirect_uri(uri: &str) -> bool {
+    let (scheme, rest) = match uri.split_once(':') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if !valid_scheme || rest.is_empty() || uri.contains('#') || uri.contains(char::is_whitespace) {
+        return false;
+    }
+    match scheme.to_ascii_lowercase().as_str() {
+        "https" => rest.starts_with("//") && rest.len() > 2,
+        "http" => ["//localhost", "//127.0.0.1", "//[::1]"]
+            .iter()
+            .any(|host| {
+                rest.strip_prefix(host)
+                    .is_some_and(|tail| tail.is_empty() || tail.starts_with([':', '/', '?']))
+            }),
+        scheme => scheme.contains('.'),
+    }
+}
+
+fn validate_registration(registration: &OAuthClientRegistration) -> Result<(), OAuthError> {
+    let grant_types = &registration.grant_types;
+    if grant_types.is_empty()
+        || !grant_types
+            .iter()
+            .all(|grant_type| SUPPORTED_GRANT_TYPES.contains(&grant_type.as_str()))
+    {
+        return Err(OAuthError::UnsupportedGrantTypeError);
+    }
+    // client credentials grant is only for confidential clients, RFC 6749 section 4.4
+    if registration.is_public
+        && grant_types
+            .iter()
+            .any(|grant_type| grant_type == CLIENT_CREDENTIALS_GRANT)
+    {
+        return Err(OAuthError::UnauthorizedClientError);
+    }
+    if registration
+        .scopes
+        .iter()
+        .any(|scope| scope.is_empty() || scope.contains(char::is_whitespace))
+    {
+        return Err(OAuthError::InvalidScopeError);
+    }
+    let needs_redirect_uri = grant_types
+        .iter()
+        .any(|grant_type| grant_type == AUTHORIZATION_CODE_GRANT);
+    if (needs_redirect_uri && registration.redirect_uris.is_empty())
+        || !registration
+            .redirect_uris
+            .iter()
+            .all(|uri| is_valid_redirect_uri(uri))
+    {
+        return Err(OAuthError::InvalidRedirectUriError);
+    }
+    Ok(())
+}
+
+/// Creates client, confidential clients get generated secret, only its sha256 hash is stored
+pub async fn register_client(
+    client_access_model: &impl RegisterClient,
+    registration: OAuthClientRegistration,
+) -> Result<RegisteredOAuthClient, OAuthError> {
+    validate_registration(&registration)?;
+    let client_secret = match registration.is_public {
+        true => None,
+        false => Some(generate_random_token(32)),
+    };
+    let client_data = OAuthClientForCreation {
+        client_id: registration.client_id,
+        secret_hash: client_secret.as_deref().map(hash_token),
+        scopes: registration.scopes,
+        grant_types: registration.grant_types,
+        redirect_uris: registration.redirect_uris,
+    };
+    match client_access_model.save_client(client_data).await {
+        Ok(client) => Ok(RegisteredOAuthClient {
+            client_id: client.client_id,
+            client_secret,
+            scopes: client.scopes,
+            grant_types: client.grant_types,
+            redirect_uris: client.redirect_uris,
+        }),
+        Err(AccessModelError::AlreadyExists) => Err(OAuthError::AlreadyExists),
+        Err(AccessModelError::TemporaryError) => Err(OAuthError::TemporaryError),
+        Err(_) => Err(OAuthError::FatalError),
+    }
+}
+
+/// Permissions of the role and the role itself are granted to client tokens
+pub async fn bind_role_to_client(
+    client_access_model: &impl RegisterClient,
+    client_id: &str,
+    role_id: i32,
+) -> Result<(), OAuthError> {
+    match client_access_model
+        .bind_client_role(client_id, role_id)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(AccessModelError::NotFoundError) => Err(OAuthError::NotFoundError),
+        Err(AccessModelError::TemporaryError) => Err(OAuthError::TemporaryError),
+        Err(_) => Err(OAuthError::FatalError),
+    }
+}
@@ -0,0 +1,227 @@
+use crate::usecases::users::entities::SignInAttempt;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::Algorithm;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+pub const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+pub const SUPPORTED_GRANT_TYPES: &[&str] = &[CLIENT_CREDENTIALS_GRANT, AUTHORIZATION_CODE_GRANT];
+pub const OPENID_SCOPE: &str = "openid";
+pub const PROFILE_SCOPE: &str = "profile";
+/// OpenID Connect scopes are allowed for every client with authorization_code grant
+pub const OIDC_SCOPES: &[&str] = &[OPENID_SCOPE, PROFILE_SCOPE];
+
+pub struct OAuthClient {
+    pub oauth_client_id: i32,
+    pub client_id: String,
+    /// None for public clients
+    pub secret_hash: Option<String>,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub redirect_uris: Vec<String>,
+}
+
+impl OAuthClient {
+    pub fn has_grant_type(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|allowed| allowed == grant_type)
+    }
+}
+
+pub struct OAuthClientRegistration {
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub redirect_uris: Vec<String>,
+    pub is_public: bool,
+}
+
+pub struct OAuthClientForCreation {
+    pub client_id: String,
+    pub secret_hash: Option<String>,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub redirect_uris: Vec<String>,
+}
+
+/// Client secret is shown only once, on registration
+#[derive(Serialize)]
+pub struct RegisteredOAuthClient {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub redirect_uris: Vec<String>,
+}
+
+/// Access token of OAuth client itself, there is no user behind it
+#[derive(Serialize, Deserialize)]
+pub struct ClientClaims {
+    pub client_id: String,
+    pub permissions: Vec<String>,
+    pub scope: String,
+    // registered claims, RFC 7519
+    pub sub: String,
+    pub iss: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub nbf: i64,
+    pub jti: String,
+}
+
+impl ClientClaims {
+    pub fn new(
+        client_id: String,
+        permissions: Vec<String>,
+        scope: String,
+        issuer: String,
+        audience: String,
+        expired_in: chrono::Duration,
+        jti: String,
+    ) -> ClientClaims {
+        let now = chrono::Utc::now();
+        ClientClaims {
+            sub: client_id.clone(),
+            client_id,
+            permissions,
+            scope,
+            iss: issuer,
+            aud: audience,
+            exp: (now + expired_in).timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti,
+        }
+    }
+}
+
+/// Successful token response, RFC 6749 section 5.1
+#[derive(Serialize, Deserialize)]
+pub struct AccessTokenInfo {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// issued for `openid` scope only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+/// Query of `authorize` endpoint, RFC 6749 section 4.1.1, RFC 7636 and OpenID Connect nonce
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+/// Request checked against the client registration, redirect URI is trusted
+pub struct ValidAuthorizationRequest {
+    pub oauth_client_id: i32,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+}
+
+pub struct AuthorizationCode {
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub session_id: String,
+    pub token_version: i32,
+    pub expired_at: DateTime<Utc>,
+    pub nonce: Option<String>,
+    /// time of the login which issued the code
+    pub auth_time: DateTime<Utc>,
+    /// the code was already exchanged before this request
+    pub was_used: bool,
+}
+
+pub struct AuthorizationCodeForCreation {
+    pub code_hash: String,
+    pub oauth_client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub session_id: String,
+    pub token_version: i32,
+    pub expired_at: DateTime<Utc>,
+    pub nonce: Option<String>,
+    pub auth_time: DateTime<Utc>,
+}
+
+/// Credentials entered on the login page
+pub struct ResourceOwnerLogin {
+    pub attempt: SignInAttempt,
+    pub totp_code: Option<String>,
+}
+
+/// Parameters of the token request for authorization_code grant
+pub struct CodeExchange {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub code: String,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+/// Standard claims of the user, OpenID Connect Core section 5.3.2
+#[derive(Serialize, Deserialize)]
+pub struct UserInfo {
+    pub sub: String,
+    pub preferred_username: String,
+    pub updated_at: i64,
+}
+
+/// Provider metadata, OpenID Connect Discovery section 3
+#[derive(Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub scopes_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+// unreserved characters of RFC 3986 are kept
+const QUERY_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Adds query parameters to registered redirect URI, which can have its own query
+pub fn redirect_url(redirect_uri: &str, params: &[(&str, &str)]) -> String {
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, QUERY_VALUE)))
+        .collect::<Vec<String>>()
+        .join("&");
+    let separator = match redirect_uri.contains('?') {
+        true => '&',
+        false => '?',
+    };
+    format!("{}{}{}", redirect_uri, separator, query)
+}
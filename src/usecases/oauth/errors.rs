@@ -0,0 +1,28 @@
+#[derive(Debug)]
+pub enum OAuthError {
+    FatalError,
+    TemporaryError,
+    NotFoundError,
+    AlreadyExists,
+    InvalidRequestError,
+    InvalidClientError,
+    InvalidRedirectUriError,
+    InvalidGrantError,
+    UnauthorizedClientError,
+    UnsupportedGrantTypeError,
+    UnsupportedResponseTypeError,
+    InvalidScopeError,
+    AccessDeniedError,
+}
+
+/// Errors of authorization endpoint, the user agent is redirected back to the client
+/// only when redirect URI is verified, otherwise the error is shown to the user
+#[derive(Debug)]
+pub enum AuthorizationError {
+    ClientError(OAuthError),
+    RedirectError {
+        redirect_uri: String,
+        state: Option<String>,
+        error: OAuthError,
+    },
+}
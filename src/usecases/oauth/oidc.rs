@@ -0,0 +1,131 @@
+use crate::common::SecurityConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::oauth::entities::{
+    AuthorizationCode, OpenIdConfiguration, UserInfo, OIDC_SCOPES, OPENID_SCOPE, PROFILE_SCOPE,
+    SUPPORTED_GRANT_TYPES,
+};
+use crate::usecases::oauth::errors::OAuthError;
+use crate::usecases::users::crypto::{decode_jwt, generate_id_token};
+use crate::usecases::users::entities::{OidcClaims, TokenSession, User};
+use crate::usecases::users::errors::SignError;
+use crate::usecases::users::get_user::FindUserById;
+use crate::usecases::users::token_revoker::{check_token_revocation, RevokeToken};
+
+use chrono::DateTime;
+use log::error;
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+/// Issuer is the public URL of the service, endpoints are resolved against it
+pub fn openid_configuration(config: &SecurityConfig) -> OpenIdConfiguration {
+    let base_url = config.jwt_issuer.trim_end_matches('/');
+    OpenIdConfiguration {
+        issuer: config.jwt_issuer.clone(),
+        authorization_endpoint: format!("{}/auth/v1/oauth/authorize", base_url),
+        token_endpoint: format!("{}/auth/v1/oauth/token", base_url),
+        userinfo_endpoint: format!("{}/auth/v1/oauth/userinfo", base_url),
+        jwks_uri: format!("{}/.well-known/jwks.json", base_url),
+        response_types_supported: to_strings(&["code"]),
+        // grants of the token endpoint, refresh tokens are rotated with auth/v1/token/refresh
+        grant_types_supported: to_strings(SUPPORTED_GRANT_TYPES),
+        subject_types_supported: to_strings(&["public"]),
+        id_token_signing_alg_values_supported: vec![config.jwt_keyring.current().algorithm],
+        scopes_supported: to_strings(OIDC_SCOPES),
+        token_endpoint_auth_methods_supported: to_strings(&[
+            "client_secret_basic",
+            "client_secret_post",
+            "none",
+        ]),
+        code_challenge_methods_supported: to_strings(&["S256"]),
+        claims_supported: to_strings(&[
+            "sub",
+            "iss",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "sid",
+            "preferred_username",
+            "updated_at",
+        ]),
+    }
+}
+
+pub fn has_scope(scope: &str, expected: &str) -> bool {
+    scope.split_whitespace().any(|scope| scope == expected)
+}
+
+fn user_info(user: User) -> Result<UserInfo, AccessModelError> {
+    let updated_at = DateTime::parse_from_rfc3339(&user.updated_at).map_err(|e| {
+        error!("Invalid updated_at of user {}: {}", user.user_id, e);
+        AccessModelError::FatalError
+    })?;
+    Ok(UserInfo {
+        sub: user.user_id.to_string(),
+        preferred_username: user.username,
+        updated_at: updated_at.timestamp(),
+    })
+}
+
+/// id_token for authorization code with `openid` scope, profile claims for `profile` scope
+pub async fn issue_id_token(
+    user_repo: &impl FindUserById,
+    security_config: &SecurityConfig,
+    client_id: &str,
+    code: &AuthorizationCode,
+) -> Result<Option<String>, OAuthError> {
+    if !has_scope(&code.scope, OPENID_SCOPE) {
+        return Ok(None);
+    }
+    let mut oidc = OidcClaims {
+        nonce: code.nonce.clone(),
+        auth_time: Some(code.auth_time.timestamp()),
+        ..OidcClaims::default()
+    };
+    if has_scope(&code.scope, PROFILE_SCOPE) {
+        let profile = match user_repo
+            .find_user_by_id(code.user_id)
+            .await
+            .and_then(user_info)
+        {
+            Ok(profile) => profile,
+            Err(AccessModelError::NotFoundError) => return Err(OAuthError::InvalidGrantError),
+            Err(AccessModelError::TemporaryError) => return Err(OAuthError::TemporaryError),
+            Err(_) => return Err(OAuthError::FatalError),
+        };
+        oidc.preferred_username = Some(profile.preferred_username);
+        oidc.updated_at = Some(profile.updated_at);
+    }
+    let session = TokenSession {
+        session_id: code.session_id.clone(),
+        token_version: code.token_version,
+    };
+    match generate_id_token(security_config, code.user_id, client_id, session, oidc) {
+        Ok(id_token) => Ok(Some(id_token)),
+        Err(_) => Err(OAuthError::FatalError),
+    }
+}
+
+/// Claims of the access token owner, OpenID Connect Core section 5.3
+pub async fn get_userinfo(
+    user_repo: &impl FindUserById,
+    revoker: &impl RevokeToken,
+    config: &SecurityConfig,
+    jwt_token: &str,
+) -> Result<UserInfo, SignError> {
+    let claims = decode_jwt(config, jwt_token)?;
+    check_token_revocation(revoker, &claims).await?;
+    match user_repo
+        .find_user_by_id(claims.user_id)
+        .await
+        .and_then(user_info)
+    {
+        Ok(user_info) => Ok(user_info),
+        Err(AccessModelError::NotFoundError) => Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
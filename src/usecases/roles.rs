@@ -3,5 +3,6 @@ pub mod errors;
 pub mod role_creator;
 pub mod role_disabler;
 pub mod role_get_item;
+pub mod role_get_list;
 pub mod role_members_binder;
 pub mod role_permissions_binder;
@@ -86,3 +86,61 @@ impl RoleMemberBinding {
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct RoleSummary {
+    pub role_id: i32,
+    pub role_name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub is_deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permissions_count: Option<i64>,
+}
+
+impl RoleSummary {
+    pub fn new(
+        role_id: i32,
+        role_name: String,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+        is_deleted: bool,
+        members_count: Option<i64>,
+        permissions_count: Option<i64>,
+    ) -> RoleSummary {
+        RoleSummary {
+            role_id,
+            role_name,
+            created_at,
+            updated_at,
+            is_deleted,
+            members_count,
+            permissions_count,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RolesFilters {
+    pub role_name: Option<String>,
+    pub is_deleted: Option<bool>,
+    pub user_id: Option<i32>,
+    pub permission_id: Option<i32>,
+    pub with_counts: bool,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RolesList {
+    pub roles: Vec<RoleSummary>,
+    pub total: i64,
+}
+
+impl RolesList {
+    pub fn new(roles: Vec<RoleSummary>, total: i64) -> RolesList {
+        RolesList { roles, total }
+    }
+}
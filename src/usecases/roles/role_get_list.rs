@@ -0,0 +1,24 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::roles::entities::{RolesFilters, RolesList};
+use crate::usecases::roles::errors::RoleUCError;
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait GetRolesList {
+    async fn get_roles_by_filters(
+        &self,
+        filters: RolesFilters,
+    ) -> Result<RolesList, AccessModelError>;
+}
+
+pub async fn get_roles_by_filters(
+    role_access_model: &impl GetRolesList,
+    filters: RolesFilters,
+) -> Result<RolesList, RoleUCError> {
+    match role_access_model.get_roles_by_filters(filters).await {
+        Ok(roles) => Ok(roles),
+        Err(AccessModelError::TemporaryError) => Err(RoleUCError::TemporaryError),
+        Err(_) => Err(RoleUCError::FatalError),
+    }
+}
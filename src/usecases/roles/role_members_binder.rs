@@ -37,7 +37,7 @@ pub async fn bind_member_to_role(
         .get_role_member_binding(role_id, user_id)
         .await
     {
-        Ok(binding) if binding.is_deleted == false => Ok(binding),
+        Ok(binding) if !binding.is_deleted => Ok(binding),
         Ok(binding) => match role_access_model
             .enable_existed_role_member_binding(binding.role_id, binding.user_id)
             .await
@@ -37,7 +37,7 @@ pub async fn bind_permission_to_role(
         .get_role_permission_binding(role_id, perm_id)
         .await
     {
-        Ok(binding) if binding.is_deleted == false => Ok(binding),
+        Ok(binding) if !binding.is_deleted => Ok(binding),
         Ok(binding) => match role_access_model
             .enable_existed_role_permission_binding(binding.role_id, binding.permission_id)
             .await
@@ -0,0 +1,3 @@
+pub mod entities;
+pub mod errors;
+pub mod service_authenticator;
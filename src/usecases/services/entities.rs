@@ -0,0 +1,21 @@
+pub struct ServiceCredentials {
+    pub service_id: i32,
+    pub client_id: String,
+    pub secret_hash: String,
+}
+
+impl ServiceCredentials {
+    pub fn new(service_id: i32, client_id: String, secret_hash: String) -> ServiceCredentials {
+        ServiceCredentials {
+            service_id,
+            client_id,
+            secret_hash,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthenticatedService {
+    pub client_id: String,
+    pub scopes: Vec<String>,
+}
@@ -0,0 +1,6 @@
+#[derive(Debug)]
+pub enum ServiceUCError {
+    FatalError,
+    TemporaryError,
+    VerificationError,
+}
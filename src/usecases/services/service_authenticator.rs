@@ -0,0 +1,77 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::services::entities::{AuthenticatedService, ServiceCredentials};
+use crate::usecases::services::errors::ServiceUCError;
+use crate::usecases::users::crypto::hash_token;
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait AuthenticateService {
+    async fn get_service_credentials(
+        &self,
+        client_id: &str,
+    ) -> Result<ServiceCredentials, AccessModelError>;
+    async fn get_service_by_cert_identities(
+        &self,
+        identities: &[String],
+    ) -> Result<ServiceCredentials, AccessModelError>;
+    async fn get_service_scopes(&self, service_id: i32) -> Result<Vec<String>, AccessModelError>;
+}
+
+/// Returns enabled service with its scopes, client secret is stored as sha256 hash
+pub async fn authenticate_service(
+    service_access_model: &impl AuthenticateService,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<AuthenticatedService, ServiceUCError> {
+    let credentials = match service_access_model
+        .get_service_credentials(client_id)
+        .await
+    {
+        Ok(credentials) => credentials,
+        Err(AccessModelError::NotFoundError) => return Err(ServiceUCError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(ServiceUCError::TemporaryError),
+        Err(_) => return Err(ServiceUCError::FatalError),
+    };
+    if hash_token(client_secret) != credentials.secret_hash {
+        return Err(ServiceUCError::VerificationError);
+    }
+    get_scopes(service_access_model, credentials).await
+}
+
+/// Returns enabled service registered with one of identities from verified client certificate
+pub async fn authenticate_service_by_certificate(
+    service_access_model: &impl AuthenticateService,
+    identities: &[String],
+) -> Result<AuthenticatedService, ServiceUCError> {
+    if identities.is_empty() {
+        return Err(ServiceUCError::VerificationError);
+    }
+    let credentials = match service_access_model
+        .get_service_by_cert_identities(identities)
+        .await
+    {
+        Ok(credentials) => credentials,
+        Err(AccessModelError::NotFoundError) => return Err(ServiceUCError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(ServiceUCError::TemporaryError),
+        Err(_) => return Err(ServiceUCError::FatalError),
+    };
+    get_scopes(service_access_model, credentials).await
+}
+
+async fn get_scopes(
+    service_access_model: &impl AuthenticateService,
+    credentials: ServiceCredentials,
+) -> Result<AuthenticatedService, ServiceUCError> {
+    match service_access_model
+        .get_service_scopes(credentials.service_id)
+        .await
+    {
+        Ok(scopes) => Ok(AuthenticatedService {
+            client_id: credentials.client_id,
+            scopes,
+        }),
+        Err(AccessModelError::TemporaryError) => Err(ServiceUCError::TemporaryError),
+        Err(_) => Err(ServiceUCError::FatalError),
+    }
+}
@@ -2,4 +2,13 @@ pub mod crypto;
 pub mod entities;
 pub mod errors;
 pub mod get_user;
+pub mod jwt_keys;
+pub mod ldap_sign_in;
+pub mod mfa;
+pub mod password_reset;
+pub mod sign_in_throttler;
+pub mod token_refresher;
+pub mod token_revoker;
+pub mod totp;
 pub mod user_creator;
+pub mod user_disabler;
@@ -1,22 +1,35 @@
 use crate::common::SecurityConfig;
 use crate::usecases::base_entities::AccessModelError;
-use crate::usecases::users::entities::{Claims, SingnedInfo};
+use crate::usecases::users::entities::{
+    AuthenticatedUser, Claims, OidcClaims, SingnedInfo, TokenSession, UserCredentials,
+};
 use crate::usecases::users::errors::SignError;
+use crate::usecases::users::token_refresher::{issue_refresh_token, StoreRefreshToken};
+use crate::usecases::users::token_revoker::{check_token_revocation, RevokeToken};
 use argon2::{self, Config};
 use async_trait::async_trait;
-use hmac::{Hmac, Mac};
-use jwt::SignWithKey;
-use jwt::VerifyWithKey;
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use log::error;
-use serde_json::json;
-use sha2::Sha256;
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-static SALT: &[u8] = "22f65e79-496a-4b48-8abc-f83e1e52aa4e".as_bytes();
+const SALT_LENGTH: usize = 16;
+/// Checked for unknown usernames, so sign in takes as long as with a wrong password
+pub const DUMMY_PASSWORD_HASH: &str =
+    "$argon2i$v=19$m=4096,t=3,p=1$BGqmX2rKRggeC7b+yiJa5Q$bRSv5SqbsGiJOnfJbiSAkj3s02KLqa7fMLklwyoDgTg";
+
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
 
 pub fn generate_hash(password: &str) -> Result<String, SignError> {
     let config = Config::default();
-    match argon2::hash_encoded(password.as_bytes(), SALT, &config) {
-        Ok(hash) => Ok(hash.to_string()),
+    let salt = generate_salt();
+    match argon2::hash_encoded(password.as_bytes(), &salt, &config) {
+        Ok(hash) => Ok(hash),
         Err(e) => {
             error!("hashing password error: {}", e);
             Err(SignError::FatalError)
@@ -24,21 +37,77 @@ pub fn generate_hash(password: &str) -> Result<String, SignError> {
     }
 }
 
+/// Checks password against stored PHC string, salt and params are taken from the hash itself
+pub fn verify_hash(hash: &str, password: &str) -> Result<bool, SignError> {
+    match argon2::verify_encoded(hash, password.as_bytes()) {
+        Ok(matches) => Ok(matches),
+        Err(e) => {
+            error!("verification password hash error: {}", e);
+            Err(SignError::VerificationError)
+        }
+    }
+}
+
+pub fn generate_random_token(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hash for high-entropy opaque tokens, salt is not needed in contrast to passwords
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
 pub fn generate_jwt(
     config: &SecurityConfig,
     user_id: i32,
     roles: Vec<String>,
+    session: TokenSession,
 ) -> Result<String, SignError> {
-    let key: Hmac<Sha256> = match Hmac::new_from_slice(config.secret_key.as_bytes()) {
-        Ok(key) => key,
-        Err(e) => {
-            error!("Error during initiatilization secret key for jwt: {}", e);
-            return Err(SignError::FatalError);
-        }
+    let claims = Claims::new(
+        user_id,
+        roles,
+        config.jwt_issuer.clone(),
+        config.jwt_audience.clone(),
+        chrono::Duration::minutes(config.expired_access_token_minutes.into()),
+        generate_random_token(16),
+        session,
+    );
+    encode_jwt(config, &claims)
+}
+
+/// OpenID Connect id_token, audience is the client, session is shared with access token
+pub fn generate_id_token(
+    config: &SecurityConfig,
+    user_id: i32,
+    client_id: &str,
+    session: TokenSession,
+    oidc: OidcClaims,
+) -> Result<String, SignError> {
+    let claims = Claims {
+        oidc,
+        ..Claims::new(
+            user_id,
+            vec![],
+            config.jwt_issuer.clone(),
+            client_id.to_string(),
+            chrono::Duration::minutes(config.expired_access_token_minutes.into()),
+            generate_random_token(16),
+            session,
+        )
     };
-    let claims = Claims::new(user_id, config.expired_jwt_days, roles);
-    let content = json!(claims);
-    match content.sign_with_key(&key) {
+    encode_jwt(config, &claims)
+}
+
+pub fn encode_jwt<T: Serialize>(config: &SecurityConfig, claims: &T) -> Result<String, SignError> {
+    let key = config.jwt_keyring.current();
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+    match encode(&header, claims, key.encoding_key()) {
         Ok(jwt) => Ok(jwt),
         Err(e) => {
             error!("Error during generation jwt: {}", e);
@@ -48,62 +117,188 @@ pub fn generate_jwt(
 }
 
 pub fn decode_jwt(config: &SecurityConfig, jwt_token: &str) -> Result<Claims, SignError> {
-    let key: Hmac<Sha256> = match Hmac::new_from_slice(config.secret_key.as_bytes()) {
-        Ok(key) => key,
-        Err(e) => {
-            error!("Error during initiatilization secret key for jwt: {}", e);
-            return Err(SignError::FatalError);
-        }
+    let header = match decode_header(jwt_token) {
+        Ok(header) => header,
+        Err(_) => return Err(SignError::VerificationError),
+    };
+    // tokens issued before kid header was introduced are verified with the current key
+    let key = match header.kid {
+        Some(kid) => match config.jwt_keyring.find(&kid) {
+            Some(key) => key,
+            None => return Err(SignError::VerificationError),
+        },
+        None => config.jwt_keyring.current(),
+    };
+    // registered claims are checked by validate_claims to tell expired tokens apart
+    let mut validation = Validation::new(key.algorithm);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    let claims: Claims = match decode(jwt_token, key.decoding_key(), &validation) {
+        Ok(token_data) => token_data.claims,
+        Err(_) => return Err(SignError::VerificationError),
     };
-    match jwt_token.verify_with_key(&key) {
-        Ok(claims) => Ok(claims),
-        Err(_) => Err(SignError::VerificationError),
+    validate_claims(config, &claims)?;
+    Ok(claims)
+}
+
+/// Checks registered claims of already verified token, `jwt_leeway_seconds` is allowed clock skew
+pub fn validate_claims(config: &SecurityConfig, claims: &Claims) -> Result<(), SignError> {
+    let now = chrono::Utc::now().timestamp();
+    let leeway = i64::from(config.jwt_leeway_seconds);
+    if claims.exp + leeway <= now {
+        return Err(SignError::ExpiredTokenError);
+    }
+    if claims.nbf - leeway > now {
+        return Err(SignError::VerificationError);
+    }
+    if claims.iss != config.jwt_issuer || claims.aud != config.jwt_audience {
+        return Err(SignError::VerificationError);
     }
+    // id tokens are not accepted as access tokens, even if client id equals audience
+    if claims.oidc.auth_time.is_some() {
+        return Err(SignError::VerificationError);
+    }
+    Ok(())
 }
 
 #[async_trait]
-pub trait SignInVerification {
-    async fn verificate_default(&self, username: &str, hash: &str)
-        -> Result<i32, AccessModelError>;
+pub trait SignInVerification: Sync {
+    async fn get_user_credentials(
+        &self,
+        username: &str,
+    ) -> Result<UserCredentials, AccessModelError>;
     async fn get_user_roles(&self, user_id: &i32) -> Result<Vec<String>, AccessModelError>;
     async fn get_user_perms(&self, user_id: &i32) -> Result<Vec<String>, AccessModelError>;
+    /// Checks password against local hash, other sign in backends override it
+    async fn verify_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<UserCredentials, SignError> {
+        let credentials = match self.get_user_credentials(username).await {
+            Ok(credentials) => credentials,
+            Err(AccessModelError::NotFoundError) => {
+                let _ = verify_hash(DUMMY_PASSWORD_HASH, password);
+                return Err(SignError::VerificationError);
+            }
+            Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+            Err(_) => return Err(SignError::FatalError),
+        };
+        // federated and LDAP users have no local password
+        if credentials.password_hash.is_empty() {
+            let _ = verify_hash(DUMMY_PASSWORD_HASH, password);
+            return Err(SignError::VerificationError);
+        }
+        if !verify_hash(&credentials.password_hash, password)? {
+            return Err(SignError::VerificationError);
+        }
+        Ok(credentials)
+    }
+}
+
+/// Checks password of enabled user
+pub async fn verify_credentials(
+    verificator: &impl SignInVerification,
+    username: &str,
+    password: &str,
+) -> Result<UserCredentials, SignError> {
+    verificator.verify_password(username, password).await
 }
 
 pub async fn sign_in(
     verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
     security_config: &SecurityConfig,
     username: String,
     password: String,
 ) -> Result<SingnedInfo, SignError> {
-    let hash = match generate_hash(&password) {
-        Ok(hash) => hash,
-        Err(_) => return Err(SignError::FatalError),
-    };
-    let user_id = match verificator.verificate_default(&username, &hash).await {
-        Ok(user_id) => user_id,
-        Err(AccessModelError::NotFoundError) => return Err(SignError::VerificationError),
-        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
-        Err(_) => return Err(SignError::FatalError),
+    let credentials = verify_credentials(verificator, &username, &password).await?;
+    let session = TokenSession {
+        session_id: generate_random_token(16),
+        token_version: credentials.token_version,
     };
+    issue_tokens(
+        verificator,
+        token_storage,
+        security_config,
+        credentials.user_id,
+        session,
+    )
+    .await
+}
+
+pub async fn issue_tokens(
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    user_id: i32,
+    session: TokenSession,
+) -> Result<SingnedInfo, SignError> {
     let roles = match verificator.get_user_roles(&user_id).await {
         Ok(roles) => roles,
         Err(_) => return Err(SignError::FatalError),
     };
-    let token_str = match generate_jwt(security_config, user_id, roles) {
-        Ok(jwt) => jwt,
+    let family_id = session.session_id.clone();
+    let token_str = generate_jwt(security_config, user_id, roles, session)?;
+    let refresh_token =
+        issue_refresh_token(token_storage, security_config, user_id, family_id).await?;
+    Ok(SingnedInfo::new(user_id, token_str, refresh_token))
+}
+
+/// Issues tokens for an active user without password check, e.g. by admin tools
+pub async fn issue_tokens_for_user(
+    verificator: &impl SignInVerification,
+    revoker: &impl RevokeToken,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    user_id: i32,
+) -> Result<SingnedInfo, SignError> {
+    let token_version = match revoker.get_token_version(user_id).await {
+        Ok(version) => version,
+        Err(AccessModelError::NotFoundError) => return Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
         Err(_) => return Err(SignError::FatalError),
     };
-    Ok(SingnedInfo::new(user_id, token_str.to_string()))
+    let session = TokenSession {
+        session_id: generate_random_token(16),
+        token_version,
+    };
+    issue_tokens(
+        verificator,
+        token_storage,
+        security_config,
+        user_id,
+        session,
+    )
+    .await
 }
 
 pub async fn verificate_jwt_token_and_enrich_perms(
     verificator: &impl SignInVerification,
+    revoker: &impl RevokeToken,
     config: &SecurityConfig,
     jwt_token: &str,
 ) -> Result<Vec<String>, SignError> {
+    let user = authenticate_by_jwt(verificator, revoker, config, jwt_token).await?;
+    Ok(user.permissions)
+}
+
+/// Verifies token and returns its user with actual permissions
+pub async fn authenticate_by_jwt(
+    verificator: &impl SignInVerification,
+    revoker: &impl RevokeToken,
+    config: &SecurityConfig,
+    jwt_token: &str,
+) -> Result<AuthenticatedUser, SignError> {
     let claims = decode_jwt(config, jwt_token)?;
+    check_token_revocation(revoker, &claims).await?;
     match verificator.get_user_perms(&claims.user_id).await {
-        Ok(perms) => Ok(perms),
-        Err(_) => return Err(SignError::FatalError),
+        Ok(permissions) => Ok(AuthenticatedUser {
+            user_id: claims.user_id,
+            permissions,
+            api_key_id: None,
+        }),
+        Err(_) => Err(SignError::FatalError),
     }
 }
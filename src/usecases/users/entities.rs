@@ -14,6 +14,7 @@ pub struct User {
 pub struct UserForCreation {
     pub username: String,
     pub password_hash: String,
+    pub email: Option<String>,
 }
 
 impl User {
@@ -34,33 +35,267 @@ impl User {
     }
 }
 
+#[derive(Deserialize, Clone)]
+pub struct UsersFilters {
+    pub username: Option<String>,
+    pub enabled: Option<bool>,
+    pub is_deleted: Option<bool>,
+    pub role_id: Option<i32>,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UsersList {
+    pub users: Vec<User>,
+    pub total: i64,
+}
+
+impl UsersList {
+    pub fn new(users: Vec<User>, total: i64) -> UsersList {
+        UsersList { users, total }
+    }
+}
+
+pub struct UserCredentials {
+    pub user_id: i32,
+    pub password_hash: String,
+    pub token_version: i32,
+}
+
+impl UserCredentials {
+    pub fn new(user_id: i32, password_hash: String, token_version: i32) -> UserCredentials {
+        UserCredentials {
+            user_id,
+            password_hash,
+            token_version,
+        }
+    }
+}
+
+/// User able to reset password: enabled, with local password and email
+pub struct ResetRecipient {
+    pub user_id: i32,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Notification {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// User entry found in LDAP directory, `groups` are DNs of its groups
+#[derive(Clone, Debug)]
+pub struct DirectoryEntry {
+    pub dn: String,
+    pub username: String,
+    pub groups: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SingnedInfo {
     pub user_id: i32,
     pub jwt_token: String,
+    pub refresh_token: String,
 }
 
 impl SingnedInfo {
-    pub fn new(user_id: i32, jwt_token: String) -> SingnedInfo {
-        SingnedInfo { user_id, jwt_token }
+    pub fn new(user_id: i32, jwt_token: String, refresh_token: String) -> SingnedInfo {
+        SingnedInfo {
+            user_id,
+            jwt_token,
+            refresh_token,
+        }
     }
 }
 
+pub struct RefreshToken {
+    pub token_id: i32,
+    pub user_id: i32,
+    pub family_id: String,
+    pub expired_at: DateTime<Utc>,
+    pub is_rotated: bool,
+    pub is_revoked: bool,
+    // current token version of the owner
+    pub token_version: i32,
+}
+
+impl RefreshToken {
+    pub fn new(
+        token_id: i32,
+        user_id: i32,
+        family_id: String,
+        expired_at: DateTime<Utc>,
+        is_rotated: bool,
+        is_revoked: bool,
+        token_version: i32,
+    ) -> RefreshToken {
+        RefreshToken {
+            token_id,
+            user_id,
+            family_id,
+            expired_at,
+            is_rotated,
+            is_revoked,
+            token_version,
+        }
+    }
+}
+
+pub struct RefreshTokenForCreation {
+    pub token_hash: String,
+    pub user_id: i32,
+    pub family_id: String,
+    pub expired_at: DateTime<Utc>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub user_id: i32,
     pub expired_at: String,
     pub permissions: Vec<String>,
+    // registered claims, RFC 7519
+    pub sub: String,
+    pub iss: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub nbf: i64,
+    pub jti: String,
+    // session id is shared with refresh token family
+    pub sid: String,
+    pub ver: i32,
+    #[serde(flatten)]
+    pub oidc: OidcClaims,
+}
+
+/// Claims of OpenID Connect id_token, absent in access tokens
+#[derive(Serialize, Deserialize, Default)]
+pub struct OidcClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+}
+
+/// Result of password check, users with confirmed TOTP have to pass the second factor
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SignInResult {
+    Signed(SingnedInfo),
+    MfaRequired(MfaPendingInfo),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MfaPendingInfo {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expired_at: DateTime<Utc>,
+}
+
+pub struct MfaChallenge {
+    pub challenge_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub token_version: i32,
+}
+
+pub struct MfaChallengeForCreation {
+    pub token_hash: String,
+    pub user_id: i32,
+    pub username: String,
+    pub token_version: i32,
+    pub expired_at: DateTime<Utc>,
+}
+
+/// Second factor is either TOTP code or one of recovery codes
+pub struct MfaVerification {
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+    pub client_ip: Option<String>,
+}
+
+pub struct TotpSecret {
+    pub user_id: i32,
+    pub secret: String,
+    pub is_confirmed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: i32,
+    pub permissions: Vec<String>,
+    /// set if the user is authenticated by API key instead of access token
+    pub api_key_id: Option<i32>,
+}
+
+pub struct TokenSession {
+    pub session_id: String,
+    pub token_version: i32,
 }
 
 impl Claims {
-    pub fn new(user_id: i32, expired_in_days: u32, permissions: Vec<String>) -> Claims {
-        let expired_at =
-            (chrono::Utc::now() + chrono::Duration::days(expired_in_days.into())).to_rfc3339();
+    pub fn new(
+        user_id: i32,
+        permissions: Vec<String>,
+        issuer: String,
+        audience: String,
+        expired_in: chrono::Duration,
+        jti: String,
+        session: TokenSession,
+    ) -> Claims {
+        let now = chrono::Utc::now();
+        let expired_at = now + expired_in;
         Claims {
             user_id,
-            expired_at,
+            expired_at: expired_at.to_rfc3339(),
             permissions,
+            sub: user_id.to_string(),
+            iss: issuer,
+            aud: audience,
+            exp: expired_at.timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti,
+            sid: session.session_id,
+            ver: session.token_version,
+            oidc: OidcClaims::default(),
         }
     }
 }
+
+/// Sign in attempt, failures are counted by username and by client IP
+pub struct SignInAttempt {
+    pub username: String,
+    pub password: String,
+    pub client_ip: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AttemptKey {
+    Username(String),
+    ClientIp(String),
+}
+
+pub struct FailedAttempts {
+    pub count: u32,
+    pub last_attempted_at: Option<DateTime<Utc>>,
+}
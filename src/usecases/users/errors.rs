@@ -1,8 +1,10 @@
+#[derive(Debug)]
 pub enum UserUCError {
     FatalError,
     TemporaryError,
     NotFoundError,
     AlreadyExists,
+    ValidationError(String),
 }
 
 #[derive(Debug)]
@@ -10,4 +12,35 @@ pub enum SignError {
     FatalError,
     TemporaryError,
     VerificationError,
+    ExpiredTokenError,
+    /// Sign in is throttled, the value is seconds to wait
+    TooManyAttemptsError(u64),
+}
+
+#[derive(Debug)]
+pub enum MfaError {
+    FatalError,
+    TemporaryError,
+    NotFoundError,
+    VerificationError,
+    AlreadyEnabledError,
+    NotEnrolledError,
+}
+
+#[derive(Debug)]
+pub enum PasswordResetError {
+    FatalError,
+    TemporaryError,
+    NotFoundError,
+    /// unknown, used or expired reset token
+    VerificationError,
+    ValidationError(String),
+}
+
+#[derive(Debug)]
+pub enum JwtKeyError {
+    ReadError(String),
+    InvalidKeyError(String),
+    UnsupportedAlgorithmError(String),
+    DuplicateKeyIdError(String),
 }
@@ -1,6 +1,8 @@
 use crate::usecases::base_entities::AccessModelError;
-use crate::usecases::users::entities::User;
+use crate::usecases::users::entities::{User, UsersFilters, UsersList};
 use crate::usecases::users::errors::UserUCError;
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+use crate::usecases::users::token_revoker::{revoke_user_sessions, RevokeToken};
 use async_trait::async_trait;
 
 #[async_trait]
@@ -15,7 +17,10 @@ pub trait RemoveUserById {
 
 #[async_trait]
 pub trait GetUsers {
-    async fn update_user_by_id(&self, limit: i64, offset: i64) -> Result<User, UserUCError>;
+    async fn get_users_by_filters(
+        &self,
+        filters: UsersFilters,
+    ) -> Result<UsersList, AccessModelError>;
 }
 
 pub async fn get_user_by_id(
@@ -30,10 +35,14 @@ pub async fn get_user_by_id(
     }
 }
 
+/// Sessions are revoked before the deletion, so failed deletion can be retried
 pub async fn remove_user_by_id(
     user_repo: &impl RemoveUserById,
+    revoker: &impl RevokeToken,
+    token_storage: &impl StoreRefreshToken,
     user_id: i32,
 ) -> Result<(), UserUCError> {
+    revoke_user_sessions(revoker, token_storage, user_id).await?;
     match user_repo.remove_user_by_id(user_id).await {
         Ok(()) => Ok(()),
         Err(AccessModelError::NotFoundError) => Err(UserUCError::NotFoundError),
@@ -41,3 +50,14 @@ pub async fn remove_user_by_id(
         Err(_) => Err(UserUCError::FatalError),
     }
 }
+
+pub async fn get_users_by_filters(
+    user_repo: &impl GetUsers,
+    filters: UsersFilters,
+) -> Result<UsersList, UserUCError> {
+    match user_repo.get_users_by_filters(filters).await {
+        Ok(users) => Ok(users),
+        Err(AccessModelError::TemporaryError) => Err(UserUCError::TemporaryError),
+        Err(_) => Err(UserUCError::FatalError),
+    }
+}
@@ -0,0 +1,177 @@
+use crate::usecases::users::errors::JwtKeyError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::SigningKey;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+/// Key used to sign and verify access tokens, `kid` goes to the header of every issued token
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    public_jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    pub fn from_secret(kid: &str, secret: &str) -> JwtKey {
+        JwtKey {
+            kid: kid.to_string(),
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            public_jwk: None,
+        }
+    }
+
+    /// Loads private key in PEM format (PKCS#8, or PKCS#1 for RSA), public part is derived from it
+    pub fn from_pem(kid: &str, algorithm: &str, private_pem: &[u8]) -> Result<JwtKey, JwtKeyError> {
+        let algorithm = parse_asymmetric_algorithm(algorithm)?;
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_pem),
+            Algorithm::ES256 => EncodingKey::from_ec_pem(private_pem),
+            _ => EncodingKey::from_ed_pem(private_pem),
+        }
+        .map_err(|e| JwtKeyError::InvalidKeyError(e.to_string()))?;
+        let mut public_jwk = match algorithm {
+            Algorithm::EdDSA => ed_public_jwk(&encoding_key)?,
+            _ => Jwk::from_encoding_key(&encoding_key, algorithm)
+                .map_err(|e| JwtKeyError::InvalidKeyError(e.to_string()))?,
+        };
+        public_jwk.common.key_id = Some(kid.to_string());
+        public_jwk.common.public_key_use = Some(PublicKeyUse::Signature);
+        let decoding_key = DecodingKey::from_jwk(&public_jwk)
+            .map_err(|e| JwtKeyError::InvalidKeyError(e.to_string()))?;
+        Ok(JwtKey {
+            kid: kid.to_string(),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            public_jwk: Some(public_jwk),
+        })
+    }
+
+    pub fn from_pem_file(kid: &str, algorithm: &str, path: &str) -> Result<JwtKey, JwtKeyError> {
+        let private_pem =
+            fs::read(path).map_err(|e| JwtKeyError::ReadError(format!("{}: {}", path, e)))?;
+        JwtKey::from_pem(kid, algorithm, &private_pem)
+    }
+
+    /// HS256 file holds the secret itself, other algorithms expect private key in PEM format
+    pub fn from_file(kid: &str, algorithm: &str, path: &str) -> Result<JwtKey, JwtKeyError> {
+        if algorithm != "HS256" {
+            return JwtKey::from_pem_file(kid, algorithm, path);
+        }
+        let secret = fs::read_to_string(path)
+            .map_err(|e| JwtKeyError::ReadError(format!("{}: {}", path, e)))?;
+        Ok(JwtKey::from_secret(kid, secret.trim()))
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    /// Public part of the key for JWKS, shared secrets are never published
+    pub fn public_jwk(&self) -> Option<&Jwk> {
+        self.public_jwk.as_ref()
+    }
+}
+
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+/// Tokens are signed only with the current key, retired keys stay here until issued tokens expire
+#[derive(Clone, Debug)]
+pub struct JwtKeyring {
+    current: JwtKey,
+    verification_keys: Vec<JwtKey>,
+}
+
+impl JwtKeyring {
+    pub fn new(current: JwtKey, verification_keys: Vec<JwtKey>) -> Result<JwtKeyring, JwtKeyError> {
+        let mut kids = HashSet::new();
+        kids.insert(current.kid.clone());
+        for key in verification_keys.iter() {
+            if !kids.insert(key.kid.clone()) {
+                return Err(JwtKeyError::DuplicateKeyIdError(key.kid.clone()));
+            }
+        }
+        Ok(JwtKeyring {
+            current,
+            verification_keys,
+        })
+    }
+
+    pub fn current(&self) -> &JwtKey {
+        &self.current
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&JwtKey> {
+        self.keys().find(|key| key.kid == kid)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &JwtKey> {
+        std::iter::once(&self.current).chain(self.verification_keys.iter())
+    }
+
+    pub fn public_jwks(&self) -> Vec<Jwk> {
+        self.keys()
+            .filter_map(|key| key.public_jwk())
+            .cloned()
+            .collect()
+    }
+}
+
+impl From<JwtKey> for JwtKeyring {
+    fn from(current: JwtKey) -> JwtKeyring {
+        JwtKeyring {
+            current,
+            verification_keys: vec![],
+        }
+    }
+}
+
+fn parse_asymmetric_algorithm(algorithm: &str) -> Result<Algorithm, JwtKeyError> {
+    match Algorithm::from_str(algorithm) {
+        Ok(alg @ (Algorithm::RS256 | Algorithm::ES256 | Algorithm::EdDSA)) => Ok(alg),
+        _ => Err(JwtKeyError::UnsupportedAlgorithmError(
+            algorithm.to_string(),
+        )),
+    }
+}
+
+fn ed_public_jwk(encoding_key: &EncodingKey) -> Result<Jwk, JwtKeyError> {
+    let signing_key = SigningKey::from_pkcs8_der(encoding_key.inner())
+        .map_err(|e| JwtKeyError::InvalidKeyError(e.to_string()))?;
+    Ok(Jwk {
+        common: CommonParameters {
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_bytes()),
+        }),
+    })
+}
@@ -0,0 +1,188 @@
+use crate::common::LdapConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::SignInVerification;
+use crate::usecases::users::entities::{DirectoryEntry, UserCredentials};
+use crate::usecases::users::errors::SignError;
+
+use async_trait::async_trait;
+use log::warn;
+
+/// Provider name of LDAP identities in `user_identities`, subject is DN of the entry
+pub const LDAP_PROVIDER: &str = "ldap";
+
+#[async_trait]
+pub trait SearchDirectory {
+    /// Searches entry of the username, NotFound unless exactly one entry matches
+    async fn find_user_entry(
+        &self,
+        config: &LdapConfig,
+        username: &str,
+    ) -> Result<DirectoryEntry, AccessModelError>;
+    /// Binds as the entry, false for invalid credentials
+    async fn bind_user(
+        &self,
+        config: &LdapConfig,
+        dn: &str,
+        password: &str,
+    ) -> Result<bool, AccessModelError>;
+}
+
+#[async_trait]
+pub trait SyncDirectoryUser {
+    /// Returns credentials of the user linked with the entry, unknown entry gets new user.
+    /// NotFound if the user is disabled, AlreadyExists if the username is taken by unlinked user
+    async fn sync_directory_user(
+        &self,
+        entry: &DirectoryEntry,
+    ) -> Result<UserCredentials, AccessModelError>;
+    /// Binds the user to `granted` roles and unbinds from other `managed` roles
+    async fn sync_user_roles(
+        &self,
+        user_id: i32,
+        managed: &[String],
+        granted: &[String],
+    ) -> Result<(), AccessModelError>;
+}
+
+fn sign_error(e: AccessModelError) -> SignError {
+    match e {
+        AccessModelError::NotFoundError => SignError::VerificationError,
+        AccessModelError::TemporaryError => SignError::TemporaryError,
+        _ => SignError::FatalError,
+    }
+}
+
+/// Value of the first RDN, e.g. `admins` for `cn=admins,ou=groups,dc=example,dc=com`
+fn group_name(group_dn: &str) -> String {
+    let rdn = group_dn.split(',').next().unwrap_or_default();
+    let name = rdn.split_once('=').map_or(rdn, |(_, value)| value);
+    name.trim().to_lowercase()
+}
+
+/// Roles of the groups mapped in config, roles without mapped groups are not touched
+fn mapped_roles(config: &LdapConfig, groups: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut managed: Vec<String> = config.group_roles.values().cloned().collect();
+    managed.sort();
+    managed.dedup();
+    let mut granted: Vec<String> = groups
+        .iter()
+        .filter_map(|group| config.group_roles.get(&group_name(group)).cloned())
+        .collect();
+    granted.sort();
+    granted.dedup();
+    (managed, granted)
+}
+
+/// Search-then-bind, the entry is synced into local users on success
+pub async fn verify_directory_password(
+    directory: &impl SearchDirectory,
+    user_storage: &impl SyncDirectoryUser,
+    config: &LdapConfig,
+    username: &str,
+    password: &str,
+) -> Result<UserCredentials, SignError> {
+    // bind with empty password is unauthenticated bind and succeeds on most servers
+    if username.is_empty() || password.is_empty() {
+        return Err(SignError::VerificationError);
+    }
+    let entry = directory
+        .find_user_entry(config, username)
+        .await
+        .map_err(sign_error)?;
+    if !directory
+        .bind_user(config, &entry.dn, password)
+        .await
+        .map_err(sign_error)?
+    {
+        return Err(SignError::VerificationError);
+    }
+    let credentials = match user_storage.sync_directory_user(&entry).await {
+        Ok(credentials) => credentials,
+        Err(AccessModelError::AlreadyExists) => {
+            warn!(
+                "LDAP entry {} isn't linked with local user {}",
+                entry.dn, entry.username
+            );
+            return Err(SignError::VerificationError);
+        }
+        Err(e) => return Err(sign_error(e)),
+    };
+    if !config.group_roles.is_empty() {
+        let (managed, granted) = mapped_roles(config, &entry.groups);
+        user_storage
+            .sync_user_roles(credentials.user_id, &managed, &granted)
+            .await
+            .map_err(sign_error)?;
+    }
+    Ok(credentials)
+}
+
+/// Password is checked by the configured backend, users, roles and permissions are
+/// always taken from local storage. Without LDAP config only local passwords are checked
+pub struct LdapSignInVerification<'a, L, D, S> {
+    local: L,
+    directory: D,
+    user_storage: S,
+    config: Option<&'a LdapConfig>,
+}
+
+impl<'a, L, D, S> LdapSignInVerification<'a, L, D, S> {
+    pub fn new(
+        local: L,
+        directory: D,
+        user_storage: S,
+        config: Option<&'a LdapConfig>,
+    ) -> LdapSignInVerification<'a, L, D, S> {
+        LdapSignInVerification {
+            local,
+            directory,
+            user_storage,
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl<L, D, S> SignInVerification for LdapSignInVerification<'_, L, D, S>
+where
+    L: SignInVerification,
+    D: SearchDirectory + Sync,
+    S: SyncDirectoryUser + Sync,
+{
+    async fn get_user_credentials(
+        &self,
+        username: &str,
+    ) -> Result<UserCredentials, AccessModelError> {
+        self.local.get_user_credentials(username).await
+    }
+    async fn get_user_roles(&self, user_id: &i32) -> Result<Vec<String>, AccessModelError> {
+        self.local.get_user_roles(user_id).await
+    }
+    async fn get_user_perms(&self, user_id: &i32) -> Result<Vec<String>, AccessModelError> {
+        self.local.get_user_perms(user_id).await
+    }
+    async fn verify_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<UserCredentials, SignError> {
+        let config = match self.config {
+            Some(config) => config,
+            None => return self.local.verify_password(username, password).await,
+        };
+        if config.local_first {
+            match self.local.verify_password(username, password).await {
+                Err(SignError::VerificationError) => (),
+                result => return result,
+            }
+        }
+        verify_directory_password(
+            &self.directory,
+            &self.user_storage,
+            config,
+            username,
+            password,
+        )
+        .await
+    }
+}
@@ -0,0 +1,282 @@
+use crate::common::{SecurityConfig, SignInThrottlingConfig};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::{
+    generate_random_token, hash_token, issue_tokens, verify_credentials, SignInVerification,
+};
+use crate::usecases::users::entities::{
+    MfaChallenge, MfaChallengeForCreation, MfaPendingInfo, MfaVerification, RecoveryCodes,
+    SignInResult, SingnedInfo, TokenSession, TotpEnrollment, TotpSecret,
+};
+use crate::usecases::users::errors::{MfaError, SignError};
+use crate::usecases::users::get_user::FindUserById;
+use crate::usecases::users::sign_in_throttler::{check_sign_in_allowed, StoreSignInAttempts};
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+use crate::usecases::users::totp;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+
+const MFA_TOKEN_MINUTES: i64 = 5;
+// wrong codes for one mfa token, then password is required again
+const MAX_CHALLENGE_FAILURES: i32 = 5;
+const RECOVERY_CODES_COUNT: usize = 10;
+
+#[async_trait]
+pub trait StoreMfa {
+    async fn get_totp(&self, user_id: i32) -> Result<TotpSecret, AccessModelError>;
+    /// Replaces secret of not confirmed enrollment, AlreadyExists if TOTP is confirmed
+    async fn save_unconfirmed_totp(
+        &self,
+        user_id: i32,
+        secret: &str,
+    ) -> Result<(), AccessModelError>;
+    /// Confirms TOTP and replaces recovery codes of the user
+    async fn confirm_totp(
+        &self,
+        user_id: i32,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), AccessModelError>;
+    /// NotFound if the same or later time step was already used
+    async fn use_totp_step(&self, user_id: i32, step: i64) -> Result<(), AccessModelError>;
+    /// NotFound if the code is unknown or used
+    async fn use_recovery_code(
+        &self,
+        user_id: i32,
+        code_hash: &str,
+    ) -> Result<(), AccessModelError>;
+    async fn save_mfa_challenge(
+        &self,
+        challenge: MfaChallengeForCreation,
+    ) -> Result<(), AccessModelError>;
+    /// Only not used and not expired challenge with less than `max_failures` failures
+    async fn get_mfa_challenge(
+        &self,
+        token_hash: &str,
+        max_failures: i32,
+    ) -> Result<MfaChallenge, AccessModelError>;
+    async fn fail_mfa_challenge(&self, challenge_id: i32) -> Result<(), AccessModelError>;
+    /// NotFound if the challenge is already used
+    async fn close_mfa_challenge(&self, challenge_id: i32) -> Result<(), AccessModelError>;
+}
+
+fn mfa_error(e: AccessModelError) -> MfaError {
+    match e {
+        AccessModelError::NotFoundError => MfaError::NotFoundError,
+        AccessModelError::TemporaryError => MfaError::TemporaryError,
+        _ => MfaError::FatalError,
+    }
+}
+
+fn sign_error(e: AccessModelError) -> SignError {
+    match e {
+        AccessModelError::NotFoundError => SignError::VerificationError,
+        AccessModelError::TemporaryError => SignError::TemporaryError,
+        _ => SignError::FatalError,
+    }
+}
+
+/// Starts enrollment with new secret, previous not confirmed secret is replaced
+pub async fn enroll_totp(
+    user_repo: &impl FindUserById,
+    mfa_store: &impl StoreMfa,
+    security_config: &SecurityConfig,
+    user_id: i32,
+) -> Result<TotpEnrollment, MfaError> {
+    let user = user_repo
+        .find_user_by_id(user_id)
+        .await
+        .map_err(mfa_error)?;
+    let secret = totp::generate_secret();
+    match mfa_store.save_unconfirmed_totp(user_id, &secret).await {
+        Ok(_) => (),
+        Err(AccessModelError::AlreadyExists) => return Err(MfaError::AlreadyEnabledError),
+        Err(e) => return Err(mfa_error(e)),
+    };
+    let otpauth_uri = totp::otpauth_uri(&secret, &security_config.jwt_issuer, &user.username);
+    Ok(TotpEnrollment {
+        secret,
+        otpauth_uri,
+    })
+}
+
+/// Enables TOTP after the first valid code, returns recovery codes, only hashes are stored
+pub async fn confirm_totp(
+    mfa_store: &impl StoreMfa,
+    user_id: i32,
+    code: &str,
+) -> Result<RecoveryCodes, MfaError> {
+    let totp_secret = match mfa_store.get_totp(user_id).await {
+        Ok(totp_secret) => totp_secret,
+        Err(AccessModelError::NotFoundError) => return Err(MfaError::NotEnrolledError),
+        Err(e) => return Err(mfa_error(e)),
+    };
+    if totp_secret.is_confirmed {
+        return Err(MfaError::AlreadyEnabledError);
+    }
+    let step = match totp::verify_code(&totp_secret.secret, code, Utc::now().timestamp()) {
+        Some(step) => step,
+        None => return Err(MfaError::VerificationError),
+    };
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| generate_random_token(8))
+        .collect();
+    let hashes: Vec<String> = recovery_codes.iter().map(|code| hash_token(code)).collect();
+    match mfa_store.confirm_totp(user_id, step, &hashes).await {
+        Ok(_) => Ok(RecoveryCodes { recovery_codes }),
+        Err(AccessModelError::NotFoundError) => Err(MfaError::AlreadyEnabledError),
+        Err(e) => Err(mfa_error(e)),
+    }
+}
+
+/// Checks password, users with confirmed TOTP get short-lived mfa token instead of tokens
+pub async fn sign_in_with_mfa(
+    mfa_store: &impl StoreMfa,
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    username: &str,
+    password: &str,
+) -> Result<SignInResult, SignError> {
+    let credentials = verify_credentials(verificator, username, password).await?;
+    let mfa_required = match mfa_store.get_totp(credentials.user_id).await {
+        Ok(totp_secret) => totp_secret.is_confirmed,
+        Err(AccessModelError::NotFoundError) => false,
+        Err(e) => return Err(sign_error(e)),
+    };
+    if mfa_required {
+        let mfa_token = generate_random_token(32);
+        let expired_at = Utc::now() + Duration::minutes(MFA_TOKEN_MINUTES);
+        let challenge = MfaChallengeForCreation {
+            token_hash: hash_token(&mfa_token),
+            user_id: credentials.user_id,
+            username: username.to_string(),
+            token_version: credentials.token_version,
+            expired_at,
+        };
+        mfa_store
+            .save_mfa_challenge(challenge)
+            .await
+            .map_err(sign_error)?;
+        return Ok(SignInResult::MfaRequired(MfaPendingInfo {
+            mfa_required: true,
+            mfa_token,
+            expired_at,
+        }));
+    }
+    let session = TokenSession {
+        session_id: generate_random_token(16),
+        token_version: credentials.token_version,
+    };
+    let signed_info = issue_tokens(
+        verificator,
+        token_storage,
+        security_config,
+        credentials.user_id,
+        session,
+    )
+    .await?;
+    Ok(SignInResult::Signed(signed_info))
+}
+
+/// Second factor of logins without mfa token, e.g. on OAuth login page, only TOTP codes are accepted
+pub async fn verify_totp_if_enabled(
+    mfa_store: &impl StoreMfa,
+    user_id: i32,
+    code: Option<&str>,
+) -> Result<(), SignError> {
+    let totp_secret = match mfa_store.get_totp(user_id).await {
+        Ok(totp_secret) if totp_secret.is_confirmed => totp_secret,
+        Ok(_) | Err(AccessModelError::NotFoundError) => return Ok(()),
+        Err(e) => return Err(sign_error(e)),
+    };
+    let step = code
+        .and_then(|code| totp::verify_code(&totp_secret.secret, code, Utc::now().timestamp()))
+        .ok_or(SignError::VerificationError)?;
+    mfa_store
+        .use_totp_step(user_id, step)
+        .await
+        .map_err(sign_error)
+}
+
+async fn check_second_factor(
+    mfa_store: &impl StoreMfa,
+    user_id: i32,
+    verification: &MfaVerification,
+) -> Result<bool, SignError> {
+    let used = match (&verification.code, &verification.recovery_code) {
+        (Some(code), _) => {
+            let totp_secret = mfa_store.get_totp(user_id).await.map_err(sign_error)?;
+            match totp::verify_code(&totp_secret.secret, code, Utc::now().timestamp()) {
+                Some(step) => mfa_store.use_totp_step(user_id, step).await,
+                None => return Ok(false),
+            }
+        }
+        (None, Some(recovery_code)) => {
+            mfa_store
+                .use_recovery_code(user_id, &hash_token(recovery_code.trim()))
+                .await
+        }
+        (None, None) => return Ok(false),
+    };
+    match used {
+        Ok(_) => Ok(true),
+        Err(AccessModelError::NotFoundError) => Ok(false),
+        Err(e) => Err(sign_error(e)),
+    }
+}
+
+/// Exchanges mfa token and the second factor for tokens, wrong codes are counted
+/// by sign in throttling the same way as wrong passwords
+pub async fn verify_mfa(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    throttling_config: &SignInThrottlingConfig,
+    mfa_store: &impl StoreMfa,
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    verification: MfaVerification,
+) -> Result<SingnedInfo, SignError> {
+    let challenge = mfa_store
+        .get_mfa_challenge(&hash_token(&verification.mfa_token), MAX_CHALLENGE_FAILURES)
+        .await
+        .map_err(sign_error)?;
+    let client_ip = verification.client_ip.as_deref();
+    check_sign_in_allowed(
+        attempts_store,
+        throttling_config,
+        &challenge.username,
+        client_ip,
+    )
+    .await?;
+    if !check_second_factor(mfa_store, challenge.user_id, &verification).await? {
+        mfa_store
+            .fail_mfa_challenge(challenge.challenge_id)
+            .await
+            .map_err(sign_error)?;
+        attempts_store
+            .add_failure(&challenge.username, client_ip, Utc::now())
+            .await
+            .map_err(sign_error)?;
+        return Err(SignError::VerificationError);
+    }
+    mfa_store
+        .close_mfa_challenge(challenge.challenge_id)
+        .await
+        .map_err(sign_error)?;
+    attempts_store
+        .clear_failures(&challenge.username)
+        .await
+        .map_err(sign_error)?;
+    let session = TokenSession {
+        session_id: generate_random_token(16),
+        token_version: challenge.token_version,
+    };
+    issue_tokens(
+        verificator,
+        token_storage,
+        security_config,
+        challenge.user_id,
+        session,
+    )
+    .await
+}
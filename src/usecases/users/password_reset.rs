@@ -0,0 +1,180 @@
+use crate::common::PasswordResetConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::{generate_hash, generate_random_token, hash_token};
+use crate::usecases::users::entities::{Notification, ResetRecipient};
+use crate::usecases::users::errors::PasswordResetError;
+use crate::usecases::users::token_revoker::RevokeToken;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+
+// one message per minute, repeated requests don't flood the mailbox
+const MIN_RESET_INTERVAL_SECONDS: i64 = 60;
+
+#[async_trait]
+pub trait StorePasswordReset {
+    /// NotFound unless the user can reset password by itself
+    async fn get_reset_recipient(&self, username: &str)
+        -> Result<ResetRecipient, AccessModelError>;
+    /// Previous tokens of the user are closed. The token isn't saved and false is returned
+    /// if the user requested another one after `requested_after`
+    async fn save_reset_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expired_at: DateTime<Utc>,
+        requested_after: DateTime<Utc>,
+    ) -> Result<bool, AccessModelError>;
+    /// Closes the token, sets new password and revokes sessions of the user in one transaction,
+    /// returns id of the user and its new token version. NotFound for unknown, used or expired token
+    async fn reset_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<(i32, i32), AccessModelError>;
+    /// Sets new password, closes reset tokens and revokes sessions of the user in one transaction,
+    /// returns new token version
+    async fn set_password(
+        &self,
+        user_id: i32,
+        password_hash: &str,
+    ) -> Result<i32, AccessModelError>;
+}
+
+#[async_trait]
+pub trait Notifier {
+    async fn send(&self, notification: &Notification) -> Result<(), AccessModelError>;
+}
+
+fn reset_error(e: AccessModelError) -> PasswordResetError {
+    match e {
+        AccessModelError::NotFoundError => PasswordResetError::NotFoundError,
+        AccessModelError::TemporaryError => PasswordResetError::TemporaryError,
+        _ => PasswordResetError::FatalError,
+    }
+}
+
+fn new_password_hash(password: &str) -> Result<String, PasswordResetError> {
+    if password.is_empty() {
+        return Err(PasswordResetError::ValidationError(
+            "password must not be empty".to_string(),
+        ));
+    }
+    generate_hash(password).map_err(|_| PasswordResetError::FatalError)
+}
+
+fn reset_notification(
+    config: &PasswordResetConfig,
+    recipient: &str,
+    token: &str,
+    expired_at: DateTime<Utc>,
+) -> Notification {
+    let mut body = format!(
+        "Password reset is requested for your account.\n\nReset token: {}\n",
+        token
+    );
+    if let Some(url) = &config.url {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        body.push_str(&format!(
+            "Reset page: {}{}token={}\n",
+            url, separator, token
+        ));
+    }
+    body.push_str(&format!(
+        "\nThe token can be used once until {}. Ignore this message if you didn't request the reset.\n",
+        expired_at.to_rfc3339()
+    ));
+    Notification {
+        recipient: recipient.to_string(),
+        subject: "Password reset".to_string(),
+        body,
+    }
+}
+
+/// Saves reset token and returns notification with it, None for unknown users and
+/// too frequent requests. Delivery is left to the caller, so the result doesn't disclose
+/// whether the user exists
+pub async fn request_password_reset(
+    reset_storage: &impl StorePasswordReset,
+    config: &PasswordResetConfig,
+    username: &str,
+) -> Result<Option<Notification>, PasswordResetError> {
+    let recipient = match reset_storage.get_reset_recipient(username).await {
+        Ok(recipient) => recipient,
+        Err(AccessModelError::NotFoundError) => return Ok(None),
+        Err(e) => return Err(reset_error(e)),
+    };
+    let token = generate_random_token(32);
+    let now = Utc::now();
+    let expired_at = now + Duration::minutes(i64::from(config.token_minutes));
+    let is_saved = match reset_storage
+        .save_reset_token(
+            recipient.user_id,
+            &hash_token(&token),
+            expired_at,
+            now - Duration::seconds(MIN_RESET_INTERVAL_SECONDS),
+        )
+        .await
+    {
+        Ok(is_saved) => is_saved,
+        Err(AccessModelError::NotFoundError) => return Ok(None),
+        Err(e) => return Err(reset_error(e)),
+    };
+    if !is_saved {
+        info!(
+            "password reset of user {} is requested too often",
+            recipient.user_id
+        );
+        return Ok(None);
+    }
+    Ok(Some(reset_notification(
+        config,
+        &recipient.email,
+        &token,
+        expired_at,
+    )))
+}
+
+/// Delivery failures are only logged, the user requests reset again
+pub async fn send_reset_notification(notifier: &impl Notifier, notification: &Notification) {
+    if notifier.send(notification).await.is_err() {
+        error!("password reset notification isn't delivered");
+    }
+}
+
+/// Sessions of the user are revoked, API keys stay valid
+pub async fn reset_password(
+    reset_storage: &impl StorePasswordReset,
+    revoker: &impl RevokeToken,
+    token: &str,
+    password: &str,
+) -> Result<(), PasswordResetError> {
+    let password_hash = new_password_hash(password)?;
+    let (user_id, token_version) = match reset_storage
+        .reset_password(&hash_token(token), &password_hash)
+        .await
+    {
+        Ok(changed) => changed,
+        Err(AccessModelError::NotFoundError) => return Err(PasswordResetError::VerificationError),
+        Err(e) => return Err(reset_error(e)),
+    };
+    revoker.token_version_changed(user_id, token_version).await;
+    Ok(())
+}
+
+/// Password is set by admin, sessions of the user are revoked
+pub async fn set_user_password(
+    reset_storage: &impl StorePasswordReset,
+    revoker: &impl RevokeToken,
+    user_id: i32,
+    password: &str,
+) -> Result<(), PasswordResetError> {
+    let password_hash = new_password_hash(password)?;
+    let token_version = reset_storage
+        .set_password(user_id, &password_hash)
+        .await
+        .map_err(reset_error)?;
+    revoker.token_version_changed(user_id, token_version).await;
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use crate::common::{SecurityConfig, SignInThrottlingConfig};
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::{verify_credentials, SignInVerification};
+use crate::usecases::users::entities::{
+    AttemptKey, FailedAttempts, SignInAttempt, SignInResult, UserCredentials,
+};
+use crate::usecases::users::errors::{SignError, UserUCError};
+use crate::usecases::users::get_user::FindUserById;
+use crate::usecases::users::mfa::{sign_in_with_mfa, verify_totp_if_enabled, StoreMfa};
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+
+#[async_trait]
+pub trait StoreSignInAttempts {
+    /// Failures after `since`, failures of username cleared by successful sign in or unlock are skipped
+    async fn get_failures(
+        &self,
+        key: &AttemptKey,
+        since: DateTime<Utc>,
+    ) -> Result<FailedAttempts, AccessModelError>;
+    async fn add_failure(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+        attempted_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError>;
+    async fn clear_failures(&self, username: &str) -> Result<(), AccessModelError>;
+}
+
+/// Moment until next attempt is rejected, None if it's allowed
+fn blocked_until(
+    failures: &FailedAttempts,
+    free_failures: u32,
+    max_failures: u32,
+    config: &SignInThrottlingConfig,
+) -> Option<DateTime<Utc>> {
+    let last_attempted_at = failures.last_attempted_at?;
+    let lockout = Duration::seconds(config.lockout_seconds.into());
+    if failures.count >= max_failures {
+        return Some(last_attempted_at + lockout);
+    }
+    if failures.count < free_failures {
+        return None;
+    }
+    let exponent = (failures.count - free_failures).min(30);
+    let delay = Duration::seconds(i64::from(config.base_delay_seconds) << exponent);
+    Some(last_attempted_at + delay.min(lockout))
+}
+
+async fn get_failures(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    key: AttemptKey,
+    since: DateTime<Utc>,
+) -> Result<FailedAttempts, SignError> {
+    match attempts_store.get_failures(&key, since).await {
+        Ok(failures) => Ok(failures),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+/// Rejects attempt with `TooManyAttemptsError` while username or client IP is throttled
+pub async fn check_sign_in_allowed(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    config: &SignInThrottlingConfig,
+    username: &str,
+    client_ip: Option<&str>,
+) -> Result<(), SignError> {
+    let now = Utc::now();
+    let since = now - Duration::seconds(config.window_seconds.into());
+    let failures = get_failures(
+        attempts_store,
+        AttemptKey::Username(username.to_string()),
+        since,
+    )
+    .await?;
+    let mut until = blocked_until(
+        &failures,
+        config.free_failures,
+        config.max_failures_per_username,
+        config,
+    );
+    if let Some(client_ip) = client_ip {
+        let failures = get_failures(
+            attempts_store,
+            AttemptKey::ClientIp(client_ip.to_string()),
+            since,
+        )
+        .await?;
+        // client IP is only locked out, delays would slow down users behind shared NAT
+        let ip_until = blocked_until(
+            &failures,
+            config.max_failures_per_ip,
+            config.max_failures_per_ip,
+            config,
+        );
+        until = until.max(ip_until);
+    }
+    match until {
+        Some(until) if until > now => {
+            let wait = (until - now).num_milliseconds();
+            Err(SignError::TooManyAttemptsError(
+                ((wait + 999) / 1000) as u64,
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Failures of username are cleared only after all factors are passed
+pub async fn sign_in_with_throttling(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    throttling_config: &SignInThrottlingConfig,
+    mfa_store: &impl StoreMfa,
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    security_config: &SecurityConfig,
+    attempt: SignInAttempt,
+) -> Result<SignInResult, SignError> {
+    let client_ip = attempt.client_ip.as_deref();
+    check_sign_in_allowed(
+        attempts_store,
+        throttling_config,
+        &attempt.username,
+        client_ip,
+    )
+    .await?;
+    let result = sign_in_with_mfa(
+        mfa_store,
+        verificator,
+        token_storage,
+        security_config,
+        &attempt.username,
+        &attempt.password,
+    )
+    .await;
+    let stored = match &result {
+        Ok(SignInResult::Signed(_)) => attempts_store.clear_failures(&attempt.username).await,
+        Err(SignError::VerificationError) => {
+            attempts_store
+                .add_failure(&attempt.username, client_ip, Utc::now())
+                .await
+        }
+        _ => Ok(()),
+    };
+    match stored {
+        Ok(_) => result,
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+/// Password and TOTP (if enabled) check of interactive logins outside of sign_in endpoint,
+/// e.g. OAuth login page, failures are counted the same way
+pub async fn verify_login_with_throttling(
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    throttling_config: &SignInThrottlingConfig,
+    mfa_store: &impl StoreMfa,
+    verificator: &impl SignInVerification,
+    attempt: &SignInAttempt,
+    totp_code: Option<&str>,
+) -> Result<UserCredentials, SignError> {
+    let client_ip = attempt.client_ip.as_deref();
+    check_sign_in_allowed(
+        attempts_store,
+        throttling_config,
+        &attempt.username,
+        client_ip,
+    )
+    .await?;
+    let result = match verify_credentials(verificator, &attempt.username, &attempt.password).await {
+        Ok(credentials) => verify_totp_if_enabled(mfa_store, credentials.user_id, totp_code)
+            .await
+            .map(|_| credentials),
+        Err(e) => Err(e),
+    };
+    let stored = match &result {
+        Ok(_) => attempts_store.clear_failures(&attempt.username).await,
+        Err(SignError::VerificationError) => {
+            attempts_store
+                .add_failure(&attempt.username, client_ip, Utc::now())
+                .await
+        }
+        _ => Ok(()),
+    };
+    match stored {
+        Ok(_) => result,
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+/// Clears failures of the user, throttling by client IP stays as is
+pub async fn unlock_user(
+    user_repo: &impl FindUserById,
+    attempts_store: &(impl StoreSignInAttempts + Sync + ?Sized),
+    user_id: i32,
+) -> Result<(), UserUCError> {
+    let user = match user_repo.find_user_by_id(user_id).await {
+        Ok(user) => user,
+        Err(AccessModelError::NotFoundError) => return Err(UserUCError::NotFoundError),
+        Err(AccessModelError::TemporaryError) => return Err(UserUCError::TemporaryError),
+        Err(_) => return Err(UserUCError::FatalError),
+    };
+    match attempts_store.clear_failures(&user.username).await {
+        Ok(_) => Ok(()),
+        Err(AccessModelError::TemporaryError) => Err(UserUCError::TemporaryError),
+        Err(_) => Err(UserUCError::FatalError),
+    }
+}
@@ -0,0 +1,109 @@
+use crate::common::SecurityConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::{
+    generate_random_token, hash_token, issue_tokens, SignInVerification,
+};
+use crate::usecases::users::entities::{
+    RefreshToken, RefreshTokenForCreation, SingnedInfo, TokenSession,
+};
+use crate::usecases::users::errors::SignError;
+
+use async_trait::async_trait;
+use log::warn;
+
+#[async_trait]
+pub trait StoreRefreshToken {
+    async fn save_refresh_token(
+        &self,
+        token: RefreshTokenForCreation,
+    ) -> Result<RefreshToken, AccessModelError>;
+    async fn get_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<RefreshToken, AccessModelError>;
+    // marks token as used, returns NotFoundError if token was already rotated
+    async fn rotate_refresh_token(&self, token_id: i32) -> Result<RefreshToken, AccessModelError>;
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<(), AccessModelError>;
+    async fn revoke_user_refresh_tokens(&self, user_id: i32) -> Result<(), AccessModelError>;
+}
+
+pub async fn issue_refresh_token(
+    token_storage: &impl StoreRefreshToken,
+    config: &SecurityConfig,
+    user_id: i32,
+    family_id: String,
+) -> Result<String, SignError> {
+    let refresh_token = generate_random_token(32);
+    let token_data = RefreshTokenForCreation {
+        token_hash: hash_token(&refresh_token),
+        user_id,
+        family_id,
+        expired_at: chrono::Utc::now()
+            + chrono::Duration::days(config.expired_refresh_token_days.into()),
+    };
+    match token_storage.save_refresh_token(token_data).await {
+        Ok(_) => Ok(refresh_token),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+async fn revoke_family(
+    token_storage: &impl StoreRefreshToken,
+    token: &RefreshToken,
+) -> Result<(), SignError> {
+    warn!(
+        "Reuse of refresh token detected, revoke token family of user {}",
+        token.user_id
+    );
+    match token_storage
+        .revoke_refresh_token_family(&token.family_id)
+        .await
+    {
+        Ok(_) | Err(AccessModelError::NotFoundError) => Ok(()),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+pub async fn refresh_tokens(
+    verificator: &impl SignInVerification,
+    token_storage: &impl StoreRefreshToken,
+    config: &SecurityConfig,
+    refresh_token: &str,
+) -> Result<SingnedInfo, SignError> {
+    let token = match token_storage
+        .get_refresh_token_by_hash(&hash_token(refresh_token))
+        .await
+    {
+        Ok(token) => token,
+        Err(AccessModelError::NotFoundError) => return Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+        Err(_) => return Err(SignError::FatalError),
+    };
+    if token.is_revoked {
+        return Err(SignError::VerificationError);
+    }
+    if token.is_rotated {
+        revoke_family(token_storage, &token).await?;
+        return Err(SignError::VerificationError);
+    }
+    if token.expired_at <= chrono::Utc::now() {
+        return Err(SignError::ExpiredTokenError);
+    }
+    let token = match token_storage.rotate_refresh_token(token.token_id).await {
+        Ok(token) => token,
+        // concurrent request has rotated the same token
+        Err(AccessModelError::NotFoundError) => {
+            revoke_family(token_storage, &token).await?;
+            return Err(SignError::VerificationError);
+        }
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+        Err(_) => return Err(SignError::FatalError),
+    };
+    let session = TokenSession {
+        session_id: token.family_id,
+        token_version: token.token_version,
+    };
+    issue_tokens(verificator, token_storage, config, token.user_id, session).await
+}
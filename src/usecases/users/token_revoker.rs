@@ -0,0 +1,88 @@
+use crate::common::SecurityConfig;
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::crypto::decode_jwt;
+use crate::usecases::users::entities::Claims;
+use crate::usecases::users::errors::{SignError, UserUCError};
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+
+#[async_trait]
+pub trait RevokeToken {
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, AccessModelError>;
+    async fn revoke_token(
+        &self,
+        jti: &str,
+        user_id: i32,
+        expired_at: DateTime<Utc>,
+    ) -> Result<(), AccessModelError>;
+    async fn get_token_version(&self, user_id: i32) -> Result<i32, AccessModelError>;
+    async fn increment_token_version(&self, user_id: i32) -> Result<i32, AccessModelError>;
+    /// Version incremented by another repo together with other changes, e.g. new password
+    async fn token_version_changed(&self, user_id: i32, version: i32);
+}
+
+pub async fn check_token_revocation(
+    revoker: &impl RevokeToken,
+    claims: &Claims,
+) -> Result<(), SignError> {
+    match revoker.get_token_version(claims.user_id).await {
+        Ok(version) if version == claims.ver => (),
+        Ok(_) | Err(AccessModelError::NotFoundError) => return Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+        Err(_) => return Err(SignError::FatalError),
+    };
+    match revoker.is_token_revoked(&claims.jti).await {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(SignError::VerificationError),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+pub async fn sign_out(
+    revoker: &impl RevokeToken,
+    token_storage: &impl StoreRefreshToken,
+    config: &SecurityConfig,
+    jwt_token: &str,
+) -> Result<(), SignError> {
+    let claims = decode_jwt(config, jwt_token)?;
+    // token is accepted until exp plus leeway, so is its revocation kept
+    let expires = claims.exp + i64::from(config.jwt_leeway_seconds);
+    let expired_at = match Utc.timestamp_opt(expires, 0).single() {
+        Some(expired_at) => expired_at,
+        None => return Err(SignError::VerificationError),
+    };
+    match revoker
+        .revoke_token(&claims.jti, claims.user_id, expired_at)
+        .await
+    {
+        Ok(_) => (),
+        Err(AccessModelError::TemporaryError) => return Err(SignError::TemporaryError),
+        Err(_) => return Err(SignError::FatalError),
+    };
+    match token_storage.revoke_refresh_token_family(&claims.sid).await {
+        Ok(_) | Err(AccessModelError::NotFoundError) => Ok(()),
+        Err(AccessModelError::TemporaryError) => Err(SignError::TemporaryError),
+        Err(_) => Err(SignError::FatalError),
+    }
+}
+
+pub async fn revoke_user_sessions(
+    revoker: &impl RevokeToken,
+    token_storage: &impl StoreRefreshToken,
+    user_id: i32,
+) -> Result<(), UserUCError> {
+    match revoker.increment_token_version(user_id).await {
+        Ok(_) => (),
+        Err(AccessModelError::NotFoundError) => return Err(UserUCError::NotFoundError),
+        Err(AccessModelError::TemporaryError) => return Err(UserUCError::TemporaryError),
+        Err(_) => return Err(UserUCError::FatalError),
+    };
+    match token_storage.revoke_user_refresh_tokens(user_id).await {
+        Ok(_) | Err(AccessModelError::NotFoundError) => Ok(()),
+        Err(AccessModelError::TemporaryError) => Err(UserUCError::TemporaryError),
+        Err(_) => Err(UserUCError::FatalError),
+    }
+}
@@ -0,0 +1,66 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+
+// RFC 6238 defaults, the only parameters supported by most authenticator apps
+const SECRET_LENGTH: usize = 20;
+const PERIOD_SECONDS: i64 = 30;
+const DIGITS: usize = 6;
+// accepted clock drift of the device in periods
+const ALLOWED_DRIFT: i64 = 1;
+
+/// New shared secret in base32 without padding
+pub fn generate_secret() -> String {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> String {
+    let label = format!("{}:{}", issuer, account);
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        utf8_percent_encode(&label, NON_ALPHANUMERIC),
+        secret,
+        utf8_percent_encode(issuer, NON_ALPHANUMERIC),
+        DIGITS,
+        PERIOD_SECONDS
+    )
+}
+
+pub fn time_step(unix_time: i64) -> i64 {
+    unix_time.div_euclid(PERIOD_SECONDS)
+}
+
+/// HOTP value (RFC 4226) of raw secret for the time step
+pub fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+/// Returns time step of the matched code, it's used to reject the same code twice
+pub fn verify_code(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let current_step = time_step(unix_time);
+    (current_step - ALLOWED_DRIFT..=current_step + ALLOWED_DRIFT)
+        .find(|step| totp_code(&secret, *step).as_bytes() == code.as_bytes())
+}
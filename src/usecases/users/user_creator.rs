@@ -10,18 +10,45 @@ pub trait CreateUser {
     async fn save_user_in_storage(&self, user: UserForCreation) -> Result<User, AccessModelError>;
 }
 
+/// Address is put into headers of notifications, so brackets and line breaks are rejected
+fn validate_email(email: &str) -> Result<(), UserUCError> {
+    let is_valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !email
+                    .chars()
+                    .any(|c| c == '<' || c == '>' || c.is_whitespace() || c.is_control())
+        }
+        None => false,
+    };
+    match is_valid {
+        true => Ok(()),
+        false => Err(UserUCError::ValidationError(format!(
+            "email {:?} is invalid",
+            email
+        ))),
+    }
+}
+
 pub async fn create_new_user(
     user_access_model: &impl CreateUser,
     username: String,
     password: String,
+    email: Option<String>,
 ) -> Result<User, UserUCError> {
+    if let Some(email) = &email {
+        validate_email(email)?;
+    }
     let hash = match generate_hash(&password) {
         Ok(hash) => hash,
         Err(_) => return Err(UserUCError::FatalError),
     };
     let user_data = UserForCreation {
-        username: username,
+        username,
         password_hash: hash,
+        email,
     };
     match user_access_model.save_user_in_storage(user_data).await {
         Ok(user) => Ok(user),
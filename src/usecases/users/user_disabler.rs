@@ -0,0 +1,45 @@
+use crate::usecases::base_entities::AccessModelError;
+use crate::usecases::users::entities::User;
+use crate::usecases::users::errors::UserUCError;
+use crate::usecases::users::token_refresher::StoreRefreshToken;
+use crate::usecases::users::token_revoker::{revoke_user_sessions, RevokeToken};
+
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait SetUserEnabled {
+    async fn set_user_enabled(&self, user_id: i32, enabled: bool)
+        -> Result<User, AccessModelError>;
+}
+
+pub async fn enable_user(
+    user_repo: &impl SetUserEnabled,
+    user_id: i32,
+) -> Result<User, UserUCError> {
+    set_user_enabled(user_repo, user_id, true).await
+}
+
+/// Disabled user can't sign in, already issued tokens are revoked
+pub async fn disable_user(
+    user_repo: &impl SetUserEnabled,
+    revoker: &impl RevokeToken,
+    token_storage: &impl StoreRefreshToken,
+    user_id: i32,
+) -> Result<User, UserUCError> {
+    let user = set_user_enabled(user_repo, user_id, false).await?;
+    revoke_user_sessions(revoker, token_storage, user_id).await?;
+    Ok(user)
+}
+
+async fn set_user_enabled(
+    user_repo: &impl SetUserEnabled,
+    user_id: i32,
+    enabled: bool,
+) -> Result<User, UserUCError> {
+    match user_repo.set_user_enabled(user_id, enabled).await {
+        Ok(user) => Ok(user),
+        Err(AccessModelError::NotFoundError) => Err(UserUCError::NotFoundError),
+        Err(AccessModelError::TemporaryError) => Err(UserUCError::TemporaryError),
+        Err(_) => Err(UserUCError::FatalError),
+    }
+}
@@ -0,0 +1,144 @@
+use actix_web::dev::Service;
+use actix_web::http::header;
+use actix_web::test;
+
+use authust::common::Config;
+use authust::usecases::api_keys::entities::{ApiKey, CreatedApiKey};
+
+use serde_json::json;
+
+mod utils;
+use utils::{
+    init_test_resources, init_test_service, init_test_service_with_config, test_delete, test_get,
+    test_post,
+    IntenalRoles::{RoleManager, RoleStaff},
+};
+
+fn with_api_key(req: test::TestRequest, token: &str) -> test::TestRequest {
+    req.insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+async fn test_api_key_permissions() {
+    let app = init_test_service().await;
+    let req = test_post("/api/v1/api_keys", RoleManager)
+        .set_json(json!({
+            "name": "reports",
+            "expires_in_days": 30,
+            "permissions": ["READ_USER"],
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let created: CreatedApiKey = test::read_body_json(resp).await;
+    assert!(created.token.starts_with("pat_"));
+    assert_eq!(
+        created.api_key.permissions,
+        Some(vec!["READ_USER".to_string()])
+    );
+
+    let req = with_api_key(test::TestRequest::get(), &created.token)
+        .uri("/api/v1/users/1")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    // WRITE_USER of the manager isn't granted to the key
+    let req = with_api_key(test::TestRequest::delete(), &created.token)
+        .uri("/api/v1/users/3")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    // keys aren't managed with keys
+    let req = with_api_key(test::TestRequest::post(), &created.token)
+        .uri("/api/v1/api_keys")
+        .set_json(json!({"name": "other", "expires_in_days": 30}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = with_api_key(test::TestRequest::get(), "pat_unknown")
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+}
+
+#[actix_web::test]
+async fn test_api_key_creation_validation() {
+    let app = init_test_service().await;
+    for (body, status) in [
+        (json!({"name": "ci", "expires_in_days": 30}), 201),
+        (json!({"name": "ci", "expires_in_days": 30}), 409),
+        (json!({"name": " ", "expires_in_days": 30}), 400),
+        (json!({"name": "zero", "expires_in_days": 0}), 400),
+        (json!({"name": "long", "expires_in_days": 366}), 400),
+        // permission which isn't granted to the user
+        (
+            json!({"name": "admin", "expires_in_days": 30, "permissions": ["NOT_GRANTED"]}),
+            403,
+        ),
+    ] {
+        let req = test_post("/api/v1/api_keys", RoleStaff)
+            .set_json(body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+}
+
+#[actix_web::test]
+async fn test_api_key_listing_and_revocation() {
+    let resources = init_test_resources().await;
+    let app = init_test_service_with_config(Config::create_config()).await;
+    let mut tokens = vec![];
+    for name in ["first", "second"] {
+        let req = test_post("/api/v1/api_keys", RoleManager)
+            .set_json(json!({"name": name, "expires_in_days": 1}))
+            .to_request();
+        let created: CreatedApiKey = test::call_and_read_body_json(&app, req).await;
+        tokens.push(created);
+    }
+    let req = with_api_key(test::TestRequest::get(), &tokens[0].token)
+        .uri("/api/v1/users/1")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let req = test_get("/api/v1/api_keys", RoleManager).to_request();
+    let api_keys: Vec<ApiKey> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(api_keys.len(), 2);
+    assert!(api_keys[0].last_used_at.is_some());
+    assert!(api_keys[1].last_used_at.is_none());
+    // keys of other users are not listed and can't be revoked
+    let req = test_get("/api/v1/api_keys", RoleStaff).to_request();
+    let api_keys: Vec<ApiKey> = test::call_and_read_body_json(&app, req).await;
+    assert!(api_keys.is_empty());
+    let uri = format!("/api/v1/api_keys/{}", tokens[0].api_key.api_key_id);
+    let req = test_delete(&uri, RoleStaff).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    let req = test_delete(&uri, RoleManager).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 204);
+    let req = test_delete(&uri, RoleManager).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let req = with_api_key(test::TestRequest::get(), &tokens[0].token)
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+
+    let client = resources.db_pool.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE api_keys SET expired_at=now() - interval '1 minute' WHERE api_key_id=$1",
+            &[&tokens[1].api_key.api_key_id],
+        )
+        .await
+        .unwrap();
+    let req = with_api_key(test::TestRequest::get(), &tokens[1].token)
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+    // the name of revoked key can be used again
+    let req = test_post("/api/v1/api_keys", RoleManager)
+        .set_json(json!({"name": "first", "expires_in_days": 1}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+}
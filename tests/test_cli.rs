@@ -0,0 +1,107 @@
+use authust::cli::{
+    run_permission, run_role, run_token, run_user, PermissionAction, RoleAction, TokenAction,
+    UserAction,
+};
+use authust::common::Config;
+use authust::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use authust::storage::postgres::user_repo::UserRepo;
+use authust::usecases::users::crypto::{sign_in, SignInVerification};
+
+mod utils;
+use utils::init_test_resources;
+
+#[actix_web::test]
+async fn test_cli_bootstrap_admin() {
+    let config = Config::create_config();
+    let resources = init_test_resources().await;
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    let token_repo = RefreshTokenRepo::new(resources.db_pool.clone());
+
+    let action = UserAction::Create {
+        username: "cli_admin".to_string(),
+        password: Some("cli-password".to_string()),
+        email: None,
+    };
+    run_user(&config, &resources, action).await.unwrap();
+    let signed_info = sign_in(
+        &user_repo,
+        &token_repo,
+        &config.security_config,
+        "cli_admin".to_string(),
+        "cli-password".to_string(),
+    )
+    .await
+    .unwrap();
+
+    let client = resources.db_pool.get().await.unwrap();
+    let admin_role_id: i32 = client
+        .query_one("SELECT find_role_id_by_name('ROLE_AUTH_ADMIN')", &[])
+        .await
+        .unwrap()
+        .get(0);
+    let action = RoleAction::BindMember {
+        role_id: admin_role_id,
+        user_id: signed_info.user_id,
+    };
+    run_role(&resources, action).await.unwrap();
+    let roles = user_repo
+        .get_user_roles(&signed_info.user_id)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(roles, vec!["ROLE_AUTH_ADMIN".to_string()]);
+}
+
+#[actix_web::test]
+async fn test_cli_create_user_rejects_invalid_email() {
+    let config = Config::create_config();
+    let resources = init_test_resources().await;
+    let action = UserAction::Create {
+        username: "cli_user".to_string(),
+        password: Some("cli-password".to_string()),
+        email: Some("cli@example.com>\r\nBcc: <other@example.com".to_string()),
+    };
+    assert!(run_user(&config, &resources, action).await.is_err());
+}
+
+#[actix_web::test]
+async fn test_cli_create_permission_and_bind() {
+    let resources = init_test_resources().await;
+    let action = PermissionAction::Create {
+        name: "CLI_PERM".to_string(),
+    };
+    run_permission(&resources, action).await.unwrap();
+    let action = PermissionAction::Create {
+        name: "CLI_PERM".to_string(),
+    };
+    assert!(run_permission(&resources, action).await.is_err());
+
+    let action = RoleAction::Create {
+        name: "CLI_ROLE".to_string(),
+    };
+    run_role(&resources, action).await.unwrap();
+    let client = resources.db_pool.get().await.unwrap();
+    let row = client
+        .query_one(
+            "SELECT find_role_id_by_name('CLI_ROLE'), find_perm_id_by_name('CLI_PERM')",
+            &[],
+        )
+        .await
+        .unwrap();
+    let action = RoleAction::BindPermission {
+        role_id: row.get(0),
+        permission_id: row.get(1),
+    };
+    run_role(&resources, action).await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_cli_issue_token() {
+    let config = Config::create_config();
+    let resources = init_test_resources().await;
+    let action = TokenAction::Issue { user: 2 };
+    run_token(&config, &resources, action).await.unwrap();
+
+    let action = TokenAction::Issue { user: 100 };
+    assert!(run_token(&config, &resources, action).await.is_err());
+}
@@ -0,0 +1,191 @@
+use authust::common::config_source::{read_config_file, ConfigError, ConfigFieldError};
+use authust::common::{Config, NotifierKind};
+use log::LevelFilter;
+use std::collections::HashMap;
+use std::path::Path;
+
+fn env_values(values: &[(&str, &str)]) -> HashMap<String, String> {
+    values
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_config_defaults() {
+    let env = env_values(&[
+        ("PG_USER", "postgres"),
+        ("PG_PASSWORD", "dbpass"),
+        ("PG_HOST", "localhost"),
+        ("PG_DBNAME", "db"),
+        ("SECRET_KEY", "some-secret"),
+    ]);
+    let config = Config::from_values(HashMap::new(), env).unwrap();
+    assert_eq!(config.server_config.host, "127.0.0.1");
+    assert_eq!(config.server_config.port, 8080);
+    assert!(config.server_config.workers.is_none());
+    assert_eq!(config.server_config.log_level, LevelFilter::Info);
+    assert_eq!(config.database_config.port, 5432);
+    assert_eq!(config.database_config.pool_max_size, 16);
+    assert!(!config.database_config.migrate_on_startup);
+    assert_eq!(config.security_config.expired_access_token_minutes, 15);
+    assert_eq!(config.security_config.jwt_keyring.current().kid, "default");
+}
+
+#[test]
+fn test_config_env_overrides_file() {
+    let file_values = read_config_file(Path::new("config.example.toml")).unwrap();
+    let env = env_values(&[("PG_PORT", "6543"), ("LOG_LEVEL", "warn")]);
+    let config = Config::from_values(file_values, env).unwrap();
+    assert_eq!(config.database_config.port, 6543);
+    assert_eq!(config.database_config.user, "postgres");
+    assert!(config.database_config.migrate_on_startup);
+    assert_eq!(config.server_config.log_level, LevelFilter::Warn);
+}
+
+#[test]
+fn test_config_from_yaml() {
+    let file_values = read_config_file(Path::new("tests/configs/authust.yaml")).unwrap();
+    let config = Config::from_values(file_values, HashMap::new()).unwrap();
+    assert_eq!(config.server_config.port, 9090);
+    assert_eq!(config.server_config.workers.unwrap().get(), 2);
+    assert_eq!(config.server_config.log_level, LevelFilter::Debug);
+    let keyring = &config.security_config.jwt_keyring;
+    assert_eq!(keyring.current().kid, "ed-key");
+    assert!(keyring.find("legacy").is_some());
+}
+
+#[test]
+fn test_config_reports_all_errors() {
+    let file_values = read_config_file(Path::new("tests/configs/broken.toml")).unwrap();
+    let env = env_values(&[("PG_POOL_MAX_SIZE", "-1")]);
+    let errors = match Config::from_values(file_values, env) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    let missing = |key: &str, env: &str| ConfigFieldError::MissingValue {
+        key: key.to_string(),
+        env: env.to_string(),
+    };
+    assert_eq!(errors.len(), 6);
+    assert!(errors.contains(&missing("database.user", "PG_USER")));
+    assert!(errors.contains(&missing("database.password", "PG_PASSWORD")));
+    assert!(errors.contains(&missing("security.secret_key", "SECRET_KEY")));
+    assert!(errors.contains(&ConfigFieldError::UnknownKey("database.usr".to_string())));
+    let invalid_keys: Vec<&str> = errors
+        .iter()
+        .filter_map(|error| match error {
+            ConfigFieldError::InvalidValue { key, .. } => Some(key.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(invalid_keys, vec!["server.port", "database.pool_max_size"]);
+}
+
+#[test]
+fn test_config_federation_providers() {
+    let file_values = read_config_file(Path::new("config.example.toml")).unwrap();
+    let env = env_values(&[
+        ("FEDERATION_PROVIDERS", "corp, bad-name"),
+        ("FEDERATION_CORP_ISSUER", "https://idp.example.com"),
+        ("FEDERATION_CORP_CLIENT_ID", "authust"),
+        (
+            "FEDERATION_CORP_AUTHORIZATION_ENDPOINT",
+            "https://idp.example.com/authorize",
+        ),
+        (
+            "FEDERATION_CORP_TOKEN_ENDPOINT",
+            "https://idp.example.com/token",
+        ),
+        ("FEDERATION_CORP_CREATE_USERS", "true"),
+    ]);
+    let errors = match Config::from_values(file_values.clone(), env.clone()) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(errors.contains(&ConfigFieldError::MissingValue {
+        key: "federation.corp.jwks_uri".to_string(),
+        env: "FEDERATION_CORP_JWKS_URI".to_string(),
+    }));
+
+    let mut env = env;
+    env.insert("FEDERATION_PROVIDERS".to_string(), "corp".to_string());
+    env.insert(
+        "FEDERATION_CORP_JWKS_URI".to_string(),
+        "https://idp.example.com/jwks".to_string(),
+    );
+    let config = Config::from_values(file_values, env).unwrap();
+    let provider = &config.federation_config.providers["corp"];
+    assert_eq!(provider.client_id, "authust");
+    assert!(provider.client_secret.is_none());
+    assert!(provider.create_users);
+    assert_eq!(provider.scope, "openid profile");
+    assert_eq!(provider.username_claim, "preferred_username");
+    assert_eq!(
+        provider.redirect_uri,
+        format!(
+            "{}/auth/v1/federation/corp/callback",
+            config.security_config.jwt_issuer.trim_end_matches('/')
+        )
+    );
+}
+
+#[test]
+fn test_config_ldap_backend() {
+    let file_values = read_config_file(Path::new("config.example.toml")).unwrap();
+    let config = Config::from_values(file_values.clone(), HashMap::new()).unwrap();
+    assert!(config.ldap_config.is_none());
+
+    let env = env_values(&[
+        ("SIGN_IN_BACKEND", "local_then_ldap"),
+        ("LDAP_GROUP_ROLES", "Admins=ROLE_AUTH_ADMIN, staff"),
+    ]);
+    let errors = match Config::from_values(file_values.clone(), env) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    assert_eq!(errors.len(), 3);
+    assert!(errors.contains(&ConfigFieldError::MissingValue {
+        key: "ldap.url".to_string(),
+        env: "LDAP_URL".to_string(),
+    }));
+
+    let env = env_values(&[
+        ("SIGN_IN_BACKEND", "ldap"),
+        ("LDAP_URL", "ldaps://ldap.example.com"),
+        ("LDAP_BASE_DN", "dc=example,dc=com"),
+        ("LDAP_GROUP_ROLES", "Admins=ROLE_AUTH_ADMIN"),
+    ]);
+    let config = Config::from_values(file_values, env).unwrap();
+    let ldap_config = config.ldap_config.unwrap();
+    assert!(!ldap_config.local_first);
+    assert_eq!(ldap_config.user_filter, "(uid={username})");
+    assert_eq!(ldap_config.group_roles["admins"], "ROLE_AUTH_ADMIN");
+}
+
+#[test]
+fn test_config_notifier() {
+    let file_values = read_config_file(Path::new("config.example.toml")).unwrap();
+    let config = Config::from_values(file_values.clone(), HashMap::new()).unwrap();
+    assert_eq!(config.notifier_config.kind, NotifierKind::Log);
+    assert_eq!(config.password_reset_config.token_minutes, 30);
+
+    let env = env_values(&[("NOTIFIER", "file")]);
+    let errors = match Config::from_values(file_values.clone(), env) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    assert_eq!(
+        errors,
+        vec![ConfigFieldError::MissingValue {
+            key: "notifier.file_path".to_string(),
+            env: "NOTIFIER_FILE_PATH".to_string(),
+        }]
+    );
+
+    let env = env_values(&[("NOTIFIER", "smtp"), ("SMTP_PORT", "1025")]);
+    let config = Config::from_values(file_values, env).unwrap();
+    assert_eq!(config.notifier_config.kind, NotifierKind::Smtp);
+    assert_eq!(config.notifier_config.smtp_port, 1025);
+}
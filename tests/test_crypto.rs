@@ -1,11 +1,265 @@
-use argon2;
+use authust::common::SecurityConfig;
 use authust::usecases::users::crypto;
+use authust::usecases::users::entities::{Claims, TokenSession};
+use authust::usecases::users::errors::{JwtKeyError, SignError};
+use authust::usecases::users::jwt_keys::{JwtKey, JwtKeyring};
+use jsonwebtoken::jwk::AlgorithmParameters;
 mod constants;
 
+fn test_security_config() -> SecurityConfig {
+    SecurityConfig {
+        jwt_keyring: JwtKey::from_secret("default", "some-secret").into(),
+        expired_access_token_minutes: 15,
+        expired_refresh_token_days: 14,
+        jwt_issuer: String::from("authust"),
+        jwt_audience: String::from("authust"),
+        jwt_leeway_seconds: 30,
+        revocation_cache_seconds: 10,
+    }
+}
+
+fn test_claims(config: &SecurityConfig, expired_in: chrono::Duration) -> Claims {
+    Claims::new(
+        constants::TEST_USER_ID_ADMIN,
+        vec![],
+        config.jwt_issuer.clone(),
+        config.jwt_audience.clone(),
+        expired_in,
+        crypto::generate_random_token(16),
+        TokenSession {
+            session_id: crypto::generate_random_token(16),
+            token_version: 0,
+        },
+    )
+}
+
 #[test]
 fn test_generate_hash() {
-    let hash = crypto::generate_hash(&constants::TEST_PASSWORD).unwrap();
-    assert_eq!(hash.to_string(), constants::TEST_PASSWORD_HASH.to_string());
+    let hash = crypto::generate_hash(constants::TEST_PASSWORD).unwrap();
     let matches = argon2::verify_encoded(&hash, constants::TEST_PASSWORD.as_bytes()).unwrap();
     assert!(matches);
 }
+
+#[test]
+fn test_generate_hash_uses_random_salt() {
+    let first_hash = crypto::generate_hash(constants::TEST_PASSWORD).unwrap();
+    let second_hash = crypto::generate_hash(constants::TEST_PASSWORD).unwrap();
+    assert_ne!(first_hash, second_hash);
+    assert!(crypto::verify_hash(&first_hash, constants::TEST_PASSWORD).unwrap());
+    assert!(crypto::verify_hash(&second_hash, constants::TEST_PASSWORD).unwrap());
+}
+
+#[test]
+fn test_verify_hash() {
+    assert!(crypto::verify_hash(constants::TEST_PASSWORD_HASH, constants::TEST_PASSWORD).unwrap());
+    assert!(!crypto::verify_hash(constants::TEST_PASSWORD_HASH, "wrong_password").unwrap());
+    assert!(crypto::verify_hash("not_a_phc_string", constants::TEST_PASSWORD).is_err());
+}
+
+#[test]
+fn test_dummy_password_hash() {
+    assert!(!crypto::verify_hash(crypto::DUMMY_PASSWORD_HASH, constants::TEST_PASSWORD).unwrap());
+}
+
+#[test]
+fn test_decode_jwt() {
+    let config = test_security_config();
+    let session = TokenSession {
+        session_id: crypto::generate_random_token(16),
+        token_version: 0,
+    };
+    let jwt =
+        crypto::generate_jwt(&config, constants::TEST_USER_ID_ADMIN, vec![], session).unwrap();
+    let claims = crypto::decode_jwt(&config, &jwt).unwrap();
+    assert_eq!(claims.user_id, constants::TEST_USER_ID_ADMIN);
+    assert_eq!(claims.jti.len(), 32);
+}
+
+#[test]
+fn test_decode_jwt_expired() {
+    let config = test_security_config();
+    let claims = test_claims(&config, chrono::Duration::seconds(-60));
+    let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::ExpiredTokenError)
+    ));
+}
+
+#[test]
+fn test_decode_jwt_expired_within_leeway() {
+    let config = test_security_config();
+    let claims = test_claims(&config, chrono::Duration::seconds(-10));
+    let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+    assert!(crypto::decode_jwt(&config, &jwt).is_ok());
+}
+
+#[test]
+fn test_decode_jwt_not_yet_valid() {
+    let config = test_security_config();
+    let mut claims = test_claims(&config, chrono::Duration::days(1));
+    claims.nbf += 3600;
+    let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::VerificationError)
+    ));
+}
+
+#[test]
+fn test_decode_jwt_wrong_issuer_or_audience() {
+    let config = test_security_config();
+    let mut claims = test_claims(&config, chrono::Duration::days(1));
+    claims.iss = String::from("another_issuer");
+    let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::VerificationError)
+    ));
+
+    let mut claims = test_claims(&config, chrono::Duration::days(1));
+    claims.aud = String::from("another_audience");
+    let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::VerificationError)
+    ));
+}
+
+fn asymmetric_security_config(algorithm: &str) -> SecurityConfig {
+    let path = format!("tests/keys/{}.pem", algorithm.to_lowercase());
+    SecurityConfig {
+        jwt_keyring: JwtKey::from_pem_file(algorithm, algorithm, &path)
+            .unwrap()
+            .into(),
+        ..test_security_config()
+    }
+}
+
+#[test]
+fn test_decode_jwt_asymmetric() {
+    for algorithm in ["RS256", "ES256", "EdDSA"] {
+        let config = asymmetric_security_config(algorithm);
+        let claims = test_claims(&config, chrono::Duration::days(1));
+        let jwt = crypto::encode_jwt(&config, &claims).unwrap();
+        let header = jsonwebtoken::decode_header(&jwt).unwrap();
+        assert_eq!(format!("{:?}", header.alg), algorithm);
+        assert_eq!(header.kid.unwrap(), algorithm);
+        let decoded = crypto::decode_jwt(&config, &jwt).unwrap();
+        assert_eq!(decoded.jti, claims.jti);
+    }
+}
+
+#[test]
+fn test_decode_jwt_signed_by_another_key() {
+    let config = asymmetric_security_config("RS256");
+    let claims = test_claims(&config, chrono::Duration::days(1));
+
+    let hmac_config = SecurityConfig {
+        jwt_keyring: JwtKey::from_secret("RS256", "some-secret").into(),
+        ..test_security_config()
+    };
+    let jwt = crypto::encode_jwt(&hmac_config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::VerificationError)
+    ));
+
+    let es_config = SecurityConfig {
+        jwt_keyring: JwtKey::from_pem_file("RS256", "ES256", "tests/keys/es256.pem")
+            .unwrap()
+            .into(),
+        ..test_security_config()
+    };
+    let jwt = crypto::encode_jwt(&es_config, &claims).unwrap();
+    assert!(matches!(
+        crypto::decode_jwt(&config, &jwt),
+        Err(SignError::VerificationError)
+    ));
+}
+
+#[test]
+fn test_jwt_key_public_jwk() {
+    assert!(test_security_config().jwt_keyring.public_jwks().is_empty());
+
+    let key = JwtKey::from_pem_file("ed-key", "EdDSA", "tests/keys/eddsa.pem").unwrap();
+    let jwk = key.public_jwk().unwrap();
+    assert_eq!(jwk.common.key_id.as_deref(), Some("ed-key"));
+    assert!(matches!(
+        jwk.algorithm,
+        AlgorithmParameters::OctetKeyPair(_)
+    ));
+
+    assert!(matches!(
+        JwtKey::from_pem_file("rs-key", "ES256", "tests/keys/rs256.pem"),
+        Err(JwtKeyError::InvalidKeyError(_))
+    ));
+    assert!(matches!(
+        JwtKey::from_pem_file("rs-key", "HS256", "tests/keys/rs256.pem"),
+        Err(JwtKeyError::UnsupportedAlgorithmError(_))
+    ));
+}
+
+fn keyring_security_config(current: JwtKey, verification_keys: Vec<JwtKey>) -> SecurityConfig {
+    SecurityConfig {
+        jwt_keyring: JwtKeyring::new(current, verification_keys).unwrap(),
+        ..test_security_config()
+    }
+}
+
+#[test]
+fn test_decode_jwt_key_rotation() {
+    let old_key = JwtKey::from_file("old", "HS256", "tests/keys/hs256.key").unwrap();
+    let new_key = JwtKey::from_pem_file("new", "EdDSA", "tests/keys/eddsa.pem").unwrap();
+
+    let old_config = keyring_security_config(old_key.clone(), vec![]);
+    let claims = test_claims(&old_config, chrono::Duration::days(1));
+    let old_jwt = crypto::encode_jwt(&old_config, &claims).unwrap();
+
+    // new key is rolled out as verification key first, then becomes current one
+    let rollout_config = keyring_security_config(old_key.clone(), vec![new_key.clone()]);
+    let rotated_config = keyring_security_config(new_key.clone(), vec![old_key]);
+    let new_jwt = crypto::encode_jwt(&rotated_config, &claims).unwrap();
+    assert_eq!(
+        jsonwebtoken::decode_header(&new_jwt).unwrap().kid.unwrap(),
+        "new"
+    );
+    assert!(crypto::decode_jwt(&rollout_config, &new_jwt).is_ok());
+    assert!(crypto::decode_jwt(&rotated_config, &old_jwt).is_ok());
+    assert!(crypto::decode_jwt(&rotated_config, &new_jwt).is_ok());
+
+    let retired_config = keyring_security_config(new_key, vec![]);
+    assert!(matches!(
+        crypto::decode_jwt(&retired_config, &old_jwt),
+        Err(SignError::VerificationError)
+    ));
+    assert!(crypto::decode_jwt(&retired_config, &new_jwt).is_ok());
+}
+
+#[test]
+fn test_jwt_keyring() {
+    let current = JwtKey::from_pem_file("current", "ES256", "tests/keys/es256.pem").unwrap();
+    let keyring = JwtKeyring::new(
+        current.clone(),
+        vec![
+            JwtKey::from_pem_file("old", "RS256", "tests/keys/rs256.pem").unwrap(),
+            JwtKey::from_file("older", "HS256", "tests/keys/hs256.key").unwrap(),
+        ],
+    )
+    .unwrap();
+    assert_eq!(keyring.current().kid, "current");
+    assert_eq!(keyring.find("older").unwrap().kid, "older");
+    assert!(keyring.find("unknown").is_none());
+    let kids: Vec<String> = keyring
+        .public_jwks()
+        .into_iter()
+        .map(|jwk| jwk.common.key_id.unwrap())
+        .collect();
+    assert_eq!(kids, vec!["current", "old"]);
+
+    let duplicate = JwtKey::from_secret("current", "some-secret");
+    assert!(matches!(
+        JwtKeyring::new(current, vec![duplicate]),
+        Err(JwtKeyError::DuplicateKeyIdError(_))
+    ));
+}
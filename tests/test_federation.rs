@@ -0,0 +1,415 @@
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::{test, web, App, HttpRequest, HttpResponse, HttpServer};
+use authust::common::{Config, UpstreamProvider};
+use authust::storage::postgres::federation_repo::FederationRepo;
+use authust::usecases::federation::entities::FederatedLoginForCreation;
+use authust::usecases::federation::errors::FederationError;
+use authust::usecases::federation::federated_sign_in::{link_user_identity, StoreFederatedLogin};
+use authust::usecases::oauth::authorization_code::code_challenge_s256;
+use authust::usecases::users::crypto::{decode_jwt, hash_token};
+use authust::usecases::users::entities::SingnedInfo;
+use authust::usecases::users::jwt_keys::JwtKey;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{encode, Header};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+mod utils;
+use utils::constants::TEST_USER_ID_MANAGER;
+use utils::{init_test_resources, init_test_service_with_config};
+
+const PROVIDER: &str = "corp";
+const ISSUER: &str = "https://idp.example.com";
+const CLIENT_ID: &str = "authust";
+const CLIENT_SECRET: &str = "upstream-secret";
+
+type ClaimsChange = fn(&mut Value);
+
+/// In-process identity provider, codes are registered by tests together with id_token claims
+struct MockIdp {
+    key: JwtKey,
+    codes: Mutex<HashMap<String, (Value, String)>>,
+}
+
+async fn mock_token(
+    idp: web::Data<MockIdp>,
+    req: HttpRequest,
+    form: web::Form<HashMap<String, String>>,
+) -> HttpResponse {
+    let basic = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET))
+    );
+    if req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|h| h.as_bytes())
+        != Some(basic.as_bytes())
+    {
+        return HttpResponse::Unauthorized().json(json!({"error": "invalid_client"}));
+    }
+    let code = form.get("code").cloned().unwrap_or_default();
+    let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+    let (claims, code_challenge) = match idp.codes.lock().unwrap().remove(&code) {
+        Some(registered) => registered,
+        None => return HttpResponse::BadRequest().json(json!({"error": "invalid_grant"})),
+    };
+    if form.get("grant_type").map(String::as_str) != Some("authorization_code")
+        || code_challenge_s256(&verifier) != code_challenge
+    {
+        return HttpResponse::BadRequest().json(json!({"error": "invalid_grant"}));
+    }
+    let mut header = Header::new(idp.key.algorithm);
+    header.kid = Some(idp.key.kid.clone());
+    let id_token = encode(&header, &claims, idp.key.encoding_key()).unwrap();
+    HttpResponse::Ok().json(json!({
+        "access_token": "upstream-access-token",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+}
+
+async fn mock_jwks(idp: web::Data<MockIdp>) -> HttpResponse {
+    HttpResponse::Ok().json(JwkSet {
+        keys: vec![idp.key.public_jwk().unwrap().clone()],
+    })
+}
+
+async fn start_mock_idp() -> (web::Data<MockIdp>, String) {
+    let idp = web::Data::new(MockIdp {
+        key: JwtKey::from_pem_file("idp-key", "RS256", "tests/keys/rs256.pem").unwrap(),
+        codes: Mutex::new(HashMap::new()),
+    });
+    let app_idp = idp.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_idp.clone())
+            .route("/token", web::post().to(mock_token))
+            .route("/jwks", web::get().to(mock_jwks))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let base_url = format!("http://{}", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    (idp, base_url)
+}
+
+fn config_with_provider(base_url: &str, create_users: bool) -> Config {
+    let mut config = Config::create_config();
+    let provider = UpstreamProvider {
+        name: PROVIDER.to_string(),
+        issuer: ISSUER.to_string(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        authorization_endpoint: format!("{}/authorize", ISSUER),
+        token_endpoint: format!("{}/token", base_url),
+        jwks_uri: format!("{}/jwks", base_url),
+        redirect_uri: "https://authust.example.com/auth/v1/federation/corp/callback".to_string(),
+        scope: "openid profile".to_string(),
+        username_claim: "preferred_username".to_string(),
+        create_users,
+    };
+    config
+        .federation_config
+        .providers
+        .insert(PROVIDER.to_string(), provider);
+    config
+}
+
+fn upstream_claims(subject: &str, username: &str, nonce: &str) -> Value {
+    let now = Utc::now().timestamp();
+    json!({
+        "iss": ISSUER,
+        "aud": CLIENT_ID,
+        "sub": subject,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": nonce,
+        "preferred_username": username,
+    })
+}
+
+fn query_param(location: &str, name: &str) -> String {
+    location
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix(&format!("{}=", name)))
+        .unwrap()
+        .to_string()
+}
+
+/// Starts login and returns location of the redirect with the state cookie
+async fn start_login(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+) -> (String, Cookie<'static>) {
+    let req = test::TestRequest::get()
+        .uri("/auth/v1/federation/corp/login")
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), 302);
+    let location = resp
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let cookie = resp
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "federation_state")
+        .unwrap()
+        .into_owned();
+    (location, cookie)
+}
+
+fn callback_request(
+    code: &str,
+    state: &str,
+    cookie: Option<Cookie<'static>>,
+) -> actix_http::Request {
+    let req = test::TestRequest::get().uri(&format!(
+        "/auth/v1/federation/corp/callback?code={}&state={}",
+        code, state
+    ));
+    match cookie {
+        Some(cookie) => req.cookie(cookie).to_request(),
+        None => req.to_request(),
+    }
+}
+
+/// Registers code of the login in the provider, lets the test adjust claims for the nonce
+fn register_code(idp: &MockIdp, location: &str, claims: impl FnOnce(&str) -> Value) -> String {
+    let code = format!("code-{}", query_param(location, "state"));
+    idp.codes.lock().unwrap().insert(
+        code.clone(),
+        (
+            claims(&query_param(location, "nonce")),
+            query_param(location, "code_challenge"),
+        ),
+    );
+    code
+}
+
+/// Starts login, lets the test adjust claims for the nonce and returns callback response with state
+async fn federated_sign_in(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+    idp: &MockIdp,
+    claims: impl FnOnce(&str) -> Value,
+) -> (ServiceResponse, String) {
+    let (location, cookie) = start_login(app).await;
+    assert!(location.starts_with("https://idp.example.com/authorize?"));
+    assert_eq!(query_param(&location, "client_id"), CLIENT_ID);
+    assert_eq!(query_param(&location, "code_challenge_method"), "S256");
+    let state = query_param(&location, "state");
+    assert_eq!(cookie.value(), state);
+    let code = register_code(idp, &location, claims);
+    let req = callback_request(&code, &state, Some(cookie));
+    (test::call_service(app, req).await, state)
+}
+
+#[actix_web::test]
+async fn test_federated_sign_in_creates_user() {
+    let (idp, base_url) = start_mock_idp().await;
+    let config = config_with_provider(&base_url, true);
+    let app = init_test_service_with_config(config.clone()).await;
+
+    let (resp, state) =
+        federated_sign_in(&app, &idp, |nonce| upstream_claims("u-1", "alice", nonce)).await;
+    assert_eq!(resp.status(), 200);
+    let signed_info: SingnedInfo = test::read_body_json(resp).await;
+    let claims = decode_jwt(&config.security_config, &signed_info.jwt_token).unwrap();
+    assert_eq!(claims.user_id, signed_info.user_id);
+
+    // linked identity returns the same user even if upstream username is changed
+    let (resp, _) =
+        federated_sign_in(&app, &idp, |nonce| upstream_claims("u-1", "alice2", nonce)).await;
+    assert_eq!(resp.status(), 200);
+    let second_info: SingnedInfo = test::read_body_json(resp).await;
+    assert_eq!(second_info.user_id, signed_info.user_id);
+
+    // state is used once
+    let cookie = Cookie::new("federation_state", state.clone());
+    let req = callback_request(&format!("code-{}", state), &state, Some(cookie));
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // existing local user isn't taken over by username
+    let (resp, _) = federated_sign_in(&app, &idp, |nonce| {
+        upstream_claims("u-2", "test_user", nonce)
+    })
+    .await;
+    assert_eq!(resp.status(), 409);
+}
+
+#[actix_web::test]
+async fn test_federated_sign_in_linked_identity() {
+    let (idp, base_url) = start_mock_idp().await;
+    let config = config_with_provider(&base_url, false);
+    let app = init_test_service_with_config(config.clone()).await;
+
+    let (resp, _) =
+        federated_sign_in(&app, &idp, |nonce| upstream_claims("u-7", "manager", nonce)).await;
+    assert_eq!(resp.status(), 403);
+
+    let resources = init_test_resources().await;
+    let repo = FederationRepo::new(resources.db_pool.clone());
+    let federation_config = &config.federation_config;
+    link_user_identity(
+        &repo,
+        federation_config,
+        TEST_USER_ID_MANAGER,
+        "corp",
+        "u-7",
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        link_user_identity(&repo, federation_config, 1, "corp", "u-7").await,
+        Err(FederationError::AlreadyExists)
+    ));
+    assert!(matches!(
+        link_user_identity(&repo, federation_config, 1, "github", "u-7").await,
+        Err(FederationError::NotFoundError)
+    ));
+    assert!(matches!(
+        link_user_identity(&repo, federation_config, 999, "corp", "u-8").await,
+        Err(FederationError::NotFoundError)
+    ));
+
+    let (resp, _) =
+        federated_sign_in(&app, &idp, |nonce| upstream_claims("u-7", "manager", nonce)).await;
+    assert_eq!(resp.status(), 200);
+    let signed_info: SingnedInfo = test::read_body_json(resp).await;
+    assert_eq!(signed_info.user_id, TEST_USER_ID_MANAGER);
+}
+
+#[actix_web::test]
+async fn test_federated_sign_in_rejects_invalid_id_token() {
+    let (idp, base_url) = start_mock_idp().await;
+    let app = init_test_service_with_config(config_with_provider(&base_url, true)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/auth/v1/federation/github/login")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    let cases: [(&str, ClaimsChange); 5] = [
+        ("wrong nonce", |claims| {
+            claims["nonce"] = json!("other-nonce")
+        }),
+        ("wrong audience", |claims| {
+            claims["aud"] = json!("other-client")
+        }),
+        ("wrong issuer", |claims| {
+            claims["iss"] = json!("https://evil.example.com")
+        }),
+        ("expired", |claims| {
+            claims["exp"] = json!(Utc::now().timestamp() - 3600)
+        }),
+        ("without username", |claims| {
+            claims.as_object_mut().unwrap().remove("preferred_username");
+        }),
+    ];
+    for (case, modify) in cases {
+        let (resp, _) = federated_sign_in(&app, &idp, |nonce| {
+            let mut claims = upstream_claims("u-1", "alice", nonce);
+            modify(&mut claims);
+            claims
+        })
+        .await;
+        assert_eq!(resp.status(), 401, "{}", case);
+    }
+
+    // unknown code is rejected by the provider
+    let (location, cookie) = start_login(&app).await;
+    let req = callback_request("unknown", &query_param(&location, "state"), Some(cookie));
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_federated_callback_requires_state_cookie() {
+    let (idp, base_url) = start_mock_idp().await;
+    let app = init_test_service_with_config(config_with_provider(&base_url, true)).await;
+
+    let (location, cookie) = start_login(&app).await;
+    assert_eq!(cookie.path(), Some("/auth/v1/federation/corp/"));
+    assert_eq!(cookie.http_only(), Some(true));
+    assert_eq!(cookie.secure(), Some(true));
+    let state = query_param(&location, "state");
+    let code = register_code(&idp, &location, |nonce| {
+        upstream_claims("u-3", "carol", nonce)
+    });
+
+    // login started in another user agent
+    let resp = test::call_service(&app, callback_request(&code, &state, None)).await;
+    assert_eq!(resp.status(), 400);
+    let (_, other_cookie) = start_login(&app).await;
+    let req = callback_request(&code, &state, Some(other_cookie));
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // rejected callbacks don't use the state
+    let resp = test::call_service(&app, callback_request(&code, &state, Some(cookie))).await;
+    assert_eq!(resp.status(), 200);
+    let removal = resp
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "federation_state")
+        .unwrap();
+    assert_eq!(removal.value(), "");
+}
+
+#[actix_web::test]
+async fn test_save_federated_login_deletes_finished_logins() {
+    let resources = init_test_resources().await;
+    let repo = FederationRepo::new(resources.db_pool.clone());
+    let login = |state: &str, expired_at| FederatedLoginForCreation {
+        state_hash: hash_token(state),
+        provider: PROVIDER.to_string(),
+        nonce: "nonce".to_string(),
+        code_verifier: "verifier".to_string(),
+        expired_at,
+    };
+    let in_future = Utc::now() + Duration::minutes(10);
+    repo.save_federated_login(login("expired", Utc::now() - Duration::minutes(1)))
+        .await
+        .ok()
+        .unwrap();
+    repo.save_federated_login(login("used", in_future))
+        .await
+        .ok()
+        .unwrap();
+    repo.use_federated_login(&hash_token("used"))
+        .await
+        .ok()
+        .unwrap();
+    repo.save_federated_login(login("pending", in_future))
+        .await
+        .ok()
+        .unwrap();
+
+    let client = resources.db_pool.get().await.unwrap();
+    let hashes = vec![
+        hash_token("expired"),
+        hash_token("used"),
+        hash_token("pending"),
+    ];
+    let rows = client
+        .query(
+            "SELECT state_hash FROM federated_logins WHERE state_hash = ANY($1)",
+            &[&hashes],
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, String>(0), hash_token("pending"));
+}
@@ -0,0 +1,277 @@
+use async_trait::async_trait;
+use authust::common::{Config, LdapConfig};
+use authust::storage::ldap::directory::LdapDirectory;
+use authust::storage::postgres::directory_repo::DirectoryRepo;
+use authust::storage::postgres::federation_repo::FederationRepo;
+use authust::storage::postgres::refresh_token_repo::RefreshTokenRepo;
+use authust::storage::postgres::role_repo::RoleRepo;
+use authust::storage::postgres::user_repo::UserRepo;
+use authust::usecases::base_entities::AccessModelError;
+use authust::usecases::federation::federated_sign_in::link_user_identity;
+use authust::usecases::roles::role_members_binder::bind_member_to_role;
+use authust::usecases::users::crypto::{sign_in, SignInVerification};
+use authust::usecases::users::entities::{DirectoryEntry, SingnedInfo};
+use authust::usecases::users::errors::SignError;
+use authust::usecases::users::ldap_sign_in::{
+    LdapSignInVerification, SearchDirectory, LDAP_PROVIDER,
+};
+use authust::usecases::users::user_disabler::enable_user;
+use authust::usecases::users::user_disabler::SetUserEnabled;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod utils;
+use utils::constants::{TEST_PASSWORD, TEST_USERNAME, TEST_USER_ID_MANAGER};
+use utils::init_test_resources;
+
+const ALICE_DN: &str = "uid=alice,ou=people,dc=example,dc=com";
+const TEST_USER_DN: &str = "uid=test_user,ou=people,dc=example,dc=com";
+
+/// Directory keeps entries with passwords, bind with empty password succeeds
+/// like unauthenticated bind of real servers
+#[derive(Clone)]
+struct MemoryDirectory {
+    entries: Arc<Mutex<Vec<(DirectoryEntry, String)>>>,
+}
+
+impl MemoryDirectory {
+    fn new() -> MemoryDirectory {
+        let entry = |dn: &str, username: &str, groups: &[&str], password: &str| {
+            let entry = DirectoryEntry {
+                dn: dn.to_string(),
+                username: username.to_string(),
+                groups: groups.iter().map(|group| group.to_string()).collect(),
+            };
+            (entry, password.to_string())
+        };
+        MemoryDirectory {
+            entries: Arc::new(Mutex::new(vec![
+                entry(
+                    ALICE_DN,
+                    "alice",
+                    &[
+                        "cn=Auth-Admins,ou=groups,dc=example,dc=com",
+                        "cn=printers,ou=groups,dc=example,dc=com",
+                    ],
+                    "ldap-secret",
+                ),
+                entry(TEST_USER_DN, TEST_USERNAME, &[], "ldap-password"),
+            ])),
+        }
+    }
+
+    fn set_groups(&self, dn: &str, groups: &[&str]) {
+        let mut entries = self.entries.lock().unwrap();
+        let (entry, _) = entries
+            .iter_mut()
+            .find(|(entry, _)| entry.dn == dn)
+            .unwrap();
+        entry.groups = groups.iter().map(|group| group.to_string()).collect();
+    }
+}
+
+#[async_trait]
+impl SearchDirectory for MemoryDirectory {
+    async fn find_user_entry(
+        &self,
+        _config: &LdapConfig,
+        username: &str,
+    ) -> Result<DirectoryEntry, AccessModelError> {
+        let entries = self.entries.lock().unwrap();
+        match entries.iter().find(|(entry, _)| entry.username == username) {
+            Some((entry, _)) => Ok(entry.clone()),
+            None => Err(AccessModelError::NotFoundError),
+        }
+    }
+    async fn bind_user(
+        &self,
+        _config: &LdapConfig,
+        dn: &str,
+        password: &str,
+    ) -> Result<bool, AccessModelError> {
+        let entries = self.entries.lock().unwrap();
+        Ok(password.is_empty()
+            || entries
+                .iter()
+                .any(|(entry, entry_password)| entry.dn == dn && entry_password == password))
+    }
+}
+
+fn ldap_config(local_first: bool) -> LdapConfig {
+    LdapConfig {
+        local_first,
+        url: "ldap://127.0.0.1:1".to_string(),
+        starttls: false,
+        bind_dn: Some("cn=authust,dc=example,dc=com".to_string()),
+        bind_password: Some("service-password".to_string()),
+        base_dn: "dc=example,dc=com".to_string(),
+        user_filter: "(uid={username})".to_string(),
+        username_attribute: "uid".to_string(),
+        group_attribute: "memberOf".to_string(),
+        group_roles: HashMap::from([
+            ("auth-admins".to_string(), "ROLE_AUTH_ADMIN".to_string()),
+            ("staff".to_string(), "ROLE_2".to_string()),
+        ]),
+        timeout_seconds: 1,
+    }
+}
+
+async fn ldap_sign_in(
+    verification: &impl SignInVerification,
+    token_repo: &RefreshTokenRepo,
+    username: &str,
+    password: &str,
+) -> Result<SingnedInfo, SignError> {
+    sign_in(
+        verification,
+        token_repo,
+        &Config::create_config().security_config,
+        username.to_string(),
+        password.to_string(),
+    )
+    .await
+}
+
+#[actix_web::test]
+async fn test_ldap_sign_in_syncs_user_and_roles() {
+    let resources = init_test_resources().await;
+    let config = ldap_config(false);
+    let directory = MemoryDirectory::new();
+    let user_repo = UserRepo::new(resources.db_pool.clone());
+    let verification = LdapSignInVerification::new(
+        UserRepo::new(resources.db_pool.clone()),
+        directory.clone(),
+        DirectoryRepo::new(resources.db_pool.clone()),
+        Some(&config),
+    );
+    let token_repo = RefreshTokenRepo::new(resources.db_pool.clone());
+
+    let signed_info = ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret")
+        .await
+        .unwrap();
+    let alice_id = signed_info.user_id;
+    assert_eq!(
+        user_repo.get_user_roles(&alice_id).await.ok(),
+        Some(vec!["ROLE_AUTH_ADMIN".to_string()])
+    );
+    let credentials = user_repo.get_user_credentials("alice").await;
+    assert_eq!(credentials.map(|c| c.user_id).ok(), Some(alice_id));
+
+    for (username, password) in [
+        ("alice", "wrong"),
+        ("alice", ""),
+        ("bob", "ldap-secret"),
+        // local passwords are not checked in ldap mode
+        (TEST_USERNAME, TEST_PASSWORD),
+    ] {
+        assert!(matches!(
+            ldap_sign_in(&verification, &token_repo, username, password).await,
+            Err(SignError::VerificationError)
+        ));
+    }
+
+    // roles without mapped groups stay, mapped roles follow groups
+    let role_repo = RoleRepo::new(resources.db_pool.clone());
+    let client = resources.db_pool.get().await.unwrap();
+    let role_id: i32 = client
+        .query_one("SELECT find_role_id_by_name('ROLE_1')", &[])
+        .await
+        .unwrap()
+        .get(0);
+    bind_member_to_role(&role_repo, role_id, alice_id)
+        .await
+        .unwrap();
+    directory.set_groups(ALICE_DN, &["cn=staff,ou=groups,dc=example,dc=com"]);
+    let signed_info = ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret")
+        .await
+        .unwrap();
+    assert_eq!(signed_info.user_id, alice_id);
+    let mut roles = user_repo
+        .get_user_roles(&alice_id)
+        .await
+        .unwrap_or_default();
+    roles.sort();
+    assert_eq!(roles, vec!["ROLE_1", "ROLE_2"]);
+
+    assert!(user_repo.set_user_enabled(alice_id, false).await.is_ok());
+    assert!(matches!(
+        ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret").await,
+        Err(SignError::VerificationError)
+    ));
+    enable_user(&user_repo, alice_id).await.unwrap();
+    assert!(
+        ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret")
+            .await
+            .is_ok()
+    );
+}
+
+#[actix_web::test]
+async fn test_local_then_ldap_sign_in() {
+    let resources = init_test_resources().await;
+    let config = ldap_config(true);
+    let directory = MemoryDirectory::new();
+    let verification = LdapSignInVerification::new(
+        UserRepo::new(resources.db_pool.clone()),
+        directory.clone(),
+        DirectoryRepo::new(resources.db_pool.clone()),
+        Some(&config),
+    );
+    let token_repo = RefreshTokenRepo::new(resources.db_pool.clone());
+
+    let signed_info = ldap_sign_in(&verification, &token_repo, TEST_USERNAME, TEST_PASSWORD)
+        .await
+        .unwrap();
+    assert_eq!(signed_info.user_id, TEST_USER_ID_MANAGER);
+    assert!(
+        ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret")
+            .await
+            .is_ok()
+    );
+
+    // local user isn't taken over by LDAP entry with the same username until it is linked
+    assert!(matches!(
+        ldap_sign_in(&verification, &token_repo, TEST_USERNAME, "ldap-password").await,
+        Err(SignError::VerificationError)
+    ));
+    link_user_identity(
+        &FederationRepo::new(resources.db_pool.clone()),
+        &Config::create_config().federation_config,
+        TEST_USER_ID_MANAGER,
+        LDAP_PROVIDER,
+        TEST_USER_DN,
+    )
+    .await
+    .unwrap();
+    let signed_info = ldap_sign_in(&verification, &token_repo, TEST_USERNAME, "ldap-password")
+        .await
+        .unwrap();
+    assert_eq!(signed_info.user_id, TEST_USER_ID_MANAGER);
+    let signed_info = ldap_sign_in(&verification, &token_repo, TEST_USERNAME, TEST_PASSWORD)
+        .await
+        .unwrap();
+    assert_eq!(signed_info.user_id, TEST_USER_ID_MANAGER);
+}
+
+#[actix_web::test]
+async fn test_ldap_sign_in_unavailable_server() {
+    let resources = init_test_resources().await;
+    let config = ldap_config(true);
+    let verification = LdapSignInVerification::new(
+        UserRepo::new(resources.db_pool.clone()),
+        LdapDirectory::new(),
+        DirectoryRepo::new(resources.db_pool.clone()),
+        Some(&config),
+    );
+    let token_repo = RefreshTokenRepo::new(resources.db_pool.clone());
+    assert!(matches!(
+        ldap_sign_in(&verification, &token_repo, "alice", "ldap-secret").await,
+        Err(SignError::TemporaryError)
+    ));
+    // local users can sign in while the server is down
+    assert!(
+        ldap_sign_in(&verification, &token_repo, TEST_USERNAME, TEST_PASSWORD)
+            .await
+            .is_ok()
+    );
+}
@@ -0,0 +1,233 @@
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{http::header, test};
+use authust::common::Config;
+use authust::usecases::users::totp::{otpauth_uri, time_step, totp_code, verify_code};
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use serde_json::{json, Value};
+
+mod utils;
+use utils::constants::TEST_BASIC_AUTH_HEADER;
+use utils::IntenalRoles::RoleManager;
+use utils::{init_test_service, init_test_service_with_config, test_post};
+
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+fn current_code(secret: &str, step_offset: i64) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    totp_code(&secret, time_step(Utc::now().timestamp()) + step_offset)
+}
+
+fn sign_in_request() -> Request {
+    test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .peer_addr("10.0.0.1:4000".parse().unwrap())
+        .uri("/auth/v1/users/sign_in")
+        .to_request()
+}
+
+fn verify_request(body: Value) -> Request {
+    test::TestRequest::post()
+        .peer_addr("10.0.0.1:4000".parse().unwrap())
+        .uri("/auth/v1/mfa/verify")
+        .set_json(body)
+        .to_request()
+}
+
+/// Enables TOTP for test_user, returns the secret and recovery codes
+async fn enable_totp(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+) -> (String, Vec<String>) {
+    let req = test_post("/auth/v1/totp/enroll", RoleManager).to_request();
+    let enrollment: Value = test::call_and_read_body_json(app, req).await;
+    let secret = enrollment["secret"].as_str().unwrap().to_string();
+    assert_eq!(
+        enrollment["otpauth_uri"].as_str().unwrap(),
+        otpauth_uri(&secret, "authust", "test_user")
+    );
+
+    let req = test_post("/auth/v1/totp/confirm", RoleManager)
+        .set_json(json!({"code": "abcdef"}))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    let req = test_post("/auth/v1/totp/confirm", RoleManager)
+        .set_json(json!({"code": current_code(&secret, 0)}))
+        .to_request();
+    let confirmed: Value = test::call_and_read_body_json(app, req).await;
+    let recovery_codes: Vec<String> = confirmed["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(recovery_codes.len(), 10);
+    (secret, recovery_codes)
+}
+
+async fn mfa_token(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+) -> String {
+    let signed: Value = test::call_and_read_body_json(app, sign_in_request()).await;
+    assert_eq!(signed["mfa_required"], true);
+    assert!(signed.get("jwt_token").is_none());
+    signed["mfa_token"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn test_totp_rfc_6238_vectors() {
+    assert_eq!(totp_code(RFC_SECRET, time_step(59)), "287082");
+    assert_eq!(totp_code(RFC_SECRET, time_step(1111111109)), "081804");
+    assert_eq!(totp_code(RFC_SECRET, time_step(1234567890)), "005924");
+    assert_eq!(totp_code(RFC_SECRET, time_step(2000000000)), "279037");
+
+    let secret = BASE32_NOPAD.encode(RFC_SECRET);
+    assert_eq!(verify_code(&secret, "081804", 1111111109), Some(37037036));
+    // one period of clock drift is accepted
+    assert_eq!(verify_code(&secret, "081804", 1111111139), Some(37037036));
+    assert_eq!(verify_code(&secret, "081804", 1111111169), None);
+    assert_eq!(verify_code(&secret, "81804", 1111111109), None);
+    assert_eq!(verify_code("not base32!", "081804", 1111111109), None);
+}
+
+#[actix_web::test]
+async fn test_otpauth_uri() {
+    assert_eq!(
+        otpauth_uri("GEZDGNBVGY3TQOJQ", "authust", "test user"),
+        "otpauth://totp/authust%3Atest%20user?secret=GEZDGNBVGY3TQOJQ&issuer=authust&algorithm=SHA1&digits=6&period=30"
+    );
+}
+
+#[actix_web::test]
+async fn test_sign_in_with_totp() {
+    let app = init_test_service().await;
+    let (secret, recovery_codes) = enable_totp(&app).await;
+
+    let req = test_post("/auth/v1/totp/enroll", RoleManager).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let token = mfa_token(&app).await;
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": "12345"})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+    // the code used for confirmation can't be used again, the next one is accepted
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": current_code(&secret, 0)})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+    let code = current_code(&secret, 1);
+    let signed: Value = test::call_and_read_body_json(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": code})),
+    )
+    .await;
+    assert!(signed["jwt_token"].is_string());
+    assert!(signed["refresh_token"].is_string());
+
+    // mfa token is single use and the code is rejected with a new token
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": code})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+    let token = mfa_token(&app).await;
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": code})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "recovery_code": recovery_codes[0]})),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let token = mfa_token(&app).await;
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "recovery_code": recovery_codes[0]})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_mfa_token_failures_limit() {
+    let mut config = Config::create_config();
+    config.sign_in_throttling_config.free_failures = 10;
+    let app = init_test_service_with_config(config).await;
+    let (secret, _) = enable_totp(&app).await;
+
+    let token = mfa_token(&app).await;
+    for _ in 0..5 {
+        let resp = test::call_service(
+            &app,
+            verify_request(json!({"mfa_token": token, "recovery_code": "wrong"})),
+        )
+        .await;
+        assert_eq!(resp.status(), 403);
+    }
+    // the token is closed after too many wrong codes, password is required again
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "code": current_code(&secret, 1)})),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn test_mfa_verify_throttling() {
+    let app = init_test_service().await;
+    enable_totp(&app).await;
+
+    let token = mfa_token(&app).await;
+    for _ in 0..3 {
+        let resp = test::call_service(
+            &app,
+            verify_request(json!({"mfa_token": token, "recovery_code": "wrong"})),
+        )
+        .await;
+        assert_eq!(resp.status(), 403);
+    }
+    let resp = test::call_service(
+        &app,
+        verify_request(json!({"mfa_token": token, "recovery_code": "wrong"})),
+    )
+    .await;
+    assert_eq!(resp.status(), 429);
+    assert!(resp.headers().contains_key(header::RETRY_AFTER));
+}
+
+#[actix_web::test]
+async fn test_totp_requires_access_token() {
+    let app = init_test_service().await;
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/totp/enroll")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test_post("/auth/v1/totp/confirm", RoleManager)
+        .set_json(json!({"code": "123456"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // users without TOTP get tokens right away
+    let signed: Value = test::call_and_read_body_json(&app, sign_in_request()).await;
+    assert!(signed["jwt_token"].is_string());
+    assert!(signed.get("mfa_required").is_none());
+}
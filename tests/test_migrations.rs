@@ -0,0 +1,57 @@
+use actix_web::rt;
+use authust::storage::postgres::migrations::{
+    migrate_up, migration_status, MigrationError, MIGRATIONS,
+};
+
+mod utils;
+use utils::init_test_resources;
+
+#[actix_web::test]
+async fn test_migrate_up_is_idempotent() {
+    let resources = init_test_resources().await;
+    let applied = migrate_up(&resources.db_pool).await.unwrap();
+    assert!(applied.is_empty());
+
+    let statuses = migration_status(&resources.db_pool).await.unwrap();
+    assert_eq!(statuses.len(), MIGRATIONS.len());
+    assert!(statuses
+        .iter()
+        .all(|status| status.applied_at.is_some() && status.checksum_matches));
+}
+
+#[actix_web::test]
+async fn test_migrate_up_concurrently() {
+    let resources = init_test_resources().await;
+    let client = resources.db_pool.get().await.unwrap();
+    client
+        .simple_query("DROP TABLE IF EXISTS users, permissions, roles, role_permissions, role_members, refresh_tokens, revoked_tokens, services, service_scopes, schema_migrations CASCADE")
+        .await
+        .unwrap();
+
+    let (first_pool, second_pool) = (resources.db_pool.clone(), resources.db_pool.clone());
+    let first = rt::spawn(async move { migrate_up(&first_pool).await });
+    let second = rt::spawn(async move { migrate_up(&second_pool).await });
+    let mut applied = first.await.unwrap().unwrap();
+    applied.extend(second.await.unwrap().unwrap());
+    applied.sort();
+    let expected: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+    assert_eq!(applied, expected);
+}
+
+#[actix_web::test]
+async fn test_migrate_up_checksum_mismatch() {
+    let resources = init_test_resources().await;
+    let client = resources.db_pool.get().await.unwrap();
+    client
+        .simple_query("UPDATE schema_migrations SET checksum='edited' WHERE version=2")
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        migrate_up(&resources.db_pool).await,
+        Err(MigrationError::ChecksumMismatchError(2))
+    ));
+    let statuses = migration_status(&resources.db_pool).await.unwrap();
+    assert!(!statuses[1].checksum_matches);
+    assert!(statuses[0].checksum_matches);
+}
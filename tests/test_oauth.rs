@@ -0,0 +1,435 @@
+use actix_web::{http::header, test};
+use authust::cli::{run_client, ClientAction};
+use authust::common::{Config, Resources};
+use authust::storage::postgres::oauth_client_repo::OAuthClientRepo;
+use authust::usecases::oauth::authorization_code::code_challenge_s256;
+use authust::usecases::oauth::client_registry::{bind_role_to_client, register_client};
+use authust::usecases::oauth::entities::{
+    redirect_url, AccessTokenInfo, ClientClaims, OAuthClientRegistration, RegisteredOAuthClient,
+};
+use authust::usecases::oauth::errors::OAuthError;
+use authust::usecases::users::crypto::decode_jwt;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use jsonwebtoken::{decode, Validation};
+use serde_json::Value;
+
+mod utils;
+use utils::constants::{TEST_PASSWORD, TEST_USERNAME, TEST_USER_ID_MANAGER};
+use utils::{init_test_resources, init_test_service};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk0123456789";
+
+fn registration(client_id: &str, grant_types: &[&str]) -> OAuthClientRegistration {
+    OAuthClientRegistration {
+        client_id: client_id.to_string(),
+        scopes: vec!["users:read".to_string(), "users:write".to_string()],
+        grant_types: grant_types.iter().map(|g| g.to_string()).collect(),
+        redirect_uris: vec![REDIRECT_URI.to_string()],
+        is_public: false,
+    }
+}
+
+async fn register_test_client(client_id: &str) -> RegisteredOAuthClient {
+    let resources = init_test_resources().await;
+    let client_repo = OAuthClientRepo::new(resources.db_pool.clone());
+    let client = register_client(
+        &client_repo,
+        registration(client_id, &["client_credentials"]),
+    )
+    .await
+    .unwrap();
+    let client_conn = resources.db_pool.get().await.unwrap();
+    let role_id: i32 = client_conn
+        .query_one("SELECT find_role_id_by_name('ROLE_2')", &[])
+        .await
+        .unwrap()
+        .get(0);
+    bind_role_to_client(&client_repo, client_id, role_id)
+        .await
+        .unwrap();
+    client
+}
+
+fn basic_header(client_id: &str, client_secret: &str) -> (header::HeaderName, String) {
+    let credentials = STANDARD.encode(format!("{}:{}", client_id, client_secret));
+    (header::AUTHORIZATION, format!("Basic {}", credentials))
+}
+
+fn token_request(form: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/auth/v1/oauth/token")
+        .insert_header(header::ContentType::form_url_encoded())
+        .set_payload(form.to_string())
+}
+
+#[actix_web::test]
+async fn test_client_credentials_grant() {
+    let app = init_test_service().await;
+    let client = register_test_client("billing").await;
+    let client_secret = client.client_secret.clone().unwrap();
+
+    let req = token_request("grant_type=client_credentials&scope=users:read")
+        .insert_header(basic_header(&client.client_id, &client_secret))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get(header::CACHE_CONTROL).unwrap(),
+        "no-store"
+    );
+    let token_info: AccessTokenInfo = test::read_body_json(resp).await;
+    assert_eq!(token_info.token_type, "Bearer");
+    assert_eq!(token_info.scope, "users:read");
+
+    let config = Config::create_config().security_config;
+    let key = config.jwt_keyring.current();
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_audience(&[&config.jwt_audience]);
+    let claims = decode::<ClientClaims>(&token_info.access_token, key.decoding_key(), &validation)
+        .unwrap()
+        .claims;
+    assert_eq!(claims.sub, "billing");
+    assert_eq!(claims.client_id, "billing");
+    assert_eq!(claims.scope, "users:read");
+    let mut permissions = claims.permissions;
+    permissions.sort();
+    assert_eq!(permissions, vec!["PERM_1", "PERM_2", "ROLE_2"]);
+
+    // client_secret_post, all allowed scopes are granted by default
+    let form = format!(
+        "grant_type=client_credentials&client_id={}&client_secret={}",
+        client.client_id,
+        client.client_secret.unwrap()
+    );
+    let token_info: AccessTokenInfo =
+        test::call_and_read_body_json(&app, token_request(&form).to_request()).await;
+    assert_eq!(token_info.scope, "users:read users:write");
+}
+
+#[actix_web::test]
+async fn test_token_endpoint_errors() {
+    let app = init_test_service().await;
+    let client = register_test_client("billing").await;
+    let client_secret = client.client_secret.unwrap();
+    let auth = basic_header(&client.client_id, &client_secret);
+
+    let cases = [
+        (
+            "grant_type=client_credentials",
+            basic_header("billing", "wrong"),
+            401,
+            "invalid_client",
+        ),
+        (
+            "grant_type=client_credentials",
+            basic_header("unknown", &client_secret),
+            401,
+            "invalid_client",
+        ),
+        (
+            "grant_type=password",
+            auth.clone(),
+            400,
+            "unsupported_grant_type",
+        ),
+        (
+            "grant_type=client_credentials&scope=users:delete",
+            auth.clone(),
+            400,
+            "invalid_scope",
+        ),
+        ("scope=users:read", auth.clone(), 400, "invalid_request"),
+    ];
+    for (form, auth, status, error) in cases {
+        let req = token_request(form).insert_header(auth).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{}", form);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], error, "{}", form);
+    }
+
+    let req = token_request("grant_type=client_credentials").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+}
+
+#[actix_web::test]
+async fn test_client_registry() {
+    let resources = init_test_resources().await;
+    let client_repo = OAuthClientRepo::new(resources.db_pool.clone());
+    let action = ClientAction::Create {
+        client_id: "cli_client".to_string(),
+        scope: vec!["users:read".to_string()],
+        grant_type: vec!["client_credentials".to_string()],
+        redirect_uri: vec![],
+        public: false,
+    };
+    run_client(&resources, action).await.unwrap();
+    let action = ClientAction::BindRole {
+        client_id: "cli_client".to_string(),
+        role_id: 1,
+    };
+    run_client(&resources, action).await.unwrap();
+
+    let result = register_client(
+        &client_repo,
+        registration("cli_client", &["client_credentials"]),
+    )
+    .await;
+    assert!(matches!(result, Err(OAuthError::AlreadyExists)));
+    let result = register_client(&client_repo, registration("other", &["password"])).await;
+    assert!(matches!(result, Err(OAuthError::UnsupportedGrantTypeError)));
+    let mut client_data = registration("other", &["client_credentials"]);
+    client_data.scopes = vec!["users read".to_string()];
+    let result = register_client(&client_repo, client_data).await;
+    assert!(matches!(result, Err(OAuthError::InvalidScopeError)));
+    let mut client_data = registration("other", &["client_credentials"]);
+    client_data.is_public = true;
+    let result = register_client(&client_repo, client_data).await;
+    assert!(matches!(result, Err(OAuthError::UnauthorizedClientError)));
+    for redirect_uris in [
+        vec![],
+        vec!["http://app.example.com/callback".to_string()],
+        vec!["https://app.example.com/callback#fragment".to_string()],
+        vec!["/callback".to_string()],
+        vec!["javascript:alert(document.cookie)".to_string()],
+        vec!["data:text/html,<script>alert(1)</script>".to_string()],
+        vec!["file:///etc/passwd".to_string()],
+        vec!["vbscript:msgbox".to_string()],
+        vec!["myapp:/oauth2redirect".to_string()],
+    ] {
+        let mut client_data = registration("other", &["authorization_code"]);
+        client_data.redirect_uris = redirect_uris;
+        let result = register_client(&client_repo, client_data).await;
+        assert!(matches!(result, Err(OAuthError::InvalidRedirectUriError)));
+    }
+    let mut client_data = registration("mobile", &["authorization_code"]);
+    client_data.redirect_uris = vec![
+        "com.example.app:/oauth2redirect".to_string(),
+        "http://127.0.0.1:8400/callback".to_string(),
+    ];
+    client_data.is_public = true;
+    let client = register_client(&client_repo, client_data).await.unwrap();
+    assert!(client.client_secret.is_none());
+
+    let result = bind_role_to_client(&client_repo, "cli_client", 9999).await;
+    assert!(matches!(result, Err(OAuthError::NotFoundError)));
+    let result = bind_role_to_client(&client_repo, "unknown", 1).await;
+    assert!(matches!(result, Err(OAuthError::NotFoundError)));
+}
+
+async fn register_public_client() {
+    let resources = init_test_resources().await;
+    let client_repo = OAuthClientRepo::new(resources.db_pool.clone());
+    let mut client_data = registration("spa", &["authorization_code"]);
+    client_data.is_public = true;
+    register_client(&client_repo, client_data).await.unwrap();
+}
+
+fn authorization_params() -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_string()),
+        ("client_id", "spa".to_string()),
+        ("redirect_uri", REDIRECT_URI.to_string()),
+        ("scope", "users:read".to_string()),
+        ("state", "xyz".to_string()),
+        ("code_challenge", code_challenge_s256(CODE_VERIFIER)),
+        ("code_challenge_method", "S256".to_string()),
+    ]
+}
+
+fn authorize_page_request(params: &[(&str, String)]) -> test::TestRequest {
+    let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    test::TestRequest::get().uri(&redirect_url("/auth/v1/oauth/authorize", &params))
+}
+
+fn login_request(password: &str, decision: &str) -> test::TestRequest {
+    let mut form = authorization_params();
+    form.push(("username", TEST_USERNAME.to_string()));
+    form.push(("password", password.to_string()));
+    form.push(("totp_code", String::new()));
+    form.push(("decision", decision.to_string()));
+    test::TestRequest::post()
+        .uri("/auth/v1/oauth/authorize")
+        .peer_addr("10.0.0.1:4000".parse().unwrap())
+        .set_form(form)
+}
+
+fn location(resp: &actix_web::dev::ServiceResponse) -> String {
+    resp.headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn authorization_code(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+) -> String {
+    let resp = test::call_service(app, login_request(TEST_PASSWORD, "allow").to_request()).await;
+    assert_eq!(resp.status(), 302);
+    let location = location(&resp);
+    let query = location
+        .strip_prefix(&format!("{}?", REDIRECT_URI))
+        .unwrap();
+    let code = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("code="))
+        .unwrap();
+    assert!(query.ends_with("&state=xyz"));
+    code.to_string()
+}
+
+fn code_exchange_request(
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: &str,
+) -> actix_http::Request {
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("client_id", "spa"),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("code_verifier", code_verifier),
+    ];
+    test::TestRequest::post()
+        .uri("/auth/v1/oauth/token")
+        .set_form(form)
+        .to_request()
+}
+
+#[actix_web::test]
+async fn test_authorization_page() {
+    let app = init_test_service().await;
+    register_public_client().await;
+
+    let resp = test::call_service(
+        &app,
+        authorize_page_request(&authorization_params()).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().contains_key(header::X_FRAME_OPTIONS));
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("<b>spa</b> wants to sign you in"));
+    assert!(body.contains("name=\"state\" value=\"xyz\""));
+
+    // errors without trusted redirect URI are shown to the user
+    let mut params = authorization_params();
+    params[2].1 = "https://evil.example.com/callback".to_string();
+    let resp = test::call_service(&app, authorize_page_request(&params).to_request()).await;
+    assert_eq!(resp.status(), 400);
+    assert!(!resp.headers().contains_key(header::LOCATION));
+    let mut params = authorization_params();
+    params[1].1 = "unknown".to_string();
+    let resp = test::call_service(&app, authorize_page_request(&params).to_request()).await;
+    assert_eq!(resp.status(), 400);
+
+    // other errors are sent to the client
+    let cases = [
+        (0, "token", "unsupported_response_type"),
+        (3, "users:delete", "invalid_scope"),
+        (5, "short", "invalid_request"),
+        (6, "plain", "invalid_request"),
+    ];
+    for (index, value, error) in cases {
+        let mut params = authorization_params();
+        params[index].1 = value.to_string();
+        let resp = test::call_service(&app, authorize_page_request(&params).to_request()).await;
+        assert_eq!(resp.status(), 302);
+        assert_eq!(
+            location(&resp),
+            format!("{}?error={}&state=xyz", REDIRECT_URI, error)
+        );
+    }
+
+    let resp = test::call_service(&app, login_request("wrong", "allow").to_request()).await;
+    assert_eq!(resp.status(), 401);
+    let resp = test::call_service(&app, login_request("", "deny").to_request()).await;
+    assert_eq!(resp.status(), 302);
+    assert_eq!(
+        location(&resp),
+        format!("{}?error=access_denied&state=xyz", REDIRECT_URI)
+    );
+}
+
+#[actix_web::test]
+async fn test_authorization_code_grant() {
+    let app = init_test_service().await;
+    register_public_client().await;
+
+    let code = authorization_code(&app).await;
+    let resp = test::call_service(
+        &app,
+        code_exchange_request(&code, REDIRECT_URI, CODE_VERIFIER),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    let token_info: AccessTokenInfo = test::read_body_json(resp).await;
+    assert_eq!(token_info.scope, "users:read");
+    let config = Config::create_config().security_config;
+    let claims = decode_jwt(&config, &token_info.access_token).unwrap();
+    assert_eq!(claims.user_id, TEST_USER_ID_MANAGER);
+    let refresh_token = token_info.refresh_token.unwrap();
+
+    // repeated exchange fails and revokes tokens issued for the code
+    let resp = test::call_service(
+        &app,
+        code_exchange_request(&code, REDIRECT_URI, CODE_VERIFIER),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_grant");
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/token/refresh")
+        .set_json(serde_json::json!({ "refresh_token": refresh_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_ne!(resp.status(), 200);
+
+    let wrong_verifier = CODE_VERIFIER.replace('d', "e");
+    let cases = [
+        ("https://app.example.com/other", CODE_VERIFIER),
+        (REDIRECT_URI, wrong_verifier.as_str()),
+    ];
+    for (redirect_uri, code_verifier) in cases {
+        let code = authorization_code(&app).await;
+        let resp = test::call_service(
+            &app,
+            code_exchange_request(&code, redirect_uri, code_verifier),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_grant");
+    }
+
+    let code = authorization_code(&app).await;
+    let resources = Resources::create_resources(&Config::create_config()).await;
+    resources
+        .db_pool
+        .get()
+        .await
+        .unwrap()
+        .execute(
+            "UPDATE authorization_codes SET expired_at=now() - interval '1 second'",
+            &[],
+        )
+        .await
+        .unwrap();
+    let resp = test::call_service(
+        &app,
+        code_exchange_request(&code, REDIRECT_URI, CODE_VERIFIER),
+    )
+    .await;
+    assert_eq!(resp.status(), 400);
+}
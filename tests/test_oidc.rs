@@ -0,0 +1,204 @@
+use actix_web::{http::header, test};
+use authust::common::Config;
+use authust::storage::postgres::oauth_client_repo::OAuthClientRepo;
+use authust::usecases::oauth::authorization_code::code_challenge_s256;
+use authust::usecases::oauth::client_registry::register_client;
+use authust::usecases::oauth::entities::{
+    AccessTokenInfo, OAuthClientRegistration, OpenIdConfiguration, UserInfo,
+};
+use authust::usecases::users::crypto::decode_jwt;
+use authust::usecases::users::entities::Claims;
+use jsonwebtoken::{decode, Validation};
+
+mod utils;
+use utils::constants::{TEST_PASSWORD, TEST_USERNAME, TEST_USER_ID_MANAGER};
+use utils::{init_test_resources, init_test_service};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk0123456789";
+
+async fn register_public_client() {
+    let resources = init_test_resources().await;
+    let client_repo = OAuthClientRepo::new(resources.db_pool.clone());
+    let registration = OAuthClientRegistration {
+        client_id: "spa".to_string(),
+        scopes: vec!["users:read".to_string()],
+        grant_types: vec!["authorization_code".to_string()],
+        redirect_uris: vec![REDIRECT_URI.to_string()],
+        is_public: true,
+    };
+    register_client(&client_repo, registration).await.unwrap();
+}
+
+async fn exchange_code(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    scope: &str,
+) -> AccessTokenInfo {
+    let code_challenge = code_challenge_s256(CODE_VERIFIER);
+    let form = [
+        ("response_type", "code"),
+        ("client_id", "spa"),
+        ("redirect_uri", REDIRECT_URI),
+        ("scope", scope),
+        ("code_challenge", code_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+        ("nonce", "n-0S6_WzA2Mj"),
+        ("username", TEST_USERNAME),
+        ("password", TEST_PASSWORD),
+        ("decision", "allow"),
+    ];
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/oauth/authorize")
+        .peer_addr("10.0.0.1:4000".parse().unwrap())
+        .set_form(form)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), 302);
+    let location = resp.headers().get(header::LOCATION).unwrap();
+    let code = location
+        .to_str()
+        .unwrap()
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix("code="))
+        .unwrap()
+        .to_string();
+
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("client_id", "spa"),
+        ("code", code.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_verifier", CODE_VERIFIER),
+    ];
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/oauth/token")
+        .set_form(form)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), 200);
+    test::read_body_json(resp).await
+}
+
+fn decode_id_token(config: &Config, id_token: &str) -> Claims {
+    let key = config.security_config.jwt_keyring.current();
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_audience(&["spa"]);
+    validation.set_issuer(&[&config.security_config.jwt_issuer]);
+    decode::<Claims>(id_token, key.decoding_key(), &validation)
+        .unwrap()
+        .claims
+}
+
+#[actix_web::test]
+async fn test_openid_configuration() {
+    let app = init_test_service().await;
+    let req = test::TestRequest::get()
+        .uri("/.well-known/openid-configuration")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let discovery: OpenIdConfiguration = test::read_body_json(resp).await;
+    let config = Config::create_config().security_config;
+    let base_url = config.jwt_issuer.trim_end_matches('/');
+    assert_eq!(discovery.issuer, config.jwt_issuer);
+    assert_eq!(
+        discovery.authorization_endpoint,
+        format!("{}/auth/v1/oauth/authorize", base_url)
+    );
+    assert_eq!(
+        discovery.jwks_uri,
+        format!("{}/.well-known/jwks.json", base_url)
+    );
+    assert_eq!(
+        discovery.id_token_signing_alg_values_supported,
+        vec![config.jwt_keyring.current().algorithm]
+    );
+    assert_eq!(
+        discovery.grant_types_supported,
+        vec!["client_credentials", "authorization_code"]
+    );
+    assert_eq!(discovery.scopes_supported, vec!["openid", "profile"]);
+    assert_eq!(discovery.code_challenge_methods_supported, vec!["S256"]);
+}
+
+#[actix_web::test]
+async fn test_id_token() {
+    let app = init_test_service().await;
+    register_public_client().await;
+    let config = Config::create_config();
+
+    let token_info = exchange_code(&app, "openid profile users:read").await;
+    assert_eq!(token_info.scope, "openid profile users:read");
+    let id_token = token_info.id_token.unwrap();
+    let claims = decode_id_token(&config, &id_token);
+    assert_eq!(claims.sub, TEST_USER_ID_MANAGER.to_string());
+    assert_eq!(claims.aud, "spa");
+    assert_eq!(claims.oidc.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert!(claims.oidc.auth_time.unwrap() <= claims.iat);
+    assert_eq!(
+        claims.oidc.preferred_username.as_deref(),
+        Some(TEST_USERNAME)
+    );
+    assert!(claims.oidc.updated_at.is_some());
+    let access_claims = decode_jwt(&config.security_config, &token_info.access_token).unwrap();
+    assert_eq!(claims.sid, access_claims.sid);
+    // id token is not accepted instead of access token
+    assert!(decode_jwt(&config.security_config, &id_token).is_err());
+
+    // openid scope alone doesn't grant scopes of the client and profile claims
+    let token_info = exchange_code(&app, "openid").await;
+    assert_eq!(token_info.scope, "openid");
+    let claims = decode_id_token(&config, &token_info.id_token.unwrap());
+    assert!(claims.oidc.preferred_username.is_none());
+
+    let token_info = exchange_code(&app, "users:read").await;
+    assert!(token_info.id_token.is_none());
+}
+
+#[actix_web::test]
+async fn test_userinfo() {
+    let app = init_test_service().await;
+    register_public_client().await;
+    let token_info = exchange_code(&app, "openid profile").await;
+    let bearer = format!("Bearer {}", token_info.access_token);
+
+    for req in [test::TestRequest::get(), test::TestRequest::post()] {
+        let req = req
+            .uri("/auth/v1/oauth/userinfo")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let user_info: UserInfo = test::read_body_json(resp).await;
+        assert_eq!(user_info.sub, TEST_USER_ID_MANAGER.to_string());
+        assert_eq!(user_info.preferred_username, TEST_USERNAME);
+    }
+
+    let cases = [
+        (None, "Bearer error=\"invalid_request\""),
+        (
+            Some(format!("Bearer {}", token_info.id_token.unwrap())),
+            "Bearer error=\"invalid_token\"",
+        ),
+        (
+            Some("Bearer broken".to_string()),
+            "Bearer error=\"invalid_token\"",
+        ),
+    ];
+    for (authorization, expected) in cases {
+        let mut req = test::TestRequest::get().uri("/auth/v1/oauth/userinfo");
+        if let Some(authorization) = authorization {
+            req = req.insert_header((header::AUTHORIZATION, authorization));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 401);
+        assert_eq!(
+            resp.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use actix_web::http::header;
+use actix_web::test;
+
+use authust::common::{Config, NotifierKind};
+use authust::storage::notify::smtp::SmtpNotifier;
+use authust::storage::postgres::password_reset_repo::PasswordResetRepo;
+use authust::usecases::base_entities::AccessModelError;
+use authust::usecases::users::entities::{Notification, SingnedInfo};
+use authust::usecases::users::password_reset::{Notifier, StorePasswordReset};
+
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+mod utils;
+use utils::constants::{TEST_BASIC_AUTH_HEADER, TEST_USER_ID_MANAGER};
+use utils::{
+    init_test_resources, init_test_service, init_test_service_with_config, test_post,
+    IntenalRoles::RoleAdmin,
+};
+
+fn basic_auth_header(username: &str, password: &str) -> (header::HeaderName, String) {
+    let credentials = data_encoding::BASE64.encode(format!("{}:{}", username, password).as_bytes());
+    (header::AUTHORIZATION, format!("Basic {}", credentials))
+}
+
+fn notifications_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("authust-{}-{}.jsonl", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn read_notifications(path: &PathBuf) -> Vec<Notification> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// Notifications are delivered after the response
+async fn wait_notifications(path: &PathBuf, count: usize) -> Vec<Notification> {
+    for _ in 0..20 {
+        let notifications = read_notifications(path);
+        if notifications.len() >= count {
+            return notifications;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    read_notifications(path)
+}
+
+fn reset_token(notification: &Notification) -> String {
+    notification
+        .body
+        .lines()
+        .find_map(|line| line.strip_prefix("Reset token: "))
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn test_password_reset() {
+    let path = notifications_path("password-reset");
+    let mut config = Config::create_config();
+    config.notifier_config.kind = NotifierKind::File;
+    config.notifier_config.file_path = Some(path.to_string_lossy().to_string());
+    config.password_reset_config.url = Some("https://example.com/reset".to_string());
+    let app = init_test_service_with_config(config).await;
+
+    let req = test_post("/api/v1/users", RoleAdmin)
+        .set_json(json!({
+            "username": "forgetful",
+            "password": "old-password",
+            "email": "forgetful@example.com",
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+    for email in [
+        "",
+        "careless",
+        "@example.com",
+        "careless@",
+        "careless@example.com>\r\nBcc: <other@example.com",
+        "careless@example.com\nBcc: other@example.com",
+    ] {
+        let req = test_post("/api/v1/users", RoleAdmin)
+            .set_json(json!({
+                "username": "careless",
+                "password": "password",
+                "email": email,
+            }))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            400,
+            "{}",
+            email
+        );
+    }
+    let req = test::TestRequest::post()
+        .insert_header(basic_auth_header("forgetful", "old-password"))
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let signed_info: SingnedInfo = test::call_and_read_body_json(&app, req).await;
+
+    // unknown users and users without email get the same response
+    for username in ["forgetful", "nobody", "test_user", "forgetful"] {
+        let req = test::TestRequest::post()
+            .uri("/auth/v1/password/reset")
+            .set_json(json!({ "username": username }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 202);
+    }
+    // repeated request in a minute doesn't send new message
+    let notifications = wait_notifications(&path, 2).await;
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].recipient, "forgetful@example.com");
+    let token = reset_token(&notifications[0]);
+    assert!(notifications[0]
+        .body
+        .contains(&format!("https://example.com/reset?token={}", token)));
+
+    for (body, status) in [
+        (json!({"token": token, "password": ""}), 400),
+        (json!({"token": "unknown", "password": "new-password"}), 400),
+        (json!({"token": token, "password": "new-password"}), 204),
+        // the token is single-use
+        (json!({"token": token, "password": "other-password"}), 400),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/auth/v1/password/reset/confirm")
+            .set_json(body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+
+    // sessions started with the old password are revoked
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/token/refresh")
+        .set_json(json!({ "refresh_token": signed_info.refresh_token }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::get()
+        .insert_header((
+            header::AUTHORIZATION,
+            format!("Bearer {}", signed_info.jwt_token),
+        ))
+        .uri("/api/v1/api_keys")
+        .to_request();
+    assert!(test::try_call_service(&app, req).await.is_err());
+
+    for (password, status) in [("old-password", 403), ("new-password", 200)] {
+        let req = test::TestRequest::post()
+            .insert_header(basic_auth_header("forgetful", password))
+            .uri("/auth/v1/users/sign_in")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[actix_web::test]
+async fn test_concurrent_reset_requests_save_one_token() {
+    let resources = init_test_resources().await;
+    let now = chrono::Utc::now();
+    let handles: Vec<_> = ["token-1", "token-2"]
+        .into_iter()
+        .map(|token_hash| {
+            let repo = PasswordResetRepo::new(resources.db_pool.clone());
+            actix_web::rt::spawn(async move {
+                repo.save_reset_token(
+                    TEST_USER_ID_MANAGER,
+                    token_hash,
+                    now + chrono::Duration::minutes(30),
+                    now - chrono::Duration::minutes(1),
+                )
+                .await
+                .ok()
+                .unwrap()
+            })
+        })
+        .collect();
+    let mut saved = vec![];
+    for handle in handles {
+        saved.push(handle.await.unwrap());
+    }
+    saved.sort();
+    assert_eq!(saved, [false, true]);
+
+    let repo = PasswordResetRepo::new(resources.db_pool.clone());
+    assert!(matches!(
+        repo.save_reset_token(999, "token-3", now, now).await,
+        Err(AccessModelError::NotFoundError)
+    ));
+}
+
+#[actix_web::test]
+async fn test_set_user_password() {
+    let app = init_test_service().await;
+    let req = test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let signed_info: SingnedInfo = test::call_and_read_body_json(&app, req).await;
+    let api_keys_request = || {
+        test::TestRequest::get()
+            .insert_header((
+                header::AUTHORIZATION,
+                format!("Bearer {}", signed_info.jwt_token),
+            ))
+            .uri("/api/v1/api_keys")
+            .to_request()
+    };
+    // token version of the user is cached
+    assert!(test::try_call_service(&app, api_keys_request())
+        .await
+        .is_ok());
+
+    let url = format!("/api/v1/users/{}/password", TEST_USER_ID_MANAGER);
+    for (url, password, status) in [
+        (url.as_str(), "", 400),
+        ("/api/v1/users/999/password", "new-password", 404),
+        (url.as_str(), "new-password", 204),
+    ] {
+        let req = test_post(url, RoleAdmin)
+            .set_json(json!({ "password": password }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+    let req = test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::post()
+        .insert_header(basic_auth_header("test_user", "new-password"))
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    // sessions are revoked together with the password change
+    assert!(test::try_call_service(&app, api_keys_request())
+        .await
+        .is_err());
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/token/refresh")
+        .set_json(json!({ "refresh_token": signed_info.refresh_token }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+}
+
+/// Mail catcher for one connection, sends received lines to the channel,
+/// recipients with `rejected` are refused
+fn start_mail_catcher() -> (u16, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = vec![];
+        let mut in_data = false;
+        writer.write_all(b"220 catcher ESMTP\r\n").unwrap();
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            lines.push(line.clone());
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-catcher\r\n250 8BITMIME\r\n"
+            } else if line.starts_with("RCPT") && line.contains("rejected") {
+                b"550 no such user\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                writer.write_all(b"221 bye\r\n").unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(reply).unwrap();
+        }
+        sender.send(lines).unwrap();
+    });
+    (port, receiver)
+}
+
+#[actix_web::test]
+async fn test_smtp_notifier() {
+    let mut config = Config::create_config().notifier_config;
+    let notification = |recipient: &str| Notification {
+        recipient: recipient.to_string(),
+        subject: "Password reset".to_string(),
+        body: "Reset token: abc\n.hidden line\nbye\n".to_string(),
+    };
+
+    let (port, receiver) = start_mail_catcher();
+    config.smtp_port = port;
+    let notifier = SmtpNotifier::new(&config);
+    assert!(notifier
+        .send(&notification("user@example.com"))
+        .await
+        .is_ok());
+    let lines = receiver.recv().unwrap();
+    assert!(lines.contains(&"RCPT TO:<user@example.com>".to_string()));
+    assert!(lines.contains(&"Subject: Password reset".to_string()));
+    assert!(lines.contains(&"Reset token: abc".to_string()));
+    assert!(lines.contains(&"..hidden line".to_string()));
+
+    let (port, _receiver) = start_mail_catcher();
+    config.smtp_port = port;
+    let notifier = SmtpNotifier::new(&config);
+    assert!(matches!(
+        notifier.send(&notification("rejected@example.com")).await,
+        Err(AccessModelError::FatalError)
+    ));
+    assert!(matches!(
+        notifier
+            .send(&notification(
+                "user@example.com>\r\nBcc: <other@example.com"
+            ))
+            .await,
+        Err(AccessModelError::FatalError)
+    ));
+
+    // nothing listens on the port after the catcher is finished
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    config.smtp_port = port;
+    let notifier = SmtpNotifier::new(&config);
+    assert!(matches!(
+        notifier.send(&notification("user@example.com")).await,
+        Err(AccessModelError::TemporaryError)
+    ));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_mut_passed, clippy::bool_assert_comparison)]
+
 use actix_web::test;
 use authust::handlers::api::permissions::views::{PermissionListingView, PermissionView};
 use serde_json::json;
@@ -7,7 +9,6 @@ use utils::{
     init_test_service, test_delete, test_get, test_post,
     IntenalRoles::{RoleAdmin, RoleStaff},
 };
-mod constants;
 
 #[actix_web::test]
 async fn test_get_permission() {
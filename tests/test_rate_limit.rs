@@ -0,0 +1,162 @@
+use actix_web::http::header;
+use actix_web::test;
+use authust::common::config_source::{ConfigError, ConfigFieldError};
+use authust::common::{BucketLimit, Config};
+use authust::storage::memory::token_buckets::TokenBuckets;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+mod utils;
+use utils::constants::TEST_SERVICE_BASIC_AUTH_HEADER;
+use utils::IntenalRoles::{RoleAdmin, RoleManager};
+use utils::{create_test_jwt, init_test_service_with_config, test_get};
+
+fn config_with_limits(api: BucketLimit, srv: BucketLimit) -> Config {
+    let mut config = Config::create_config();
+    config.rate_limit_config.enabled = true;
+    config.rate_limit_config.api = api;
+    config.rate_limit_config.srv = srv;
+    config
+}
+
+#[actix_web::test]
+async fn test_token_bucket() {
+    let buckets = TokenBuckets::new();
+    let limit = BucketLimit {
+        burst: 2,
+        per_second: 1.0,
+    };
+    let now = Instant::now();
+    let decision = buckets.take_at("a", limit, now);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 1);
+    assert_eq!(decision.reset_seconds, 1);
+    assert!(buckets.take_at("a", limit, now).allowed);
+    let decision = buckets.take_at("a", limit, now);
+    assert!(!decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    assert_eq!(decision.retry_after_seconds, 1);
+    assert_eq!(decision.reset_seconds, 2);
+    // other key has its own bucket
+    assert!(buckets.take_at("b", limit, now).allowed);
+    // the bucket is refilled with time, but never above burst
+    assert!(
+        buckets
+            .take_at("a", limit, now + Duration::from_secs(1))
+            .allowed
+    );
+    let decision = buckets.take_at("a", limit, now + Duration::from_secs(60));
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 1);
+}
+
+#[actix_web::test]
+async fn test_api_rate_limit_per_user() {
+    let limit = BucketLimit {
+        burst: 2,
+        per_second: 0.01,
+    };
+    let app = init_test_service_with_config(config_with_limits(limit, limit)).await;
+    for remaining in ["1", "0"] {
+        let resp =
+            test::call_service(&app, test_get("/api/v1/users/1", RoleAdmin).to_request()).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("RateLimit-Limit").unwrap(), "2");
+        assert_eq!(
+            resp.headers().get("RateLimit-Remaining").unwrap(),
+            remaining
+        );
+    }
+    let resp = test::call_service(&app, test_get("/api/v1/users/1", RoleAdmin).to_request()).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "100");
+    assert_eq!(resp.headers().get("RateLimit-Remaining").unwrap(), "0");
+    assert_eq!(resp.headers().get("RateLimit-Reset").unwrap(), "200");
+
+    // another user is not affected
+    let resp =
+        test::call_service(&app, test_get("/api/v1/users/1", RoleManager).to_request()).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_rate_limit_overrides() {
+    let limit = BucketLimit {
+        burst: 1,
+        per_second: 0.01,
+    };
+    let mut config = config_with_limits(limit, limit);
+    config.rate_limit_config.overrides.insert(
+        "user:1".to_string(),
+        BucketLimit {
+            burst: 3,
+            per_second: 0.01,
+        },
+    );
+    let app = init_test_service_with_config(config).await;
+    for _ in 0..3 {
+        let resp =
+            test::call_service(&app, test_get("/api/v1/users/1", RoleAdmin).to_request()).await;
+        assert_eq!(resp.status(), 200);
+    }
+    let resp = test::call_service(&app, test_get("/api/v1/users/1", RoleAdmin).to_request()).await;
+    assert_eq!(resp.status(), 429);
+}
+
+#[actix_web::test]
+async fn test_srv_rate_limit_per_service() {
+    let limit = BucketLimit {
+        burst: 1,
+        per_second: 0.5,
+    };
+    let app = init_test_service_with_config(config_with_limits(limit, limit)).await;
+    let validate = || {
+        test::TestRequest::post()
+            .insert_header(TEST_SERVICE_BASIC_AUTH_HEADER)
+            .uri("/srv/v1/validate_jwt")
+            .set_json(json!({ "jwt_token": create_test_jwt() }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, validate()).await;
+    assert_eq!(resp.status(), 200);
+    let resp = test::call_service(&app, validate()).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "2");
+}
+
+#[actix_web::test]
+async fn test_rate_limit_config_errors() {
+    let env: HashMap<String, String> = [
+        ("PG_USER", "postgres"),
+        ("PG_PASSWORD", "dbpass"),
+        ("PG_HOST", "localhost"),
+        ("PG_DBNAME", "db"),
+        ("SECRET_KEY", "some-secret"),
+        ("RATE_LIMIT_API_PER_SECOND", "0"),
+        ("RATE_LIMIT_SRV_BURST", "many"),
+        ("RATE_LIMIT_OVERRIDES", "user:1=10:5,billing=10:5"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    let errors = match Config::from_values(HashMap::new(), env) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    let invalid_keys: Vec<&str> = errors
+        .iter()
+        .filter_map(|error| match error {
+            ConfigFieldError::InvalidValue { key, .. } => Some(key.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        invalid_keys,
+        vec![
+            "rate_limit.api_per_second",
+            "rate_limit.srv_burst",
+            "rate_limit.overrides"
+        ]
+    );
+}
@@ -1,6 +1,8 @@
+#![allow(clippy::unnecessary_mut_passed, clippy::bool_assert_comparison)]
+
 use actix_web::test;
 use authust::handlers::api::roles::views::{
-    RoleMemberBindingView, RolePermissionBindingView, RoleView,
+    RoleListingView, RoleMemberBindingView, RolePermissionBindingView, RoleView,
 };
 use serde_json::json;
 
@@ -8,7 +10,6 @@ mod utils;
 use utils::{
     init_test_service, test_delete, test_get, test_post, test_put, IntenalRoles::RoleAdmin,
 };
-mod constants;
 
 #[actix_web::test]
 async fn test_get_role() {
@@ -218,3 +219,95 @@ async fn test_unbind_member_with_role() {
     assert_eq!(binding.is_deleted, true);
     assert_ne!(binding.created_at, binding.updated_at);
 }
+
+struct RolesFiltersTestCase<'a> {
+    url: &'a str,
+    role_ids: Vec<i32>,
+    total: i64,
+}
+
+#[actix_web::test]
+async fn test_get_roles_listing() {
+    let app = init_test_service().await;
+    let test_cases = [
+        RolesFiltersTestCase {
+            url: "/api/v1/roles",
+            role_ids: vec![6, 5, 4, 3, 2, 1],
+            total: 6,
+        },
+        RolesFiltersTestCase {
+            url: "/api/v1/roles?offset=1&limit=2",
+            role_ids: vec![5, 4],
+            total: 6,
+        },
+        RolesFiltersTestCase {
+            url: "/api/v1/roles?role_name=auth",
+            role_ids: vec![3, 2, 1],
+            total: 3,
+        },
+        // binding of user 3 with role 1 is deleted
+        RolesFiltersTestCase {
+            url: "/api/v1/roles?user_id=3",
+            role_ids: vec![4, 3, 2],
+            total: 3,
+        },
+        RolesFiltersTestCase {
+            url: "/api/v1/roles?permission_id=11&is_deleted=false",
+            role_ids: vec![4],
+            total: 1,
+        },
+        RolesFiltersTestCase {
+            url: "/api/v1/roles?is_deleted=true",
+            role_ids: vec![6],
+            total: 1,
+        },
+    ];
+    for test_case in test_cases.into_iter() {
+        let req = test_get(test_case.url, RoleAdmin).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let resp_view: RoleListingView = test::read_body_json(resp).await;
+        let role_ids: Vec<i32> = resp_view.roles.iter().map(|r| r.role_id).collect();
+        assert_eq!(role_ids, test_case.role_ids);
+        assert_eq!(resp_view.pagination.total, test_case.total);
+        assert!(resp_view.roles.iter().all(|r| r.members_count.is_none()));
+    }
+}
+
+#[actix_web::test]
+async fn test_get_roles_listing_with_counts() {
+    let app = init_test_service().await;
+    let req = test_get("/api/v1/roles?with_counts=true&offset=1&limit=3", RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let resp_view: RoleListingView = test::read_body_json(resp).await;
+    let counts: Vec<(i32, Option<i64>, Option<i64>)> = resp_view
+        .roles
+        .iter()
+        .map(|r| (r.role_id, r.members_count, r.permissions_count))
+        .collect();
+    // deleted bindings and deleted permissions are not counted
+    assert_eq!(
+        counts,
+        vec![
+            (5, Some(2), Some(2)),
+            (4, Some(3), Some(2)),
+            (3, Some(2), Some(1))
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn test_get_roles_listing_400() {
+    let app = init_test_service().await;
+    let test_cases = [
+        "/api/v1/roles?limit=10000",
+        "/api/v1/roles?offset=-1",
+        "/api/v1/roles?limit=0",
+    ];
+    for url in test_cases {
+        let req = test_get(url, RoleAdmin).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
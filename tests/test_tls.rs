@@ -0,0 +1,178 @@
+use authust::common::config_source::{ConfigError, ConfigFieldError};
+use authust::common::tls::{
+    certificate_identities, create_pg_tls_config, create_server_tls_config, load_root_certs,
+    PgSslMode, ReloadableCertResolver,
+};
+use authust::common::Config;
+use authust::storage::postgres::service_repo::ServiceRepo;
+use authust::usecases::services::errors::ServiceUCError;
+use authust::usecases::services::service_authenticator::authenticate_service_by_certificate;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+
+mod utils;
+use utils::init_test_resources;
+
+const SERVER_CERT: &str = "tests/keys/tls/server.pem";
+const SERVER_KEY: &str = "tests/keys/tls/server.key";
+const OTHER_CERT: &str = "tests/keys/tls/other.pem";
+const OTHER_KEY: &str = "tests/keys/tls/other.key";
+const CA_CERT: &str = "tests/keys/tls/ca.pem";
+const CLIENT_CERT: &str = "tests/keys/tls/client.pem";
+const CLIENT_CA_CERT: &str = "tests/keys/tls/client_ca.pem";
+
+fn current_cert(resolver: &ReloadableCertResolver) -> Vec<u8> {
+    resolver.current().end_entity_cert().unwrap().to_vec()
+}
+
+#[test]
+fn test_cert_resolver_reload() {
+    let dir = std::env::temp_dir().join("authust_test_cert_resolver_reload");
+    fs::create_dir_all(&dir).unwrap();
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    fs::copy(SERVER_CERT, &cert_path).unwrap();
+    fs::copy(SERVER_KEY, &key_path).unwrap();
+    let resolver =
+        ReloadableCertResolver::new(cert_path.to_str().unwrap(), key_path.to_str().unwrap())
+            .unwrap();
+    let first_cert = current_cert(&resolver);
+
+    fs::copy(OTHER_CERT, &cert_path).unwrap();
+    fs::copy(OTHER_KEY, &key_path).unwrap();
+    resolver.reload().unwrap();
+    let second_cert = current_cert(&resolver);
+    assert_ne!(first_cert, second_cert);
+
+    fs::write(&cert_path, "broken").unwrap();
+    assert!(resolver.reload().is_err());
+    assert_eq!(current_cert(&resolver), second_cert);
+}
+
+#[test]
+fn test_pg_tls_config() {
+    assert!(create_pg_tls_config(PgSslMode::Disable, None)
+        .unwrap()
+        .is_none());
+    assert!(create_pg_tls_config(PgSslMode::Require, None)
+        .unwrap()
+        .is_some());
+    assert!(create_pg_tls_config(PgSslMode::VerifyFull, Some(CA_CERT))
+        .unwrap()
+        .is_some());
+    assert!(create_pg_tls_config(PgSslMode::VerifyCa, Some(SERVER_KEY)).is_err());
+}
+
+#[test]
+fn test_tls_config_errors() {
+    let env: HashMap<String, String> = [
+        ("PG_USER", "postgres"),
+        ("PG_PASSWORD", "dbpass"),
+        ("PG_HOST", "localhost"),
+        ("PG_DBNAME", "db"),
+        ("SECRET_KEY", "some-secret"),
+        ("TLS_CERT_PATH", SERVER_CERT),
+        ("TLS_CLIENT_CA_PATH", "tests/keys/tls/not_found.pem"),
+        ("PG_SSLMODE", "verify-full"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    let errors = match Config::from_values(HashMap::new(), env) {
+        Err(ConfigError::ValidationError(errors)) => errors,
+        _ => panic!("expected validation error"),
+    };
+    let missing = |key: &str, env: &str| ConfigFieldError::MissingValue {
+        key: key.to_string(),
+        env: env.to_string(),
+    };
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0], missing("server.tls_key_path", "TLS_KEY_PATH"));
+    assert!(matches!(
+        &errors[1],
+        ConfigFieldError::InvalidValue { key, .. } if key == "server.tls_client_ca_path"
+    ));
+    assert_eq!(
+        errors[2],
+        missing("database.ca_cert_path", "PG_CA_CERT_PATH")
+    );
+}
+
+#[test]
+fn test_server_tls_config_with_client_roots() {
+    let resolver = Arc::new(ReloadableCertResolver::new(SERVER_CERT, SERVER_KEY).unwrap());
+    let client_roots = load_root_certs(CLIENT_CA_CERT).unwrap();
+    assert!(create_server_tls_config(resolver.clone(), Some(client_roots)).is_ok());
+    assert!(create_server_tls_config(resolver, None).is_ok());
+    assert!(load_root_certs(SERVER_KEY).is_err());
+}
+
+#[test]
+fn test_certificate_identities() {
+    let cert = CertificateDer::from_pem_file(CLIENT_CERT).unwrap();
+    assert_eq!(
+        certificate_identities(&cert).unwrap(),
+        vec!["spiffe://authust/test_service", "test_service"]
+    );
+    let cert = CertificateDer::from_pem_file(SERVER_CERT).unwrap();
+    assert_eq!(
+        certificate_identities(&cert).unwrap(),
+        vec!["localhost", "localhost"]
+    );
+    assert!(certificate_identities(b"broken").is_err());
+}
+
+#[actix_web::test]
+async fn test_authenticate_service_by_certificate() {
+    let resources = init_test_resources().await;
+    let service_repo = ServiceRepo::new(resources.db_pool.clone());
+    let cert = CertificateDer::from_pem_file(CLIENT_CERT).unwrap();
+    let identities = certificate_identities(&cert).unwrap();
+    let service = authenticate_service_by_certificate(&service_repo, &identities)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(service.client_id, "test_service");
+    assert_eq!(service.scopes, vec!["VALIDATE_JWT"]);
+
+    for identities in [
+        vec![],
+        vec!["spiffe://authust/unknown".to_string()],
+        vec!["disabled_service".to_string()],
+    ] {
+        match authenticate_service_by_certificate(&service_repo, &identities).await {
+            Err(ServiceUCError::VerificationError) => (),
+            _ => panic!("expected verification error for {:?}", identities),
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_authenticate_service_by_certificate_prefers_san() {
+    let resources = init_test_resources().await;
+    let client = resources.db_pool.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE services SET cert_identity='test_service' WHERE client_id='unscoped_service'",
+            &[],
+        )
+        .await
+        .unwrap();
+    let service_repo = ServiceRepo::new(resources.db_pool.clone());
+    let cert = CertificateDer::from_pem_file(CLIENT_CERT).unwrap();
+    let identities = certificate_identities(&cert).unwrap();
+    // SAN matches test_service, common name matches unscoped_service
+    let service = authenticate_service_by_certificate(&service_repo, &identities)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(service.client_id, "test_service");
+    let service = authenticate_service_by_certificate(&service_repo, &identities[1..])
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(service.client_id, "unscoped_service");
+}
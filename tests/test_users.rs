@@ -1,71 +1,76 @@
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
 use actix_web::test;
+use actix_web_httpauth::headers::authorization::{Authorization, Basic};
 
-use authust::common::SecurityConfig;
+use authust::common::{Config, Resources, SecurityConfig};
+use authust::handlers::api::users::views::UserListingView;
 use authust::usecases::users::crypto::decode_jwt;
 use authust::usecases::users::entities::{SingnedInfo, User};
+use authust::usecases::users::jwt_keys::JwtKey;
 
 use serde_json::json;
 
 mod utils;
+use utils::constants::{TEST_BASIC_AUTH_HEADER, TEST_SERVICE_BASIC_AUTH_HEADER};
 use utils::{
     create_test_jwt, init_test_service, test_delete, test_get, test_post,
     IntenalRoles::{RoleAdmin, RoleStaff},
 };
-mod constants;
-use constants::TEST_BASIC_AUTH_HEADER;
 
 #[actix_web::test]
 async fn test_get_user() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let req = test_get("/api/v1/users/1", RoleStaff).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 200)
 }
 
 #[actix_web::test]
 async fn test_get_user_not_found() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let req = test_get("/api/v1/users/999991", RoleStaff).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
 }
 
 #[actix_web::test]
 async fn test_get_user_wrong_params() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let req = test_get("/api/v1/users/sadf", RoleStaff).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
 }
 
 #[actix_web::test]
 async fn test_delete_user() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
 
     let req = test_get("/api/v1/users/3", RoleAdmin).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 200);
 
     let req = test_delete("/api/v1/users/3", RoleAdmin).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 204);
 
     let req = test_get("/api/v1/users/3", RoleAdmin).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
 }
 
 #[actix_web::test]
 async fn test_delete_user_what_doesnt_exist() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let req = test_delete("/api/v1/users/999", RoleAdmin).to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 204)
 }
 
 #[actix_web::test]
 async fn test_create_new_user() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let request_body = json!({
         "username": "tester",
         "password": "test_pass",
@@ -73,7 +78,7 @@ async fn test_create_new_user() {
     let req = test_post("/api/v1/users", RoleAdmin)
         .set_json(request_body)
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let status = resp.status();
     assert_eq!(status, 201);
     let user: User = test::read_body_json(resp).await;
@@ -90,54 +95,448 @@ async fn test_sign_in_forbidden() {
         ("Authorization", "asd"),
         ("Authorization", ""),
     ];
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     for wrong_header in wrong_headers {
         let req = test::TestRequest::post()
             .insert_header(wrong_header)
             .uri("/auth/v1/users/sign_in")
             .to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), 403)
     }
 }
 
 #[actix_web::test]
 async fn test_sign_in() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let req = test::TestRequest::post()
         .insert_header(TEST_BASIC_AUTH_HEADER)
         .uri("/auth/v1/users/sign_in")
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let status = resp.status();
     assert_eq!(status, 200);
     let signed_info: SingnedInfo = test::read_body_json(resp).await;
     let conf = SecurityConfig {
-        secret_key: String::from("some-secret"),
-        expired_jwt_days: 14,
+        jwt_keyring: JwtKey::from_secret("default", "some-secret").into(),
+        expired_access_token_minutes: 15,
+        expired_refresh_token_days: 14,
+        jwt_issuer: String::from("authust"),
+        jwt_audience: String::from("authust"),
+        jwt_leeway_seconds: 30,
+        revocation_cache_seconds: 10,
     };
     let claims = decode_jwt(&conf, &signed_info.jwt_token).unwrap();
     assert_eq!(claims.user_id, 2);
+    assert_eq!(claims.sub, "2");
+    assert_eq!(claims.iss, "authust");
+    assert_eq!(claims.aud, "authust");
+    assert_eq!(claims.exp - claims.iat, 15 * 60);
     assert_eq!(
         claims.permissions,
         vec!["ROLE_AUTH_MANAGER", "ROLE_AUTH_STAFF", "ROLE_1", "ROLE_2"]
     );
 }
 
+#[actix_web::test]
+async fn test_sign_in_created_user() {
+    let app = init_test_service().await;
+    let request_body = json!({
+        "username": "tester",
+        "password": "test_pass",
+    });
+    let req = test_post("/api/v1/users", RoleAdmin)
+        .set_json(request_body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let user: User = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::post()
+        .insert_header(Authorization::from(Basic::new(
+            "tester",
+            Some("wrong_pass"),
+        )))
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    let req = test::TestRequest::post()
+        .insert_header(Authorization::from(Basic::new("tester", Some("test_pass"))))
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let signed_info: SingnedInfo = test::read_body_json(resp).await;
+    assert_eq!(signed_info.user_id, user.user_id);
+}
+
+async fn sign_in_test_user(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+) -> SingnedInfo {
+    let req = test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), 200);
+    test::read_body_json(resp).await
+}
+
+async fn refresh(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+    refresh_token: &str,
+) -> ServiceResponse<BoxBody> {
+    let req = test::TestRequest::post()
+        .uri("/auth/v1/token/refresh")
+        .set_json(json!({ "refresh_token": refresh_token }))
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[actix_web::test]
+async fn test_refresh_token() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    assert!(!signed_info.refresh_token.is_empty());
+
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 200);
+    let refreshed_info: SingnedInfo = test::read_body_json(resp).await;
+    assert_eq!(refreshed_info.user_id, signed_info.user_id);
+    assert_ne!(refreshed_info.refresh_token, signed_info.refresh_token);
+
+    let resp = refresh(&app, &refreshed_info.refresh_token).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_refresh_token_reuse_revokes_family() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    let another_session = sign_in_test_user(&app).await;
+
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 200);
+    let refreshed_info: SingnedInfo = test::read_body_json(resp).await;
+
+    // already rotated token is presented again
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    // the whole family is revoked
+    let resp = refresh(&app, &refreshed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    // other sessions are untouched
+    let resp = refresh(&app, &another_session.refresh_token).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_refresh_token_wrong_token() {
+    let app = init_test_service().await;
+    let resp = refresh(&app, "wrong_token").await;
+    assert_eq!(resp.status(), 403);
+}
+
+async fn validate(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+    jwt_token: &str,
+) -> u16 {
+    let req = test::TestRequest::post()
+        .insert_header(TEST_SERVICE_BASIC_AUTH_HEADER)
+        .uri("/srv/v1/validate_jwt")
+        .set_json(json!({ "jwt_token": jwt_token }))
+        .to_request();
+    test::call_service(app, req).await.status().as_u16()
+}
+
+#[actix_web::test]
+async fn test_sign_out() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    let another_session = sign_in_test_user(&app).await;
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 200);
+
+    let req = test::TestRequest::post()
+        .insert_header(("Authorization", format!("Bearer {}", signed_info.jwt_token)))
+        .uri("/auth/v1/sign_out")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    // other sessions are untouched
+    assert_eq!(validate(&app, &another_session.jwt_token).await, 200);
+}
+
+#[actix_web::test]
+async fn test_sign_out_deletes_expired_revocations() {
+    let app = init_test_service().await;
+    let resources = Resources::create_resources(&Config::create_config()).await;
+    let client = resources.db_pool.get().await.unwrap();
+    client
+        .execute(
+            "INSERT INTO revoked_tokens (jti, user_id, expired_at, created_at)
+            VALUES ('expired', 2, now() - interval '1 day', now() - interval '2 days')",
+            &[],
+        )
+        .await
+        .unwrap();
+    let signed_info = sign_in_test_user(&app).await;
+
+    let req = test::TestRequest::post()
+        .insert_header(("Authorization", format!("Bearer {}", signed_info.jwt_token)))
+        .uri("/auth/v1/sign_out")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let rows = client
+        .query("SELECT jti FROM revoked_tokens", &[])
+        .await
+        .unwrap();
+    let jti: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    assert_eq!(jti.len(), 1);
+    assert_ne!(jti[0], "expired");
+}
+
+#[actix_web::test]
+async fn test_sign_out_wrong_token() {
+    let app = init_test_service().await;
+    let req = test::TestRequest::post()
+        .insert_header(("Authorization", "Bearer wrong_token"))
+        .uri("/auth/v1/sign_out")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_revoke_user_sessions() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    let user_id = signed_info.user_id;
+
+    let url = format!("/api/v1/users/{}/revoke_sessions", user_id);
+    let req = test_post(&url, RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    // bearer_validator rejects tokens of the user too
+    let req = test::TestRequest::get()
+        .insert_header(("Authorization", format!("Bearer {}", signed_info.jwt_token)))
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+
+    // new sign in works as usual
+    let signed_info = sign_in_test_user(&app).await;
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 200);
+}
+
+#[actix_web::test]
+async fn test_revoke_user_sessions_not_found() {
+    let app = init_test_service().await;
+    let req = test_post("/api/v1/users/9999/revoke_sessions", RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_disable_user() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    let user_id = signed_info.user_id;
+
+    let req = test_post(&format!("/api/v1/users/{}/disable", user_id), RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let user: User = test::read_body_json(resp).await;
+    assert!(!user.enabled);
+
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    let req = test::TestRequest::get()
+        .insert_header(("Authorization", format!("Bearer {}", signed_info.jwt_token)))
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+    let req = test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    let req = test_post(&format!("/api/v1/users/{}/enable", user_id), RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let user: User = test::read_body_json(resp).await;
+    assert!(user.enabled);
+    // tokens issued before disabling stay revoked
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let signed_info = sign_in_test_user(&app).await;
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 200);
+}
+
+#[actix_web::test]
+async fn test_disable_user_not_found() {
+    let app = init_test_service().await;
+    for action in ["enable", "disable"] {
+        let url = format!("/api/v1/users/9999/{}", action);
+        let req = test_post(&url, RoleAdmin).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+    }
+}
+
+#[actix_web::test]
+async fn test_deleted_user_tokens() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+
+    let url = format!("/api/v1/users/{}", signed_info.user_id);
+    let req = test_delete(&url, RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let resp = refresh(&app, &signed_info.refresh_token).await;
+    assert_eq!(resp.status(), 403);
+    let req = test::TestRequest::post()
+        .insert_header(TEST_BASIC_AUTH_HEADER)
+        .uri("/auth/v1/users/sign_in")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+}
+
+struct UsersFiltersTestCase {
+    url: &'static str,
+    usernames: Vec<&'static str>,
+    total: i64,
+}
+
+#[actix_web::test]
+async fn test_get_users_listing() {
+    let app = init_test_service().await;
+    let test_cases = [
+        UsersFiltersTestCase {
+            url: "/api/v1/users",
+            usernames: vec!["Godzilla", "test_user", "Ivan"],
+            total: 3,
+        },
+        UsersFiltersTestCase {
+            url: "/api/v1/users?offset=1&limit=1",
+            usernames: vec!["test_user"],
+            total: 3,
+        },
+        UsersFiltersTestCase {
+            url: "/api/v1/users?username=test",
+            usernames: vec!["test_user"],
+            total: 1,
+        },
+        UsersFiltersTestCase {
+            url: "/api/v1/users?role_id=5&enabled=true&is_deleted=false",
+            usernames: vec!["test_user", "Ivan"],
+            total: 2,
+        },
+        // binding of Godzilla with role 1 is deleted
+        UsersFiltersTestCase {
+            url: "/api/v1/users?role_id=1",
+            usernames: vec!["Ivan"],
+            total: 1,
+        },
+        UsersFiltersTestCase {
+            url: "/api/v1/users?enabled=false",
+            usernames: vec![],
+            total: 0,
+        },
+    ];
+    for test_case in test_cases.into_iter() {
+        let req = test_get(test_case.url, RoleStaff).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let resp_view: UserListingView = test::read_body_json(resp).await;
+        let usernames: Vec<String> = resp_view.users.into_iter().map(|u| u.username).collect();
+        assert_eq!(usernames, test_case.usernames);
+        assert_eq!(resp_view.pagination.total, test_case.total);
+    }
+
+    let req = test_delete("/api/v1/users/2", RoleAdmin).to_request();
+    test::call_service(&app, req).await;
+    let req = test_get("/api/v1/users?is_deleted=true", RoleAdmin).to_request();
+    let resp_view: UserListingView = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp_view.users[0].username, "test_user");
+    assert_eq!(resp_view.pagination.total, 1);
+}
+
+#[actix_web::test]
+async fn test_get_users_listing_400() {
+    let app = init_test_service().await;
+    let test_cases = [
+        "/api/v1/users?limit=10000",
+        "/api/v1/users?offset=-1",
+        "/api/v1/users?limit=0",
+    ];
+    for url in test_cases {
+        let req = test_get(url, RoleAdmin).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
+
+#[actix_web::test]
+async fn test_delete_user_revokes_cached_sessions() {
+    let app = init_test_service().await;
+    let signed_info = sign_in_test_user(&app).await;
+    // token version of the user gets cached
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 200);
+
+    let url = format!("/api/v1/users/{}", signed_info.user_id);
+    let req = test_delete(&url, RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    assert_eq!(validate(&app, &signed_info.jwt_token).await, 404);
+    let req = test::TestRequest::get()
+        .insert_header(("Authorization", format!("Bearer {}", signed_info.jwt_token)))
+        .uri("/api/v1/users/1")
+        .to_request();
+    let err = app.call(req).await.err().unwrap();
+    assert_eq!(err.as_response_error().status_code(), 401);
+
+    // deleted user has no sessions to revoke
+    let url = format!("/api/v1/users/{}/revoke_sessions", signed_info.user_id);
+    let req = test_post(&url, RoleAdmin).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
 #[actix_web::test]
 async fn test_validate_jwt() {
     // create_test_jwt create fake perms in payload
     // test checks that /srv/v1/validate_jwt get perms from db by user_id
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let jwt = create_test_jwt();
     let request_body = json!({
         "jwt_token": jwt,
     });
     let req = test::TestRequest::post()
+        .insert_header(TEST_SERVICE_BASIC_AUTH_HEADER)
         .uri("/srv/v1/validate_jwt")
         .set_json(request_body)
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let status = resp.status();
     assert_eq!(status, 200);
     let permissions: Vec<String> = test::read_body_json(resp).await;
@@ -163,15 +562,48 @@ async fn test_validate_jwt() {
 
 #[actix_web::test]
 async fn test_validate_jwt_wrong_token() {
-    let mut app = init_test_service().await;
+    let app = init_test_service().await;
     let request_body = json!({
         "jwt_token": "wrong_token",
     });
     let req = test::TestRequest::post()
+        .insert_header(TEST_SERVICE_BASIC_AUTH_HEADER)
         .uri("/srv/v1/validate_jwt")
         .set_json(request_body)
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     let status = resp.status();
     assert_eq!(status, 404);
 }
+
+#[actix_web::test]
+async fn test_validate_jwt_service_credentials() {
+    let app = init_test_service().await;
+    let jwt = create_test_jwt();
+    let cases = [
+        (None, 401),
+        (Some("Basic dGVzdF9zZXJ2aWNlOndyb25nLXNlY3JldA=="), 401), // wrong secret
+        (
+            Some("Basic ZGlzYWJsZWRfc2VydmljZTpzZXJ2aWNlLXNlY3JldA=="),
+            401,
+        ), // disabled service
+        (
+            Some("Basic dW5zY29wZWRfc2VydmljZTpzZXJ2aWNlLXNlY3JldA=="),
+            403,
+        ), // without scope
+        (Some(TEST_SERVICE_BASIC_AUTH_HEADER.1), 200),
+    ];
+    for (auth_header, expected_status) in cases {
+        let mut req = test::TestRequest::post()
+            .uri("/srv/v1/validate_jwt")
+            .set_json(json!({ "jwt_token": jwt }));
+        if let Some(auth_header) = auth_header {
+            req = req.insert_header(("Authorization", auth_header));
+        }
+        let status = match app.call(req.to_request()).await {
+            Ok(resp) => resp.status(),
+            Err(err) => err.as_response_error().status_code(),
+        };
+        assert_eq!(status, expected_status);
+    }
+}
@@ -0,0 +1,18 @@
+use actix_web::test;
+use serde_json::Value;
+
+mod utils;
+use utils::init_test_service;
+
+#[actix_web::test]
+async fn test_jwks() {
+    let app = init_test_service().await;
+    let req = test::TestRequest::get()
+        .uri("/.well-known/jwks.json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let jwks: Value = test::read_body_json(resp).await;
+    // shared HS256 secret from the test environment is never published
+    assert_eq!(jwks["keys"].as_array().unwrap().len(), 0);
+}
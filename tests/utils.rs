@@ -1,19 +1,26 @@
 use actix_http::Request;
 use actix_web::body::BoxBody;
 use actix_web::dev::{Service, ServiceResponse};
+use actix_web::middleware::from_fn;
 use actix_web::{http::header, test, web, App};
+use actix_web_grants::GrantsMiddleware;
 use actix_web_httpauth::middleware::HttpAuthentication;
 
-use authust::apps::{init_api_v1, init_external_v1, init_internal_v1, init_system};
+use authust::apps::{
+    init_api_v1, init_external_v1, init_internal_v1, init_system, init_well_known,
+};
 use authust::common::{Config, Resources};
-use authust::middlewares::bearer_validator;
-use authust::usecases::users::crypto::generate_jwt;
+use authust::middlewares::{
+    api_rate_limiter, bearer_validator, service_permissions_extractor, srv_rate_limiter,
+};
+use authust::storage::postgres::migrations::migrate_up;
+use authust::usecases::users::crypto::{generate_jwt, generate_random_token};
+use authust::usecases::users::entities::TokenSession;
 
-use std::fs;
 use std::str::FromStr;
 
 #[path = "./constants.rs"]
-mod constants;
+pub mod constants;
 
 const USERS_FIXTURE: &str = "INSERT INTO users 
     (username, password_hash, enabled, created_at, updated_at, is_deleted)
@@ -57,6 +64,20 @@ const ROLE_MEMBERS_BINDING_FUXTURE: &str =
     (3, 1, now(), now(), TRUE),
     (3, 2, now(), now(), FALSE)";
 
+const SERVICES_FIXTURE: &str =
+    "INSERT INTO services (client_id, secret_hash, cert_identity, enabled, created_at, updated_at, is_deleted)
+    VALUES 
+    ('test_service', encode(sha256('service-secret'), 'hex'), 'spiffe://authust/test_service', TRUE, now(), now(), FALSE),
+    ('unscoped_service', encode(sha256('service-secret'), 'hex'), NULL, TRUE, now(), now(), FALSE),
+    ('disabled_service', encode(sha256('service-secret'), 'hex'), 'disabled_service', FALSE, now(), now(), FALSE)";
+const SERVICE_SCOPES_FIXTURE: &str =
+    "INSERT INTO service_scopes (service_id, scope, created_at, updated_at, is_deleted)
+    VALUES 
+    (1, 'VALIDATE_JWT', now(), now(), FALSE),
+    (2, 'VALIDATE_JWT', now(), now(), TRUE),
+    (3, 'VALIDATE_JWT', now(), now(), FALSE)";
+
+#[allow(dead_code, clippy::enum_variant_names)]
 pub enum IntenalRoles {
     RoleAdmin,
     RoleManager,
@@ -67,31 +88,43 @@ async fn refresh_db(resources: &Resources) -> () {
     let client = resources.db_pool.get().await.unwrap();
 
     client
-    .simple_query("DROP TABLE IF EXISTS users, permissions, roles, role_permissions, role_members CASCADE")
+    .simple_query("DROP TABLE IF EXISTS users, permissions, roles, role_permissions, role_members, refresh_tokens, revoked_tokens, services, service_scopes, failed_sign_in_attempts, user_totp, recovery_codes, mfa_challenges, oauth_clients, oauth_client_roles, authorization_codes, user_identities, federated_logins, api_keys, password_reset_tokens, schema_migrations CASCADE")
     .await
     .unwrap();
 
-    let migration_paths = fs::read_dir("./tests/migrations").unwrap();
-    for path in migration_paths {
-        let filename = path.unwrap().path().display().to_string();
-        let query = &fs::read_to_string(&filename).unwrap();
-        client.batch_execute(query).await.unwrap();
-    }
+    migrate_up(&resources.db_pool).await.unwrap();
     let fixtures_queries = [
         USERS_FIXTURE,
         PERMISSIONS_FIXTURE,
         ROLES_FUXTURE,
         ROLE_MEMBERS_BINDING_FUXTURE,
         ROLE_PERMISSIONS_BINDING_FUXTURE,
+        SERVICES_FIXTURE,
+        SERVICE_SCOPES_FIXTURE,
     ];
     for query in fixtures_queries.into_iter() {
         client.simple_query(query).await.unwrap();
     }
 }
 
+#[allow(dead_code)]
+pub async fn init_test_resources() -> Resources {
+    let config = Config::create_config();
+    let resources = Resources::create_resources(&config).await;
+    refresh_db(&resources).await;
+    resources
+}
+
+#[allow(dead_code)]
 pub async fn init_test_service(
 ) -> impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error> {
-    let config = Config::create_config();
+    init_test_service_with_config(Config::create_config()).await
+}
+
+#[allow(dead_code)]
+pub async fn init_test_service_with_config(
+    config: Config,
+) -> impl Service<Request, Response = ServiceResponse<BoxBody>, Error = actix_web::Error> {
     let resources = Resources::create_resources(&config).await;
     refresh_db(&resources).await;
     let auth = HttpAuthentication::bearer(bearer_validator);
@@ -99,9 +132,22 @@ pub async fn init_test_service(
         App::new()
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(resources.clone()))
-            .service(web::scope("api/v1").configure(init_api_v1).wrap(auth))
-            .service(web::scope("srv/v1").configure(init_internal_v1))
+            .service(
+                web::scope("api/v1")
+                    .configure(init_api_v1)
+                    .wrap(from_fn(api_rate_limiter))
+                    .wrap(auth),
+            )
+            .service(
+                web::scope("srv/v1")
+                    .configure(init_internal_v1)
+                    .wrap(from_fn(srv_rate_limiter))
+                    .wrap(GrantsMiddleware::with_extractor(
+                        service_permissions_extractor,
+                    )),
+            )
             .service(web::scope("auth/v1").configure(init_external_v1))
+            .service(web::scope(".well-known").configure(init_well_known))
             .service(web::scope("").configure(init_system)),
     )
     .await
@@ -111,23 +157,30 @@ use actix_web::http::header::{HeaderName, HeaderValue};
 #[allow(dead_code)]
 pub fn create_test_jwt() -> String {
     let config = Config::create_config().security_config;
-    let jwt = generate_jwt(
+    generate_jwt(
         &config,
         constants::TEST_USER_ID_ADMIN,
         vec!["fake".to_string()],
+        test_session(),
     )
-    .expect("can not create jwt for tests");
-    jwt
+    .expect("can not create jwt for tests")
 }
 
-fn create_bearer_header<'a>(role: IntenalRoles) -> (HeaderName, HeaderValue) {
+pub fn test_session() -> TokenSession {
+    TokenSession {
+        session_id: generate_random_token(16),
+        token_version: 0,
+    }
+}
+
+fn create_bearer_header(role: IntenalRoles) -> (HeaderName, HeaderValue) {
     let config = Config::create_config().security_config;
     let user_id = match role {
         IntenalRoles::RoleAdmin => constants::TEST_USER_ID_ADMIN,
         IntenalRoles::RoleManager => constants::TEST_USER_ID_MANAGER,
         IntenalRoles::RoleStaff => constants::TEST_USER_ID_STAFF,
     };
-    let jwt = generate_jwt(&config, user_id, vec!["fake".to_string()])
+    let jwt = generate_jwt(&config, user_id, vec!["fake".to_string()], test_session())
         .expect("can not create jwt for tests");
     let token = format!("Bearer {}", jwt);
     (
@@ -136,6 +189,7 @@ fn create_bearer_header<'a>(role: IntenalRoles) -> (HeaderName, HeaderValue) {
     )
 }
 
+#[allow(dead_code)]
 pub fn test_post(url: &str, role: IntenalRoles) -> test::TestRequest {
     test::TestRequest::post()
         .insert_header(header::ContentType::json())
@@ -143,12 +197,14 @@ pub fn test_post(url: &str, role: IntenalRoles) -> test::TestRequest {
         .uri(url)
 }
 
+#[allow(dead_code)]
 pub fn test_get(url: &str, role: IntenalRoles) -> test::TestRequest {
     test::TestRequest::get()
         .insert_header(create_bearer_header(role))
         .uri(url)
 }
 
+#[allow(dead_code)]
 pub fn test_delete(url: &str, role: IntenalRoles) -> test::TestRequest {
     test::TestRequest::delete()
         .insert_header(create_bearer_header(role))